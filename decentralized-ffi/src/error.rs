@@ -14,6 +14,7 @@ use bdk_wallet::bitcoin::psbt::ExtractTxError as BdkExtractTxError;
 use bdk_wallet::bitcoin::psbt::PsbtParseError as BdkPsbtParseError;
 use bdk_wallet::bitcoin::script::PushBytesError;
 use bdk_wallet::chain::local_chain::CannotConnectError as BdkCannotConnectError;
+#[cfg(feature = "sqlite")]
 use bdk_wallet::chain::rusqlite::Error as BdkSqliteError;
 use bdk_wallet::chain::tx_graph::CalculateFeeError as BdkCalculateFeeError;
 use bdk_wallet::descriptor::DescriptorError as BdkDescriptorError;
@@ -408,6 +409,18 @@ pub enum FromScriptError {
     OtherFromScriptErr,
 }
 
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum LabelError {
+    #[error("invalid label on line {line}: {error_message}")]
+    InvalidRecord { line: u32, error_message: String },
+
+    #[error("invalid {label_type} label reference: {reference}")]
+    InvalidReference {
+        label_type: String,
+        reference: String,
+    },
+}
+
 #[derive(Debug, thiserror::Error, uniffi::Error)]
 pub enum RequestBuilderError {
     #[error("the request has already been consumed")]
@@ -566,6 +579,15 @@ pub enum ParseAmountError {
 pub enum PersistenceError {
     #[error("persistence error: {error_message}")]
     Reason { error_message: String },
+
+    #[error("wrong encryption key")]
+    WrongKey,
+
+    #[error("invalid encryption key: {error_message}")]
+    InvalidKey { error_message: String },
+
+    #[error("the persister is not encrypted")]
+    NotEncrypted,
 }
 
 #[derive(Debug, thiserror::Error, uniffi::Error)]
@@ -776,12 +798,14 @@ pub enum TxidParseError {
     InvalidTxid { txid: String },
 }
 
+#[cfg(feature = "kyoto")]
 #[derive(Debug, thiserror::Error, uniffi::Error)]
 pub enum CbfBuilderError {
     #[error("the database could not be opened or created: {reason}")]
     DatabaseError { reason: String },
 }
 
+#[cfg(feature = "kyoto")]
 #[derive(Debug, thiserror::Error, uniffi::Error)]
 pub enum CbfError {
     #[error("the node is no longer running")]
@@ -1002,6 +1026,7 @@ impl From<PushBytesError> for CreateTxError {
     }
 }
 
+#[cfg(feature = "sqlite")]
 impl From<BdkCreateWithPersistError<chain::rusqlite::Error>> for CreateWithPersistError {
     fn from(error: BdkCreateWithPersistError<chain::rusqlite::Error>) -> Self {
         match error {
@@ -1233,6 +1258,7 @@ impl From<BdkFromScriptError> for FromScriptError {
     }
 }
 
+#[cfg(feature = "sqlite")]
 impl From<BdkLoadWithPersistError<chain::rusqlite::Error>> for LoadWithPersistError {
     fn from(error: BdkLoadWithPersistError<chain::rusqlite::Error>) -> Self {
         match error {
@@ -1248,6 +1274,7 @@ impl From<BdkLoadWithPersistError<chain::rusqlite::Error>> for LoadWithPersistEr
     }
 }
 
+#[cfg(feature = "sqlite")]
 impl From<BdkSqliteError> for PersistenceError {
     fn from(error: BdkSqliteError) -> Self {
         PersistenceError::Reason {
@@ -1521,6 +1548,7 @@ pub enum HashParseError {
     InvalidHexString { hex: String },
 }
 
+#[cfg(feature = "kyoto")]
 impl From<bdk_kyoto::builder::SqlInitializationError> for CbfBuilderError {
     fn from(value: bdk_kyoto::builder::SqlInitializationError) -> Self {
         CbfBuilderError::DatabaseError {
@@ -1529,6 +1557,7 @@ impl From<bdk_kyoto::builder::SqlInitializationError> for CbfBuilderError {
     }
 }
 
+#[cfg(feature = "kyoto")]
 impl From<bdk_kyoto::kyoto::ClientError> for CbfError {
     fn from(_value: bdk_kyoto::kyoto::ClientError) -> Self {
         CbfError::NodeStopped
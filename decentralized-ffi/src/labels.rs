@@ -0,0 +1,454 @@
+use crate::error::LabelError;
+
+use bdk_wallet::bitcoin::address::NetworkUnchecked;
+use bdk_wallet::bitcoin::bip32::Xpub;
+use bdk_wallet::bitcoin::{Address, OutPoint, PublicKey, Txid};
+use bdk_wallet::serde_json;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// What a [BIP-329](https://github.com/bitcoin/bips/blob/master/bip-0329.mediawiki) label
+/// refers to.
+#[derive(
+    uniffi::Enum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelType {
+    /// A transaction, referenced by its txid.
+    Tx,
+    /// An address.
+    Addr,
+    /// A public key, referenced by its hex encoding.
+    Pubkey,
+    /// A transaction input, referenced by `txid:vin`.
+    Input,
+    /// A transaction output, referenced by `txid:vout`.
+    Output,
+    /// An extended public key.
+    Xpub,
+}
+
+impl LabelType {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            LabelType::Tx => "tx",
+            LabelType::Addr => "addr",
+            LabelType::Pubkey => "pubkey",
+            LabelType::Input => "input",
+            LabelType::Output => "output",
+            LabelType::Xpub => "xpub",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        [
+            LabelType::Tx,
+            LabelType::Addr,
+            LabelType::Pubkey,
+            LabelType::Input,
+            LabelType::Output,
+            LabelType::Xpub,
+        ]
+        .into_iter()
+        .find(|label_type| label_type.as_str() == name)
+    }
+}
+
+impl Display for LabelType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A BIP-329 label record.
+///
+/// A label without `label`, `origin` and `spendable` carries nothing and removes the label it
+/// replaces.
+#[derive(uniffi::Record, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Label {
+    #[serde(rename = "type")]
+    pub label_type: LabelType,
+    #[serde(rename = "ref")]
+    pub reference: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Key origin of the descriptor the reference belongs to, e.g. `wpkh([d34db33f/84'/0'/0'])`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Whether an output may be spent, only kept for [`LabelType::Output`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spendable: Option<bool>,
+}
+
+impl Label {
+    /// Check the reference against the label type and bring it into its canonical form, so the
+    /// same txid, address or outpoint always maps to the same label.
+    pub(crate) fn normalize(mut self) -> Result<Self, LabelError> {
+        self.reference = normalize_reference(self.label_type, &self.reference)?;
+        if self.label_type != LabelType::Output {
+            self.spendable = None;
+        }
+        Ok(self)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.label.is_none() && self.origin.is_none() && self.spendable.is_none()
+    }
+
+    fn key(&self) -> LabelKey {
+        (self.label_type, self.reference.clone())
+    }
+}
+
+pub(crate) fn normalize_reference(
+    label_type: LabelType,
+    reference: &str,
+) -> Result<String, LabelError> {
+    let invalid = || LabelError::InvalidReference {
+        label_type: label_type.to_string(),
+        reference: reference.to_string(),
+    };
+    Ok(match label_type {
+        LabelType::Tx => Txid::from_str(reference)
+            .map_err(|_| invalid())?
+            .to_string(),
+        LabelType::Addr => Address::<NetworkUnchecked>::from_str(reference)
+            .map_err(|_| invalid())?
+            .assume_checked()
+            .to_string(),
+        LabelType::Pubkey => PublicKey::from_str(reference)
+            .map_err(|_| invalid())?
+            .to_string(),
+        LabelType::Input | LabelType::Output => OutPoint::from_str(reference)
+            .map_err(|_| invalid())?
+            .to_string(),
+        LabelType::Xpub => Xpub::from_str(reference)
+            .map_err(|_| invalid())?
+            .to_string(),
+    })
+}
+
+type LabelKey = (LabelType, String);
+
+/// Labels of a wallet together with the changes not persisted yet.
+#[derive(Debug, Default)]
+pub(crate) struct Labels {
+    labels: BTreeMap<LabelKey, Label>,
+    stage: BTreeMap<LabelKey, Label>,
+}
+
+impl Labels {
+    /// Labels from an aggregate changeset, later records replace earlier ones.
+    pub(crate) fn from_changeset(changeset: Vec<Label>) -> Self {
+        let mut labels = BTreeMap::new();
+        for label in changeset {
+            if label.is_empty() {
+                labels.remove(&label.key());
+            } else {
+                labels.insert(label.key(), label);
+            }
+        }
+        Self {
+            labels,
+            stage: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn get(&self, label_type: LabelType, reference: &str) -> Option<&Label> {
+        self.labels.get(&(label_type, reference.to_string()))
+    }
+
+    /// The text of the label of `label_type` for `reference`, if any.
+    pub(crate) fn text(&self, label_type: LabelType, reference: &str) -> Option<String> {
+        self.get(label_type, reference)
+            .and_then(|label| label.label.clone())
+    }
+
+    /// Replace the label with the same type and reference and stage the change. `label` must be
+    /// normalized.
+    pub(crate) fn insert(&mut self, label: Label) {
+        let key = label.key();
+        if label.is_empty() {
+            if self.labels.remove(&key).is_none() {
+                return;
+            }
+        } else {
+            self.labels.insert(key.clone(), label.clone());
+        }
+        self.stage.insert(key, label);
+    }
+
+    /// Outputs labeled as not spendable.
+    pub(crate) fn frozen(&self) -> BTreeSet<OutPoint> {
+        self.iter()
+            .filter(|label| {
+                label.label_type == LabelType::Output && label.spendable == Some(false)
+            })
+            .filter_map(|label| OutPoint::from_str(&label.reference).ok())
+            .collect()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Label> {
+        self.labels.values()
+    }
+
+    pub(crate) fn staged(&self) -> Vec<Label> {
+        self.stage.values().cloned().collect()
+    }
+
+    pub(crate) fn clear_stage(&mut self) {
+        self.stage.clear();
+    }
+
+    /// Export all labels as BIP-329 JSON lines.
+    pub(crate) fn export(&self) -> String {
+        self.iter()
+            .map(|label| serde_json::to_string(label).expect("label serialization") + "\n")
+            .collect()
+    }
+}
+
+/// Merge two label changesets, records of `right` replace those of `left`.
+pub(crate) fn merge_labels(left: Vec<Label>, right: Vec<Label>) -> Vec<Label> {
+    let mut merged = BTreeMap::new();
+    for label in left.into_iter().chain(right) {
+        merged.insert(label.key(), label);
+    }
+    merged.into_values().collect()
+}
+
+/// Parse and normalize BIP-329 JSON lines, blank lines are skipped.
+pub(crate) fn parse_labels(jsonl: &str) -> Result<Vec<Label>, LabelError> {
+    jsonl
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str::<Label>(line)
+                .map_err(|e| LabelError::InvalidRecord {
+                    line: i as u32 + 1,
+                    error_message: e.to_string(),
+                })?
+                .normalize()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MemoryPersistence, wallet_descriptor};
+    use crate::store::Persister;
+    use crate::wallet::Wallet;
+    use bdk_wallet::KeychainKind;
+    use bdk_wallet::bitcoin::Network;
+    use std::sync::Arc;
+
+    const TXID: &str = "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd";
+
+    #[test]
+    fn parse_bip329_records() {
+        let jsonl = format!(
+            r#"{{ "type": "tx", "ref": "{TXID}", "label": "Transaction", "origin": "wpkh([d34db33f/84'/0'/0'])" }}
+{{ "type": "addr", "ref": "BC1Q34AQ5DRPUWY3WGL9LHUP9892QP6SVR8LDZYY7C", "label": "Address" }}
+
+{{ "type": "output", "ref": "{TXID}:1", "label": "Output", "spendable": false, "height": 800000 }}
+{{ "type": "tx", "ref": "{TXID}", "spendable": true }}"#
+        );
+        let labels = parse_labels(&jsonl).unwrap();
+        assert_eq!(labels.len(), 4);
+        assert_eq!(
+            labels[0].origin.as_deref(),
+            Some("wpkh([d34db33f/84'/0'/0'])")
+        );
+        assert_eq!(
+            labels[1].reference,
+            "bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c"
+        );
+        assert_eq!(labels[2].spendable, Some(false));
+        // spendable only applies to outputs
+        assert!(labels[3].is_empty());
+
+        let labels = Labels::from_changeset(labels);
+        assert_eq!(labels.iter().count(), 2);
+        assert_eq!(
+            labels.text(LabelType::Output, &format!("{TXID}:1")),
+            Some("Output".into())
+        );
+        assert_eq!(
+            labels.export(),
+            format!(
+                "{{\"type\":\"addr\",\"ref\":\"bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c\",\"label\":\"Address\"}}\n\
+                 {{\"type\":\"output\",\"ref\":\"{TXID}:1\",\"label\":\"Output\",\"spendable\":false}}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn reject_invalid_records() {
+        assert!(matches!(
+            parse_labels("{\"type\":\"tx\",\"ref\":\"00\"}"),
+            Err(LabelError::InvalidReference { .. })
+        ));
+        assert!(matches!(
+            parse_labels(&format!("\n{{\"type\":\"block\",\"ref\":\"{TXID}\"}}")),
+            Err(LabelError::InvalidRecord { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn stage_and_remove() {
+        let label = |text: Option<&str>| Label {
+            label_type: LabelType::Tx,
+            reference: TXID.into(),
+            label: text.map(Into::into),
+            origin: None,
+            spendable: None,
+        };
+        let mut labels = Labels::default();
+        labels.insert(label(Some("rent")));
+        assert_eq!(labels.staged().len(), 1);
+        labels.clear_stage();
+
+        labels.insert(label(None));
+        assert_eq!(labels.get(LabelType::Tx, TXID), None);
+        assert_eq!(labels.staged(), vec![label(None)]);
+
+        // removing a missing label changes nothing
+        labels.clear_stage();
+        labels.insert(label(None));
+        assert!(labels.staged().is_empty());
+
+        assert_eq!(
+            merge_labels(vec![label(Some("a"))], vec![label(Some("b"))]),
+            vec![label(Some("b"))]
+        );
+    }
+
+    fn persisted_labels_reload(persister: Arc<Persister>) {
+        let wallet = Wallet::new(
+            wallet_descriptor(0),
+            wallet_descriptor(1),
+            Network::Signet,
+            persister.clone(),
+            25,
+        )
+        .unwrap();
+        let address = wallet.reveal_next_address(KeychainKind::External).address;
+        wallet
+            .import_labels(format!(
+                "{{\"type\":\"addr\",\"ref\":\"{address}\",\"label\":\"Deposit\"}}\n\
+                 {{\"type\":\"tx\",\"ref\":\"{TXID}\",\"label\":\"Rent\"}}"
+            ))
+            .unwrap();
+        wallet
+            .remove_label(LabelType::Tx, TXID.to_uppercase())
+            .unwrap();
+        assert!(wallet.persist(persister.clone()).unwrap());
+        assert!(!wallet.persist(persister.clone()).unwrap());
+
+        let wallet = Wallet::load(
+            wallet_descriptor(0),
+            Some(wallet_descriptor(1)),
+            persister,
+        )
+        .unwrap();
+        assert_eq!(wallet.labels().len(), 1);
+        assert_eq!(
+            wallet
+                .get_label(LabelType::Addr, address.to_string())
+                .and_then(|label| label.label),
+            Some("Deposit".into())
+        );
+        assert_eq!(wallet.get_label(LabelType::Tx, TXID.into()), None);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_labels_persist() {
+        persisted_labels_reload(Arc::new(Persister::new_in_memory().unwrap()));
+    }
+
+    #[test]
+    fn custom_labels_persist() {
+        let persistence = Arc::new(MemoryPersistence::default());
+        persisted_labels_reload(Arc::new(Persister::custom(persistence.clone())));
+
+        // labels come from the aggregate the wallet is loaded from
+        let initialized = persistence.initializations();
+        let wallet = Wallet::load(
+            wallet_descriptor(0),
+            Some(wallet_descriptor(1)),
+            Arc::new(Persister::custom(persistence.clone())),
+        )
+        .unwrap();
+        assert_eq!(wallet.labels().len(), 1);
+        assert_eq!(persistence.initializations(), initialized + 1);
+    }
+
+    #[test]
+    fn import_never_removes() {
+        let wallet = crate::testing::funded_wallet(&[]);
+        wallet
+            .import_labels(format!(
+                "{{\"type\":\"tx\",\"ref\":\"{TXID}\",\"label\":\"Rent\"}}"
+            ))
+            .unwrap();
+        let imported = wallet
+            .import_labels(format!(
+                "{{\"type\":\"tx\",\"ref\":\"{TXID}\",\"spendable\":true}}\n\
+                 {{\"type\":\"tx\",\"ref\":\"{TXID}\"}}"
+            ))
+            .unwrap();
+        assert_eq!(imported, 0);
+        assert_eq!(
+            wallet
+                .get_label(LabelType::Tx, TXID.into())
+                .and_then(|label| label.label),
+            Some("Rent".into())
+        );
+    }
+
+    #[test]
+    fn outputs_and_transactions_carry_labels() {
+        let wallet = crate::testing::funded_wallet(&[10_000]);
+        let utxo = wallet.list_unspent().remove(0);
+        assert_eq!(utxo.label, None);
+        let txid = utxo.outpoint.txid.0;
+        let outpoint = format!("{txid}:{}", utxo.outpoint.vout);
+
+        for (label_type, reference, text) in [
+            (LabelType::Output, outpoint, "Change"),
+            (LabelType::Tx, txid.to_string(), "Salary"),
+        ] {
+            wallet
+                .set_label(Label {
+                    label_type,
+                    reference,
+                    label: Some(text.into()),
+                    origin: None,
+                    spendable: None,
+                })
+                .unwrap();
+        }
+
+        assert_eq!(wallet.list_unspent()[0].label.as_deref(), Some("Change"));
+        assert_eq!(
+            wallet.transaction_details()[0].label.as_deref(),
+            Some("Salary")
+        );
+        assert!(matches!(
+            wallet.set_label(Label {
+                label_type: LabelType::Output,
+                reference: txid.to_string(),
+                label: Some("missing vout".into()),
+                origin: None,
+                spendable: None,
+            }),
+            Err(LabelError::InvalidReference { .. })
+        ));
+    }
+}
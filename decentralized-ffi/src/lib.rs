@@ -6,9 +6,13 @@ mod electrum;
 mod error;
 pub mod esplora;
 mod keys;
+#[cfg(feature = "kyoto")]
 mod kyoto;
+mod labels;
 mod ordinal;
 mod store;
+#[cfg(test)]
+mod testing;
 mod tx_builder;
 mod types;
 mod wallet;
@@ -44,6 +48,7 @@ use crate::error::ElectrumError;
 use crate::error::EsploraError;
 use crate::error::ExtractTxError;
 use crate::error::FromScriptError;
+use crate::error::LabelError;
 use crate::error::LoadWithPersistError;
 use crate::error::PersistenceError;
 use crate::error::PsbtError;
@@ -64,7 +69,12 @@ use crate::keys::DerivationPath;
 use crate::keys::DescriptorPublicKey;
 use crate::keys::DescriptorSecretKey;
 use crate::keys::Mnemonic;
+use crate::labels::Label;
+use crate::labels::LabelType;
 //use crate::keys::WordCount;
+#[cfg(feature = "sqlite")]
+use crate::store::EncryptionKey;
+use crate::store::FileFormat;
 use crate::store::Persister;
 use crate::tx_builder::BumpFeeTxBuilder;
 use crate::tx_builder::TxBuilder;
@@ -110,16 +120,85 @@ use crate::ordinal::rune::Edict;
 use crate::ordinal::rune::Rune;
 use crate::ordinal::rune::RuneId;
 use crate::ordinal::rune::RuneParseError;
+use crate::ordinal::rune::RuneAmount;
+use crate::ordinal::rune::RuneAmountError;
+use crate::ordinal::rune::decipher_runestone;
+use crate::ordinal::rune::RuneArtifact;
+use crate::ordinal::rune::encipher_runestone;
+use crate::ordinal::tracker::ClassifiedUtxo;
+use crate::ordinal::tracker::InscriptionOffset;
+use crate::ordinal::tracker::OrdinalTracker;
+use crate::ordinal::tracker::OrdinalTrackerError;
+use crate::ordinal::tracker::UtxoKind;
+use crate::ordinal::rune::RunestoneError;
 
 use crate::ordinal::build_rune_snipe_psbt;
 use crate::ordinal::get_single_anyone_pay_tx_pair;
 use crate::ordinal::inscription::mint;
+use crate::ordinal::inscription::mint_with_wallet;
 use crate::ordinal::inscription::MintError;
+use crate::ordinal::inscription::parse_inscriptions;
+use crate::ordinal::inscription::InscriptionRecord;
+use crate::ordinal::inscription::etch_rune;
+use crate::ordinal::inscription::etch_rune_with_wallet;
+use crate::ordinal::inscription::EtchOutput;
+use crate::ordinal::inscription::InscriptionParent;
+use crate::ordinal::inscription::mint_batch;
+use crate::ordinal::inscription::mint_batch_with_wallet;
+use crate::ordinal::inscription::BatchEntry;
+use crate::ordinal::inscription::BatchMode;
+use crate::ordinal::inscription::InscriptionInfo;
+use crate::ordinal::inscription::InscriptionMetadata;
+use crate::ordinal::inscription::InscriptionOptions;
+use crate::ordinal::inscription::InscriptionSatpoint;
+use crate::ordinal::inscription::detect_content_type;
+use crate::ordinal::inscription::estimate_inscription_cost;
+use crate::ordinal::inscription::InscriptionCost;
+use crate::ordinal::inscription::ContentInfo;
+use crate::ordinal::inscription::media::Language;
+use crate::ordinal::inscription::media::Media;
+use crate::ordinal::inscription::sat::Sat;
+use crate::ordinal::inscription::sat::ParseSatError;
+use crate::ordinal::inscription::sat::SatRange;
+use crate::ordinal::inscription::sat::CoinbaseOutputSats;
+use crate::ordinal::inscription::sat::coinbase_sat_ranges;
+use crate::ordinal::inscription::rarity::Rarity;
+use crate::ordinal::inscription::degree::Degree;
+use crate::ordinal::inscription::decimal_sat::DecimalSat;
+use crate::ordinal::inscription::height::Height;
+use crate::ordinal::inscription::epoch::Epoch;
+use crate::ordinal::inscription::recovery::InscriptionSession;
+use crate::ordinal::inscription::recovery::recover_commit_output;
+use crate::ordinal::inscription::recovery::recover_inscription_session;
+use crate::ordinal::inscription::recovery::inscription_session_to_json;
+use crate::ordinal::inscription::recovery::inscription_session_from_json;
+use crate::ordinal::inscription::recovery::inscription_session_reveal_tx;
+use crate::ordinal::inscription::recovery::inscription_session_reveal_psbt;
 use crate::ordinal::snipe::SnipeError;
+use crate::ordinal::snipe::SnipePairReport;
+use crate::ordinal::snipe::SnipePairStatus;
+use crate::ordinal::validate_inscription_snipe_pairs;
+use crate::ordinal::validate_rune_snipe_pairs;
+use crate::ordinal::build_dummy_utxo_psbt;
+use crate::ordinal::select_dummy_utxos;
+use crate::ordinal::build_listing_psbt;
+use crate::ordinal::verify_listing_psbt;
+use crate::ordinal::Listing;
+use crate::ordinal::listing::ListingError;
 use crate::ordinal::SnipeInscriptionPair;
 use crate::ordinal::SnipePsbtPair;
 use crate::ordinal::SnipeRuneUtxoPair;
 use crate::ordinal::TxInAndTxOut;
+use crate::ordinal::build_rune_transfer_psbt;
+use crate::ordinal::transfer::RuneTransferError;
+use crate::ordinal::build_unbundle_psbt;
+use crate::ordinal::unbundle::UnbundleError;
+use crate::ordinal::RuneBalance;
+use crate::ordinal::RuneTransfer;
+use crate::ordinal::RuneUtxo;
+use crate::ordinal::build_rune_mint_psbt;
+use crate::ordinal::mint::RuneMintError;
+use crate::ordinal::RuneMintTerms;
 
 // uniffi::include_scaffolding!("bdk");
 
@@ -1,68 +1,349 @@
 use bdk_wallet::bitcoin::{
     absolute::LockTime, key::constants::SCHNORR_SIGNATURE_SIZE, transaction::Version, Amount,
-    OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Weight, Witness,
+    OutPoint, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Weight, Witness,
 };
-pub(crate) struct DummyTransaction(pub Transaction);
+use bdk_wallet::descriptor::ExtendedDescriptor;
+use bdk_wallet::miniscript;
+
+/// Largest DER encoded ECDSA signature together with its sighash byte.
+const ECDSA_SIGNATURE_SIZE: usize = 73;
+const PUBLIC_KEY_SIZE: usize = 33;
+
+/// How an input will be satisfied once signed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InputKind {
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+    /// `required`-of-`keys` `multi` script in P2WSH.
+    P2wshMultisig { required: usize, keys: usize },
+    /// Key path spend, `sighash` for a non-default sighash type appended to the signature.
+    P2trKeyPath { sighash: bool },
+    /// Script path spend with `signatures` schnorr signatures for the leaf script.
+    P2trScriptPath {
+        script_size: usize,
+        control_block_size: usize,
+        signatures: usize,
+    },
+}
+
+impl InputKind {
+    /// Guess from the script pubkey alone: P2SH is taken to wrap P2WPKH and P2WSH to hold a
+    /// 1-of-1 `multi`.
+    pub(crate) fn from_script(script_pubkey: &Script) -> Option<Self> {
+        if script_pubkey.is_p2tr() {
+            Some(Self::P2trKeyPath { sighash: false })
+        } else if script_pubkey.is_p2wpkh() {
+            Some(Self::P2wpkh)
+        } else if script_pubkey.is_p2wsh() {
+            Some(Self::P2wshMultisig {
+                required: 1,
+                keys: 1,
+            })
+        } else if script_pubkey.is_p2sh() {
+            Some(Self::P2shP2wpkh)
+        } else if script_pubkey.is_p2pkh() {
+            Some(Self::P2pkh)
+        } else {
+            None
+        }
+    }
+
+    fn is_segwit(self) -> bool {
+        self != Self::P2pkh
+    }
+
+    /// Input carrying placeholders of the largest satisfaction.
+    fn satisfied_input(self) -> TxIn {
+        let ecdsa = || vec![0; ECDSA_SIGNATURE_SIZE];
+        let (script_sig, witness) = match self {
+            Self::P2pkh => (
+                ScriptBuf::builder()
+                    .push_slice([0; ECDSA_SIGNATURE_SIZE])
+                    .push_slice([0; PUBLIC_KEY_SIZE])
+                    .into_script(),
+                Witness::new(),
+            ),
+            Self::P2shP2wpkh => (
+                // push of the P2WPKH redeem script
+                ScriptBuf::builder().push_slice([0; 22]).into_script(),
+                Witness::from_slice(&[ecdsa(), vec![0; PUBLIC_KEY_SIZE]]),
+            ),
+            Self::P2wpkh => (
+                ScriptBuf::new(),
+                Witness::from_slice(&[ecdsa(), vec![0; PUBLIC_KEY_SIZE]]),
+            ),
+            Self::P2wshMultisig { required, keys } => {
+                let mut witness = vec![Vec::new()];
+                witness.extend((0..required).map(|_| ecdsa()));
+                // OP_m <key>... OP_n OP_CHECKMULTISIG
+                witness.push(vec![0; 3 + keys * (1 + PUBLIC_KEY_SIZE)]);
+                (ScriptBuf::new(), Witness::from_slice(&witness))
+            }
+            Self::P2trKeyPath { sighash } => (
+                ScriptBuf::new(),
+                Witness::from_slice(&[vec![0; SCHNORR_SIGNATURE_SIZE + usize::from(sighash)]]),
+            ),
+            Self::P2trScriptPath {
+                script_size,
+                control_block_size,
+                signatures,
+            } => {
+                let mut witness: Vec<Vec<u8>> = (0..signatures)
+                    .map(|_| vec![0; SCHNORR_SIGNATURE_SIZE])
+                    .collect();
+                witness.push(vec![0; script_size]);
+                witness.push(vec![0; control_block_size]);
+                (ScriptBuf::new(), Witness::from_slice(&witness))
+            }
+        };
+        TxIn {
+            script_sig,
+            witness,
+            ..Default::default()
+        }
+    }
+
+    /// Weight the satisfaction adds to an unsigned input, the same measure as miniscript's
+    /// `max_weight_to_satisfy`.
+    pub(crate) fn satisfaction_weight(self) -> Weight {
+        self.satisfied_input().segwit_weight() - TxIn::default().segwit_weight()
+    }
+}
+
+/// Transaction used to size fees before the real inputs are signed.
+///
+/// Inputs are either given with their final scriptSig and witness or left empty with the weight
+/// of their satisfaction added on top.
+pub(crate) struct DummyTransaction {
+    tx: Transaction,
+    satisfaction_weight: Weight,
+    segwit: bool,
+}
 
 impl DummyTransaction {
     pub(crate) fn new() -> Self {
-        DummyTransaction(Transaction {
-            version: Version::TWO,
-            lock_time: LockTime::ZERO,
-            input: vec![],
-            output: vec![],
-        })
+        DummyTransaction {
+            tx: Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: vec![],
+                output: vec![],
+            },
+            satisfaction_weight: Weight::ZERO,
+            segwit: false,
+        }
     }
 
+    /// Append an input spending `script_pubkey`, signed with `sig` and `witness` when known and
+    /// estimated from the script type otherwise.
     pub(crate) fn append_input(
         &mut self,
         script_pubkey: ScriptBuf,
         sig: Option<ScriptBuf>,
         witness: Option<Witness>,
     ) {
-        let sig = sig.unwrap_or({
-            if script_pubkey.is_p2sh() {
-                script_pubkey.to_p2sh()
-            } else if script_pubkey.is_p2wsh() {
-                script_pubkey.to_p2wsh()
-            } else {
-                ScriptBuf::new()
-            }
-        });
-        let witness = witness.unwrap_or({
-            match true {
-                _ if script_pubkey.is_p2wsh() => {}
-                _ if script_pubkey.is_p2sh() => {}
-                _ => {}
-            }
-            if script_pubkey.is_p2tr() {
-                Witness::from_slice(&[&[0; SCHNORR_SIGNATURE_SIZE]])
-            } else if script_pubkey.is_p2wpkh() {
-                Witness::from_slice(&[vec![0; 105]]) // 第一个值最大73 这里已知在xx下
-            } else {
-                Witness::new()
-            }
-        });
-        self.0.input.push(TxIn {
+        if sig.is_none()
+            && witness.is_none()
+            && let Some(kind) = InputKind::from_script(&script_pubkey)
+        {
+            self.append_estimated_input(kind);
+            return;
+        }
+        self.push_input(sig.unwrap_or_default(), witness.unwrap_or_default());
+    }
+
+    pub(crate) fn append_estimated_input(&mut self, kind: InputKind) {
+        self.push_input(ScriptBuf::new(), Witness::new());
+        self.satisfaction_weight += kind.satisfaction_weight();
+        self.segwit |= kind.is_segwit();
+    }
+
+    /// Append an input spending `descriptor`, sized by its largest satisfaction.
+    pub(crate) fn append_descriptor_input(
+        &mut self,
+        descriptor: &ExtendedDescriptor,
+    ) -> Result<(), miniscript::Error> {
+        let weight = descriptor.max_weight_to_satisfy()?;
+        self.push_input(ScriptBuf::new(), Witness::new());
+        self.satisfaction_weight += weight;
+        self.segwit |= descriptor.desc_type().segwit_version().is_some();
+        Ok(())
+    }
+
+    fn push_input(&mut self, script_sig: ScriptBuf, witness: Witness) {
+        self.segwit |= !witness.is_empty();
+        self.tx.input.push(TxIn {
             previous_output: OutPoint::null(),
-            script_sig: sig,
+            script_sig,
             sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
             witness,
         })
     }
 
     pub(crate) fn append_output(&mut self, script_pubkey: ScriptBuf) {
-        self.0.output.push(TxOut {
+        self.tx.output.push(TxOut {
             value: Amount::ZERO,
             script_pubkey,
         })
     }
 
     pub(crate) fn vsize(&self) -> usize {
-        self.0.vsize()
+        self.weight().to_vbytes_ceil() as usize
     }
 
     pub(crate) fn weight(&self) -> Weight {
-        self.0.weight()
+        let base = self.tx.base_size();
+        // marker, flag and a witness stack for every input
+        let witness = if self.segwit {
+            2 + self
+                .tx
+                .input
+                .iter()
+                .map(|input| input.witness.size())
+                .sum::<usize>()
+        } else {
+            0
+        };
+        Weight::from_wu((base * 4 + witness) as u64) + self.satisfaction_weight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{address, TPRV};
+    use bdk_wallet::bitcoin::{self, hashes::Hash, Network};
+    use bdk_wallet::{KeychainKind, SignOptions, Wallet};
+
+    const NUMS: &str = "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+    /// Sweep `inputs` outputs of a `descriptor` wallet and return the signed transaction.
+    fn signed_sweep(descriptor: &str, inputs: usize) -> (Wallet, Transaction) {
+        let change = descriptor.replace("/0/*", "/9/*");
+        let mut wallet = Wallet::create(descriptor.to_string(), change)
+            .network(Network::Signet)
+            .create_wallet_no_persist()
+            .unwrap();
+        let funding = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: bitcoin::Txid::all_zeros(),
+                    vout: 0,
+                },
+                ..Default::default()
+            }],
+            output: (0..inputs)
+                .map(|_| TxOut {
+                    value: Amount::from_sat(100_000),
+                    script_pubkey: wallet
+                        .reveal_next_address(KeychainKind::External)
+                        .script_pubkey(),
+                })
+                .collect(),
+        };
+        wallet.apply_unconfirmed_txs([(funding, 0)]);
+
+        let mut builder = wallet.build_tx();
+        builder.drain_wallet().drain_to(address().script_pubkey());
+        let mut psbt = builder.finish().unwrap();
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap(), "{descriptor}");
+        (wallet, psbt.extract_tx_unchecked_fee_rate())
+    }
+
+    fn estimate(tx: &Transaction, append: impl Fn(&mut DummyTransaction)) -> Weight {
+        let mut dummy_tx = DummyTransaction::new();
+        for _ in &tx.input {
+            append(&mut dummy_tx);
+        }
+        for output in &tx.output {
+            dummy_tx.append_output(output.script_pubkey.clone());
+        }
+        dummy_tx.weight()
+    }
+
+    /// Checks `estimated` is an upper bound at most `slack` weight units per signature off.
+    #[track_caller]
+    fn assert_bound(estimated: Weight, tx: &Transaction, signatures: usize, slack: u64) {
+        let actual = tx.weight();
+        assert!(estimated >= actual, "{estimated} < {actual}");
+        let signatures = (tx.input.len() * signatures) as u64;
+        assert!(
+            estimated - actual <= Weight::from_wu(signatures * slack),
+            "{estimated} too far above {actual}"
+        );
+    }
+
+    #[test]
+    fn single_key_inputs() {
+        for (descriptor, slack) in [
+            (format!("pkh({TPRV}/44'/1'/0'/0/*)"), 8),
+            (format!("sh(wpkh({TPRV}/49'/1'/0'/0/*))"), 2),
+            (format!("wpkh({TPRV}/84'/1'/0'/0/*)"), 2),
+            (format!("tr({TPRV}/86'/1'/0'/0/*)"), 0),
+        ] {
+            let (wallet, tx) = signed_sweep(&descriptor, 3);
+            let script_pubkey = wallet
+                .peek_address(KeychainKind::External, 0)
+                .script_pubkey();
+            let by_script = estimate(&tx, |dummy_tx| {
+                dummy_tx.append_input(script_pubkey.clone(), None, None)
+            });
+            assert_bound(by_script, &tx, 1, slack);
+
+            let public = wallet.public_descriptor(KeychainKind::External).clone();
+            let by_descriptor =
+                estimate(&tx, |dummy_tx| dummy_tx.append_descriptor_input(&public).unwrap());
+            // miniscript leaves room for a sighash byte on schnorr signatures too
+            assert_bound(by_descriptor, &tx, 1, slack.max(1));
+        }
+    }
+
+    #[test]
+    fn multisig_and_script_path_inputs() {
+        let [a, b, c] = [1u8, 2, 3]
+            .map(|seed| bitcoin::bip32::Xpriv::new_master(Network::Signet, &[seed; 32]).unwrap());
+        let (_, tx) = signed_sweep(&format!("wsh(multi(2,{a}/0/*,{b}/0/*,{c}/0/*))"), 2);
+        let multisig = estimate(&tx, |dummy_tx| {
+            dummy_tx.append_estimated_input(InputKind::P2wshMultisig {
+                required: 2,
+                keys: 3,
+            })
+        });
+        assert_bound(multisig, &tx, 2, 2);
+
+        let (_, tx) = signed_sweep(&format!("tr({NUMS},pk({TPRV}/86'/1'/0'/0/*))"), 2);
+        let script_path = estimate(&tx, |dummy_tx| {
+            dummy_tx.append_estimated_input(InputKind::P2trScriptPath {
+                // <key> OP_CHECKSIG
+                script_size: 34,
+                control_block_size: 33,
+                signatures: 1,
+            })
+        });
+        assert_eq!(script_path, tx.weight());
+    }
+
+    #[test]
+    fn signed_inputs_are_taken_as_is() {
+        let (wallet, tx) = signed_sweep(&format!("wpkh({TPRV}/84'/1'/0'/0/*)"), 2);
+        let script_pubkey = wallet
+            .peek_address(KeychainKind::External, 0)
+            .script_pubkey();
+        let mut dummy_tx = DummyTransaction::new();
+        for input in &tx.input {
+            dummy_tx.append_input(
+                script_pubkey.clone(),
+                Some(input.script_sig.clone()),
+                Some(input.witness.clone()),
+            );
+        }
+        for output in &tx.output {
+            dummy_tx.append_output(output.script_pubkey.clone());
+        }
+        assert_eq!(dummy_tx.weight(), tx.weight());
+        assert_eq!(dummy_tx.vsize(), tx.vsize());
     }
 }
@@ -1,3 +1,6 @@
+use super::{default_postage, inscription::Inscription, inscription_id::InscriptionId, NamedFile};
+use anyhow::{bail, ensure, Result};
+use bdk_wallet::bitcoin::{Address, Amount, Network};
 use serde::{Deserialize, Serialize};
 
 #[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize, Default)]
@@ -10,3 +13,98 @@ pub(crate) enum Mode {
     #[serde(rename = "shared-output")]
     SharedOutput,
 }
+
+/// Inscriptions of a batch, in reveal order.
+pub(crate) struct Batchfile {
+    pub(crate) mode: Mode,
+    pub(crate) postage: Option<Amount>,
+    pub(crate) inscriptions: Vec<Entry>,
+}
+
+pub(crate) struct Entry {
+    pub(crate) file: NamedFile,
+    pub(crate) destination: Option<Address>,
+    pub(crate) metadata: Option<Vec<u8>>, // cbor
+    pub(crate) postage: Option<Amount>,
+}
+
+impl Batchfile {
+    /// Inscriptions with their pointers, destinations and postages.
+    ///
+    /// Pointers are the sat offset of each inscription in the reveal outputs, after the parent
+    /// output if any.
+    pub(crate) fn inscriptions(
+        self,
+        network: Network,
+        parent: Option<InscriptionId>,
+        parent_value: Option<Amount>,
+        destination: &Address,
+        compress: bool,
+    ) -> Result<(Vec<Inscription>, Vec<Address>, Vec<Amount>)> {
+        ensure!(
+            !self.inscriptions.is_empty(),
+            "batch must contain inscriptions"
+        );
+
+        if self.mode != Mode::SeparateOutputs
+            && self
+                .inscriptions
+                .iter()
+                .any(|entry| entry.destination.is_some())
+        {
+            bail!("destination can only be set per inscription in `separate-outputs` mode");
+        }
+
+        if self.mode == Mode::SameSat
+            && self
+                .inscriptions
+                .iter()
+                .any(|entry| entry.postage.is_some())
+        {
+            bail!("postage can only be set per inscription in `separate-outputs` and `shared-output` mode");
+        }
+
+        let mut pointer = parent_value.unwrap_or_default().to_sat();
+        let mut inscriptions = Vec::new();
+        let mut destinations = Vec::new();
+        let mut postages = Vec::new();
+
+        for entry in self.inscriptions {
+            let entry_destination = entry.destination.unwrap_or(destination.clone());
+            let entry_postage = entry
+                .postage
+                .or(self.postage)
+                .unwrap_or_else(|| default_postage(&entry_destination));
+
+            let dust = entry_destination.script_pubkey().minimal_non_dust();
+            if entry_postage < dust {
+                bail!(
+                    "postage {entry_postage} of {} is below dust {dust}",
+                    entry.file.name
+                );
+            }
+
+            inscriptions.push(Inscription::from_bytes(
+                network,
+                entry.file,
+                parent,
+                Some(pointer),
+                None,
+                entry.metadata,
+                compress,
+                None,
+            )?);
+
+            if self.mode != Mode::SameSat {
+                pointer += entry_postage.to_sat();
+            }
+
+            if self.mode == Mode::SeparateOutputs || destinations.is_empty() {
+                destinations.push(entry_destination);
+            }
+            postages.push(entry_postage);
+        }
+
+        Ok((inscriptions, destinations, postages))
+    }
+}
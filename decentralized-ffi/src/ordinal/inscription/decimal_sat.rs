@@ -1,12 +1,15 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt,
+    fmt::{Display, Formatter},
+};
 
-use super::*;
-use crate::ordinal::inscription::height::Height;
+use crate::ordinal::inscription::{height::Height, sat::Sat};
 
-#[derive(PartialEq, Debug)]
-pub(crate) struct DecimalSat {
-    height: Height,
-    offset: u64,
+/// Height of the block that mined a sat and the sat's offset within that block's subsidy.
+#[derive(uniffi::Record, PartialEq, Debug)]
+pub struct DecimalSat {
+    pub height: Height,
+    pub offset: u64,
 }
 
 impl From<Sat> for DecimalSat {
@@ -7,12 +7,14 @@ use bdk_wallet::bitcoin::constants::{DIFFCHANGE_INTERVAL, SUBSIDY_HALVING_INTERV
 
 use crate::ordinal::inscription::{common::CYCLE_EPOCHS, sat::Sat};
 
-#[derive(PartialEq, Debug)]
-pub(crate) struct Degree {
-    pub(crate) hour: u32,
-    pub(crate) minute: u32,
-    pub(crate) second: u32,
-    pub(crate) third: u64,
+/// Position of a sat as cycle (`hour`), block within the halving epoch (`minute`), block within
+/// the difficulty period (`second`) and sat within the block subsidy (`third`).
+#[derive(uniffi::Record, PartialEq, Debug)]
+pub struct Degree {
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub third: u64,
 }
 
 impl Display for Degree {
@@ -21,6 +21,7 @@ pub(crate) const PARENT_TAG: [u8; 1] = [3];
 pub(crate) const METADATA_TAG: [u8; 1] = [5];
 pub(crate) const METAPROTOCOL_TAG: [u8; 1] = [7];
 pub(crate) const CONTENT_ENCODING_TAG: [u8; 1] = [9];
+pub(crate) const DELEGATE_TAG: [u8; 1] = [11];
 
 type Result<T> = std::result::Result<T, script::Error>;
 type RawEnvelope = Envelope<Vec<Vec<u8>>>;
@@ -87,9 +88,15 @@ impl From<RawEnvelope> for ParsedEnvelope {
 
         let content_encoding = remove_field(&mut fields, &CONTENT_ENCODING_TAG);
         let content_type = remove_field(&mut fields, &CONTENT_TYPE_TAG);
+        let delegate = remove_field(&mut fields, &DELEGATE_TAG);
         let metadata = remove_and_concatenate_field(&mut fields, &METADATA_TAG);
         let metaprotocol = remove_field(&mut fields, &METAPROTOCOL_TAG);
-        let parent = remove_field(&mut fields, &PARENT_TAG);
+        let parents = fields
+            .remove(PARENT_TAG.as_slice())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|value| value.to_vec())
+            .collect();
         let pointer = remove_field(&mut fields, &POINTER_TAG);
 
         let unrecognized_even_field = fields
@@ -107,11 +114,12 @@ impl From<RawEnvelope> for ParsedEnvelope {
                 }),
                 content_encoding,
                 content_type,
+                delegate,
                 duplicate_field,
                 incomplete_field,
                 metadata,
                 metaprotocol,
-                parent,
+                parents,
                 pointer,
                 unrecognized_even_field,
             },
@@ -293,6 +301,111 @@ impl RawEnvelope {
 mod tests {
     use super::*;
     use crate::ordinal::inscription::common::Output;
+    use bdk_wallet::bitcoin::hashes::Hash;
+
+    fn inscription_tx(inscription: &Inscription) -> Transaction {
+        Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: inscription.to_witness(),
+            }],
+            output: vec![],
+        }
+    }
+
+    #[test]
+    fn parse_inscriptions_from_transaction() {
+        let parent = InscriptionId {
+            txid: Txid::all_zeros(),
+            index: 1,
+        };
+        let mut metadata = Vec::new();
+        ciborium::into_writer(&Value::Text("hello".into()), &mut metadata).unwrap();
+
+        let inscription = Inscription {
+            content_type: Some(b"text/plain;charset=utf-8".to_vec()),
+            body: Some(b"ord".to_vec()),
+            metadata: Some(metadata),
+            metaprotocol: Some(b"brc-20".to_vec()),
+            parents: vec![parent.value()],
+            pointer: Some(Inscription::pointer_value(1000)),
+            ..Default::default()
+        };
+
+        let records = crate::ordinal::inscription::parse_inscriptions(&crate::Transaction(
+            inscription_tx(&inscription),
+        ));
+
+        assert_eq!(
+            records,
+            vec![InscriptionRecord {
+                content_type: Some("text/plain;charset=utf-8".into()),
+                body: Some(b"ord".to_vec()),
+                content_encoding: None,
+                metadata: Some("\"hello\"".into()),
+                metaprotocol: Some("brc-20".into()),
+                parents: vec![parent.to_string()],
+                delegate: None,
+                pointer: Some(1000),
+                input: 0,
+                offset: 0,
+                duplicate_field: false,
+                incomplete_field: false,
+                unrecognized_even_field: false,
+                stutter: false,
+                pushnum: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_inscription_delegate() {
+        let delegate = InscriptionId {
+            txid: Txid::all_zeros(),
+            index: 300,
+        };
+        let inscription = Inscription {
+            delegate: Some(delegate.value()),
+            ..Default::default()
+        };
+
+        let parsed = ParsedEnvelope::from_transaction(&inscription_tx(&inscription));
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].payload, inscription);
+        assert_eq!(parsed[0].payload.delegate(), Some(delegate));
+        assert!(!parsed[0].payload.unrecognized_even_field);
+    }
+
+    #[test]
+    fn parse_inscriptions_flags_multiple_parents_as_duplicate() {
+        let parents = vec![
+            InscriptionId {
+                txid: Txid::all_zeros(),
+                index: 0,
+            },
+            InscriptionId {
+                txid: Txid::all_zeros(),
+                index: 2,
+            },
+        ];
+        let inscription = Inscription {
+            content_type: Some(b"text/plain".to_vec()),
+            body: Some(Vec::new()),
+            parents: parents.iter().map(|id| id.value()).collect(),
+            ..Default::default()
+        };
+
+        let parsed = ParsedEnvelope::from_transaction(&inscription_tx(&inscription));
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].payload.parents(), parents);
+        assert!(parsed[0].payload.duplicate_field);
+    }
     //
     // #[test]
     // fn test_witness() {
@@ -7,7 +7,9 @@ use crate::ordinal::inscription::{height::Height, sat::Sat};
 use crate::ordinal::inscription::common::COIN_VALUE;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Serialize, PartialOrd)]
-pub(crate) struct Epoch(pub(crate) u32);
+pub struct Epoch(pub u32);
+
+uniffi::custom_newtype!(Epoch, u32);
 
 impl Epoch {
     pub(crate) const STARTING_SATS: [Sat; 34] = [
@@ -7,7 +7,9 @@ use serde::Serialize;
 use crate::ordinal::inscription::{epoch::Epoch, sat::Sat};
 
 #[derive(Copy, Clone, Debug, Display, FromStr, Ord, Eq, Serialize, PartialEq, PartialOrd)]
-pub(crate) struct Height(pub(crate) u32);
+pub struct Height(pub u32);
+
+uniffi::custom_newtype!(Height, u32);
 
 impl Height {
     pub(crate) fn n(self) -> u32 {
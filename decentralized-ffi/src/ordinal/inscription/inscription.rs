@@ -7,6 +7,7 @@ use bdk_wallet::bitcoin::{
         script::{self, PushBytesBuf},
     },
     hashes::Hash,
+    taproot::{LeafVersion, TAPROOT_CONTROL_BASE_SIZE},
     Network, ScriptBuf, Txid, Witness,
 };
 use brotli::enc::{writer::CompressorWriter, BrotliEncoderParams};
@@ -18,18 +19,21 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 
-use crate::ordinal::inscription::{envelope, inscription_id::InscriptionId, media::Media};
+use crate::ordinal::inscription::{
+    envelope, inscription_id::InscriptionId, media::Media, NamedFile,
+};
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Eq, Default)]
 pub struct Inscription {
     pub body: Option<Vec<u8>>,
     pub content_encoding: Option<Vec<u8>>,
     pub content_type: Option<Vec<u8>>,
+    pub delegate: Option<Vec<u8>>,
     pub duplicate_field: bool,
     pub incomplete_field: bool,
     pub metadata: Option<Vec<u8>>,
     pub metaprotocol: Option<Vec<u8>>,
-    pub parent: Option<Vec<u8>>,
+    pub parents: Vec<Vec<u8>>,
     pub pointer: Option<Vec<u8>>,
     pub unrecognized_even_field: bool,
 }
@@ -52,17 +56,34 @@ impl Inscription {
         }
     }
 
+    /// With a `delegate` the file may be empty, the inscription then has no body and content
+    /// type and is displayed with the content of the delegate.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn from_bytes(
         network: Network,
-        file: (String, Vec<u8>),
+        file: NamedFile,
         parent: Option<InscriptionId>,
         pointer: Option<u64>,
         metaprotocol: Option<String>,
         metadata: Option<Vec<u8>>,
         compress: bool,
+        delegate: Option<InscriptionId>,
     ) -> Result<Self, Error> {
-        let fp = Path::new(&file.0);
-        let (content_type, compression_mode) = Media::content_type_for_path(fp, &file.1)?;
+        if delegate.is_some() && file.data.is_empty() {
+            return Ok(Self {
+                delegate: delegate.map(InscriptionId::value),
+                metadata,
+                metaprotocol: metaprotocol.map(|metaprotocol| metaprotocol.into_bytes()),
+                parents: parent.into_iter().map(InscriptionId::value).collect(),
+                pointer: pointer.map(Self::pointer_value),
+                ..Default::default()
+            });
+        }
+
+        let (content_type, compression_mode) =
+            Media::content_type_for_file(&file.name, &file.data, file.content_type.as_deref())?;
+
+        let body = file.data;
 
         let (body, content_encoding) = if compress {
             let mut compressed = Vec::new();
@@ -70,17 +91,17 @@ impl Inscription {
             {
                 CompressorWriter::with_params(
                     &mut compressed,
-                    file.1.len(),
+                    body.len(),
                     &BrotliEncoderParams {
                         lgblock: 24,
                         lgwin: 24,
                         mode: compression_mode,
                         quality: 11,
-                        size_hint: file.1.len(),
+                        size_hint: body.len(),
                         ..Default::default()
                     },
                 )
-                .write_all(&file.1)?;
+                .write_all(&body)?;
 
                 let mut decompressor =
                     brotli::Decompressor::new(compressed.as_slice(), compressed.len());
@@ -89,16 +110,16 @@ impl Inscription {
 
                 decompressor.read_to_end(&mut decompressed)?;
 
-                ensure!(decompressed == file.1, "decompression roundtrip failed");
+                ensure!(decompressed == body, "decompression roundtrip failed");
             }
 
-            if compressed.len() < file.1.len() {
+            if compressed.len() < body.len() {
                 (compressed, Some("br".as_bytes().to_vec()))
             } else {
-                (file.1, None)
+                (body, None)
             }
         } else {
-            (file.1, None)
+            (body, None)
         };
 
         if let Some(limit) = get_inscription_content_size_limit(&network) {
@@ -114,8 +135,9 @@ impl Inscription {
             content_encoding,
             metadata,
             metaprotocol: metaprotocol.map(|metaprotocol| metaprotocol.into_bytes()),
-            parent: parent.map(|id| id.parent_value()),
+            parents: parent.into_iter().map(InscriptionId::value).collect(),
             pointer: pointer.map(Self::pointer_value),
+            delegate: delegate.map(InscriptionId::value),
             ..Default::default()
         })
     }
@@ -134,7 +156,8 @@ impl Inscription {
         let body =
             fs::read(path).with_context(|| format!("io error reading {}", path.display()))?;
 
-        let (content_type, compression_mode) = Media::content_type_for_path(path, &[])?; // todo
+        let (content_type, compression_mode) =
+            Media::content_type_for_file(&path.to_string_lossy(), &body, None)?;
 
         let (body, content_encoding) = if compress {
             let mut compressed = Vec::new();
@@ -186,7 +209,7 @@ impl Inscription {
             content_encoding,
             metadata,
             metaprotocol: metaprotocol.map(|metaprotocol| metaprotocol.into_bytes()),
-            parent: parent.map(|id| id.parent_value()),
+            parents: parent.into_iter().map(InscriptionId::value).collect(),
             pointer: pointer.map(Self::pointer_value),
             ..Default::default()
         })
@@ -229,12 +252,18 @@ impl Inscription {
                 .push_slice(PushBytesBuf::try_from(protocol).unwrap());
         }
 
-        if let Some(parent) = self.parent.clone() {
+        for parent in self.parents.iter().cloned() {
             builder = builder
                 .push_slice(envelope::PARENT_TAG)
                 .push_slice(PushBytesBuf::try_from(parent).unwrap());
         }
 
+        if let Some(delegate) = self.delegate.clone() {
+            builder = builder
+                .push_slice(envelope::DELEGATE_TAG)
+                .push_slice(PushBytesBuf::try_from(delegate).unwrap());
+        }
+
         if let Some(pointer) = self.pointer.clone() {
             builder = builder
                 .push_slice(envelope::POINTER_TAG)
@@ -322,9 +351,22 @@ impl Inscription {
         str::from_utf8(self.metaprotocol.as_ref()?).ok()
     }
 
+    pub(crate) fn delegate(&self) -> Option<InscriptionId> {
+        Self::inscription_id_field(self.delegate.as_ref()?)
+    }
+
     pub(crate) fn parent(&self) -> Option<InscriptionId> {
-        let value = self.parent.as_ref()?;
+        self.parents().into_iter().next()
+    }
 
+    pub(crate) fn parents(&self) -> Vec<InscriptionId> {
+        self.parents
+            .iter()
+            .filter_map(|value| Self::inscription_id_field(value))
+            .collect()
+    }
+
+    fn inscription_id_field(value: &[u8]) -> Option<InscriptionId> {
         if value.len() < Txid::LEN {
             return None;
         }
@@ -386,8 +428,11 @@ impl Inscription {
 
         let mut witness = Witness::new();
 
+        let mut control_block = vec![0; TAPROOT_CONTROL_BASE_SIZE];
+        control_block[0] = LeafVersion::TapScript.to_consensus();
+
         witness.push(script);
-        witness.push([]);
+        witness.push(control_block);
 
         witness
     }
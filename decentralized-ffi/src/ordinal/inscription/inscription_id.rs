@@ -9,7 +9,7 @@ use std::{
 
 use crate::ordinal::inscription::deserialize_from_str::DeserializeFromStr;
 
-#[derive( Debug, PartialEq, Copy, Clone, Hash, Eq)]
+#[derive(Debug, PartialEq, Copy, Clone, Hash, Eq)]
 pub struct InscriptionId {
     pub txid: Txid,
     pub index: u32,
@@ -25,7 +25,7 @@ impl Default for InscriptionId {
 }
 
 impl InscriptionId {
-    pub(crate) fn parent_value(self) -> Vec<u8> {
+    pub(crate) fn value(self) -> Vec<u8> {
         let index = self.index.to_le_bytes();
         let mut index_slice = index.as_slice();
 
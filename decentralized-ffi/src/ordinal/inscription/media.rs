@@ -4,17 +4,21 @@ use std::{
     fs::File,
     io::{BufReader, Bytes, Cursor},
     path::Path,
+    str,
     str::FromStr,
 };
 
 use anyhow::{anyhow, Error};
+use bdk_wallet::serde_json;
 use brotli::enc::backward_references::BrotliEncoderMode::{
     self, BROTLI_MODE_FONT, BROTLI_MODE_GENERIC, BROTLI_MODE_TEXT,
 };
+use http::header::HeaderValue;
 use mp4::{MediaType, Mp4Reader, TrackType};
 
-#[derive(Debug, PartialEq, Copy, Clone)]
-pub(crate) enum Media {
+/// How inscription content is displayed.
+#[derive(uniffi::Enum, Debug, PartialEq, Copy, Clone)]
+pub enum Media {
     Audio,
     Code(Language),
     Font,
@@ -28,8 +32,8 @@ pub(crate) enum Media {
     Video,
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
-pub(crate) enum Language {
+#[derive(uniffi::Enum, Debug, PartialEq, Copy, Clone)]
+pub enum Language {
     Css,
     JavaScript,
     Json,
@@ -129,6 +133,173 @@ impl Media {
         ))
     }
 
+    /// Content type of a file, in order of preference the explicit `content_type`, the magic bytes
+    /// of binary formats, the extension of `name` and the shape of textual data.
+    pub(crate) fn content_type_for_file(
+        name: &str,
+        data: &[u8],
+        content_type: Option<&str>,
+    ) -> Result<(String, BrotliEncoderMode), Error> {
+        let path = Path::new(name);
+
+        let (content_type, mode) = if let Some(content_type) = content_type {
+            let content_type = content_type.trim();
+            if content_type.is_empty() || HeaderValue::from_str(content_type).is_err() {
+                return Err(anyhow!("invalid content type `{content_type}`"));
+            }
+            let mode = Self::TABLE
+                .iter()
+                .find(|(table_type, ..)| table_type.eq_ignore_ascii_case(content_type))
+                .map(|(_, mode, ..)| *mode)
+                .unwrap_or(BROTLI_MODE_GENERIC);
+            (content_type.to_string(), mode)
+        } else if let Some(content_type) = Self::sniff_binary(data) {
+            (content_type.to_string(), Self::mode(content_type))
+        } else if path.extension().is_some() {
+            let (content_type, mode) = Self::content_type_for_path(path, data)?;
+            (content_type.to_string(), mode)
+        } else if data.len() >= 12 && &data[4..8] == b"ftyp" {
+            return Err(anyhow!(
+                "unsupported ISO media brand `{}` of `{name}`, set the content type explicitly",
+                String::from_utf8_lossy(&data[8..12])
+            ));
+        } else if let Some(content_type) = Self::sniff_text(data) {
+            (content_type.to_string(), Self::mode(content_type))
+        } else {
+            return Err(anyhow!(
+                "unable to detect content type of `{name}`, set the content type explicitly"
+            ));
+        };
+
+        if content_type == "video/mp4" {
+            Media::check_mp4_codec(path, data)?;
+        }
+
+        Ok((content_type, mode))
+    }
+
+    fn mode(content_type: &str) -> BrotliEncoderMode {
+        Self::TABLE
+            .iter()
+            .find(|(table_type, ..)| *table_type == content_type)
+            .map(|(_, mode, ..)| *mode)
+            .unwrap_or(BROTLI_MODE_GENERIC)
+    }
+
+    /// Content type of binary formats identified by their magic bytes.
+    pub(crate) fn sniff_binary(data: &[u8]) -> Option<&'static str> {
+        const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+        if data.starts_with(PNG) {
+            // animated PNGs carry an animation control chunk before the image data
+            let idat = data.windows(4).position(|window| window == b"IDAT");
+            let actl = data.windows(4).position(|window| window == b"acTL");
+            return match (actl, idat) {
+                (Some(actl), Some(idat)) if actl < idat => Some("image/apng"),
+                (Some(_), None) => Some("image/apng"),
+                _ => Some("image/png"),
+            };
+        }
+
+        if data.starts_with(b"\xff\xd8\xff") {
+            return Some("image/jpeg");
+        }
+
+        if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            return Some("image/gif");
+        }
+
+        if data.len() >= 12 && data.starts_with(b"RIFF") {
+            match &data[8..12] {
+                b"WEBP" => return Some("image/webp"),
+                b"WAVE" => return Some("audio/wav"),
+                _ => {}
+            }
+        }
+
+        // ISO base media files share the `ftyp` box, only the brand tells them apart
+        if data.len() >= 12 && &data[4..8] == b"ftyp" {
+            return match &data[8..12] {
+                b"avif" | b"avis" => Some("image/avif"),
+                b"isom" | b"iso2" | b"iso3" | b"iso4" | b"iso5" | b"iso6" | b"mp41" | b"mp42"
+                | b"avc1" | b"dash" => Some("video/mp4"),
+                _ => None,
+            };
+        }
+
+        if data.starts_with(b"\x1a\x45\xdf\xa3") {
+            return Some("video/webm");
+        }
+
+        if data.starts_with(b"glTF") {
+            return Some("model/gltf-binary");
+        }
+
+        if data.starts_with(b"wOFF") {
+            return Some("font/woff");
+        }
+
+        if data.starts_with(b"wOF2") {
+            return Some("font/woff2");
+        }
+
+        if data.starts_with(b"OTTO") {
+            return Some("font/otf");
+        }
+
+        if data.starts_with(b"\x00\x01\x00\x00") {
+            return Some("font/ttf");
+        }
+
+        if data.starts_with(b"%PDF-") {
+            return Some("application/pdf");
+        }
+
+        if data.starts_with(b"fLaC") {
+            return Some("audio/flac");
+        }
+
+        if data.starts_with(b"ID3") {
+            return Some("audio/mpeg");
+        }
+
+        None
+    }
+
+    /// Content type of UTF-8 data, recognizing SVG, HTML and JSON and falling back to plain text.
+    pub(crate) fn sniff_text(data: &[u8]) -> Option<&'static str> {
+        let text = str::from_utf8(data).ok()?;
+        let text = text.trim_start_matches('\u{feff}').trim_start();
+
+        let head = text
+            .chars()
+            .take(1024)
+            .collect::<String>()
+            .to_ascii_lowercase();
+
+        if head.starts_with("<svg") || (head.starts_with("<?xml") && head.contains("<svg")) {
+            return Some("image/svg+xml");
+        }
+
+        if head.starts_with("<!doctype html") || head.starts_with("<html") {
+            return Some("text/html;charset=utf-8");
+        }
+
+        if (head.starts_with('{') || head.starts_with('['))
+            && let Ok(value) = serde_json::from_str::<serde_json::Value>(text)
+        {
+            if value
+                .get("asset")
+                .is_some_and(|asset| asset.get("version").is_some())
+            {
+                return Some("model/gltf+json");
+            }
+            return Some("application/json");
+        }
+
+        Some("text/plain;charset=utf-8")
+    }
+
     pub(crate) fn check_mp4_codec(path: &Path, data: &[u8]) -> Result<(), Error> {
         let reader = BufReader::new(Cursor::new(data));
 
@@ -162,3 +333,89 @@ impl FromStr for Media {
         Err(anyhow!("unknown content type: {s}"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content_type(name: &str, data: &[u8]) -> String {
+        Media::content_type_for_file(name, data, None).unwrap().0
+    }
+
+    #[test]
+    fn content_type_from_magic_bytes() {
+        assert_eq!(
+            content_type("", b"\x89PNG\r\n\x1a\n....IHDR....IDAT"),
+            "image/png"
+        );
+        assert_eq!(
+            content_type("", b"\x89PNG\r\n\x1a\n....acTL....IDAT"),
+            "image/apng"
+        );
+        assert_eq!(content_type("", b"\xff\xd8\xff\xe0"), "image/jpeg");
+        assert_eq!(content_type("", b"GIF89a"), "image/gif");
+        assert_eq!(content_type("", b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(content_type("", b"\0\0\0\x1cftypavif"), "image/avif");
+        assert_eq!(content_type("", b"\x1a\x45\xdf\xa3"), "video/webm");
+        assert_eq!(content_type("", b"glTF\x02\0\0\0"), "model/gltf-binary");
+        assert_eq!(content_type("", b"wOF2"), "font/woff2");
+        assert_eq!(content_type("", b"\0\x01\0\0"), "font/ttf");
+        // magic bytes win over a wrong extension
+        assert_eq!(content_type("image.txt", b"GIF87a"), "image/gif");
+    }
+
+    #[test]
+    fn other_iso_media_brands_are_not_mp4() {
+        for data in [
+            b"   ftypheic    ".as_slice(),
+            b"   ftypM4A     ",
+            b"   ftypqt      ",
+        ] {
+            assert_eq!(Media::sniff_binary(data), None);
+            assert!(Media::content_type_for_file("", data, None).is_err());
+        }
+        assert_eq!(
+            Media::content_type_for_file("", b"   ftypheic    ", Some("image/heic"))
+                .unwrap()
+                .0,
+            "image/heic"
+        );
+    }
+
+    #[test]
+    fn content_type_from_text() {
+        assert_eq!(
+            content_type("", b"<?xml version=\"1.0\"?><svg></svg>"),
+            "image/svg+xml"
+        );
+        assert_eq!(
+            content_type("", b"\n<!DOCTYPE html><html></html>"),
+            "text/html;charset=utf-8"
+        );
+        assert_eq!(content_type("", br#"{"p":"brc-20"}"#), "application/json");
+        assert_eq!(
+            content_type("", br#"{"asset":{"version":"2.0"}}"#),
+            "model/gltf+json"
+        );
+        assert_eq!(content_type("", b"hello"), "text/plain;charset=utf-8");
+        // the extension wins over the shape of text
+        assert_eq!(content_type("a.md", b"{}"), "text/markdown;charset=utf-8");
+
+        assert!(Media::content_type_for_file("", &[0xff, 0xfe, 0x00], None).is_err());
+    }
+
+    #[test]
+    fn content_type_override() {
+        let (content_type, mode) =
+            Media::content_type_for_file("", b"hello", Some("text/css")).unwrap();
+        assert_eq!(content_type, "text/css");
+        assert_eq!(mode, BROTLI_MODE_TEXT);
+        assert_eq!(
+            content_type.parse::<Media>().unwrap(),
+            Media::Code(Language::Css)
+        );
+
+        assert!(Media::content_type_for_file("", b"hello", Some("")).is_err());
+        assert!(Media::content_type_for_file("", b"hello", Some("text/\nplain")).is_err());
+    }
+}
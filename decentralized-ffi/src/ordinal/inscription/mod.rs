@@ -1,13 +1,18 @@
 use crate::ordinal::inscription::{
-    batch::Mode, inscription::Inscription, inscription_id::InscriptionId,
+    batch::Mode, inscription::Inscription, inscription_id::InscriptionId, media::Media,
 };
 
+use crate::descriptor::Descriptor;
+use crate::ordinal::dummy_transaction::DummyTransaction;
+use crate::ordinal::rune::MAX_RUNESTONE_SCRIPT_SIZE;
 use crate::types::LocalOutput;
+use crate::wallet::Wallet;
 use anyhow::{bail, Context, Result};
 use bdk_wallet::bitcoin::transaction::Version;
 use bdk_wallet::bitcoin::{
     absolute::LockTime,
     address::NetworkChecked,
+    hashes::Hash,
     key::{
         constants::SCHNORR_SIGNATURE_SIZE, TapTweak, TweakedPublicKey, UntweakedKeypair,
         XOnlyPublicKey,
@@ -18,14 +23,19 @@ use bdk_wallet::bitcoin::{
     script, secp256k1,
     secp256k1::{rand::thread_rng, Secp256k1},
     sighash::{Prevouts, SighashCache, TapSighashType},
-    taproot::{ControlBlock, LeafVersion, Signature, TapLeafHash, TaprootBuilder},
-    Address, AddressType, Amount, FeeRate, Network, OutPoint, PrivateKey, Script, ScriptBuf,
-    Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+    taproot::{
+        ControlBlock, LeafVersion, Signature, TapLeafHash, TaprootBuilder, TaprootSpendInfo,
+    },
+    Address, AddressType, Amount, FeeRate, Network, OutPoint, PrivateKey, PubkeyHash, Script,
+    ScriptBuf, ScriptHash, Sequence, Transaction, TxIn, TxOut, Txid, WPubkeyHash, WScriptHash,
+    Witness, WitnessProgram, WitnessVersion,
 };
+use bdk_wallet::descriptor::ExtendedDescriptor;
 use bdk_wallet::{bitcoin, serde_json};
 use ciborium::Value;
 use derive_more::Display;
 use ordinals::{Sat, SatPoint};
+use recovery::InscriptionSession;
 use serde::{Deserialize, Serialize};
 use std::convert::{TryFrom, TryInto};
 use std::sync::Arc;
@@ -38,18 +48,19 @@ mod common;
 pub mod config;
 mod envelope;
 mod inscription;
-mod inscription_id;
+pub(crate) mod inscription_id;
+pub(crate) mod recovery;
 
-mod decimal;
-// mod decimal_sat;
-// mod degree;
+pub(crate) mod decimal;
+pub(crate) mod decimal_sat;
+pub(crate) mod degree;
 mod deserialize_from_str;
-// mod epoch;
-// mod height;
-mod media;
-// mod rarity;
-// mod sat;
-// mod sat_point;
+pub(crate) mod epoch;
+pub(crate) mod height;
+pub(crate) mod media;
+pub(crate) mod rarity;
+pub(crate) mod sat;
+mod sat_point;
 
 pub struct Client;
 
@@ -61,6 +72,29 @@ pub struct Client;
 pub struct NamedFile {
     pub name: String,
     pub data: Vec<u8>,
+    /// MIME type of `data`, detected from its magic bytes or the extension of `name` when unset
+    #[uniffi(default = None)]
+    pub content_type: Option<String>,
+}
+
+/// Detected content type of a file and how it will be displayed.
+#[derive(uniffi::Record, Debug, Clone, PartialEq)]
+pub struct ContentInfo {
+    pub content_type: String,
+    pub media: Media,
+}
+
+/// Detect the content type `mint` would inscribe `file` with.
+#[uniffi::export]
+pub fn detect_content_type(file: NamedFile) -> Result<ContentInfo, MintError> {
+    let (content_type, _) =
+        Media::content_type_for_file(&file.name, &file.data, file.content_type.as_deref())
+            .map_err(|e| MintError::AnyError(e.to_string()))?;
+    let media = content_type.parse().unwrap_or(Media::Unknown);
+    Ok(ContentInfo {
+        content_type,
+        media,
+    })
 }
 
 #[derive(uniffi::Error, Debug, Display)]
@@ -85,7 +119,130 @@ where
     }
 }
 
+#[derive(uniffi::Record, Debug, Clone, PartialEq, Eq)]
+pub struct InscriptionRecord {
+    pub content_type: Option<String>,
+    pub body: Option<Vec<u8>>,
+    pub content_encoding: Option<String>,
+    /// CBOR metadata decoded to a JSON string
+    pub metadata: Option<String>,
+    pub metaprotocol: Option<String>,
+    /// parent inscription ids, formatted as `<txid>i<index>`
+    pub parents: Vec<String>,
+    /// inscription whose content is shown instead of this one
+    pub delegate: Option<String>,
+    pub pointer: Option<u64>,
+    /// index of the input carrying the envelope
+    pub input: u32,
+    /// index of the envelope within the input
+    pub offset: u32,
+    pub duplicate_field: bool,
+    pub incomplete_field: bool,
+    pub unrecognized_even_field: bool,
+    pub stutter: bool,
+    pub pushnum: bool,
+}
+
+impl From<envelope::ParsedEnvelope> for InscriptionRecord {
+    fn from(envelope: envelope::ParsedEnvelope) -> Self {
+        let inscription = &envelope.payload;
+        InscriptionRecord {
+            content_type: inscription.content_type().map(str::to_string),
+            content_encoding: inscription
+                .content_encoding
+                .as_ref()
+                .map(|encoding| String::from_utf8_lossy(encoding).to_string()),
+            metadata: inscription
+                .metadata()
+                .and_then(|metadata| serde_json::to_string(&metadata).ok()),
+            metaprotocol: inscription.metaprotocol().map(str::to_string),
+            parents: inscription
+                .parents()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            delegate: inscription.delegate().map(|id| id.to_string()),
+            pointer: inscription.pointer(),
+            body: inscription.body.clone(),
+            input: envelope.input,
+            offset: envelope.offset,
+            duplicate_field: inscription.duplicate_field,
+            incomplete_field: inscription.incomplete_field,
+            unrecognized_even_field: inscription.unrecognized_even_field,
+            stutter: envelope.stutter,
+            pushnum: envelope.pushnum,
+        }
+    }
+}
+
+/// Parse all inscription envelopes found in the taproot witnesses of `tx`.
 #[uniffi::export]
+pub fn parse_inscriptions(tx: &crate::Transaction) -> Vec<InscriptionRecord> {
+    envelope::ParsedEnvelope::from_transaction(&tx.0)
+        .into_iter()
+        .map(InscriptionRecord::from)
+        .collect()
+}
+
+/// Parent inscription and the wallet output currently holding it.
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct InscriptionParent {
+    /// inscription id formatted as `<txid>i<index>`
+    pub inscription_id: String,
+    pub utxo: LocalOutput,
+}
+
+/// Inscription metadata, stored on chain as CBOR.
+#[derive(uniffi::Enum, Debug, Clone)]
+pub enum InscriptionMetadata {
+    /// JSON document, converted to CBOR
+    Json { json: String },
+    /// CBOR encoded value, stored as is
+    Cbor { cbor: Vec<u8> },
+}
+
+/// Optional inscription fields.
+#[derive(uniffi::Record, Debug, Clone, Default)]
+pub struct InscriptionOptions {
+    #[uniffi(default = None)]
+    pub metadata: Option<InscriptionMetadata>,
+    #[uniffi(default = None)]
+    pub metaprotocol: Option<String>,
+    /// sat offset of the inscription in the reveal outputs, past the parent output if any
+    #[uniffi(default = None)]
+    pub pointer: Option<u64>,
+    /// brotli compress the body when it gets smaller
+    #[uniffi(default = false)]
+    pub compress: bool,
+    /// inscription whose content is shown instead, the file may then be empty
+    #[uniffi(default = None)]
+    pub delegate: Option<String>,
+    /// inscribe the first sat of this output instead of a fresh one
+    #[uniffi(default = None)]
+    pub satpoint: Option<InscriptionSatpoint>,
+    /// allow `satpoint` to be a sat that is already inscribed
+    #[uniffi(default = false)]
+    pub reinscribe: bool,
+}
+
+/// Wallet output whose first sat gets the inscription.
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct InscriptionSatpoint {
+    pub utxo: LocalOutput,
+    /// inscriptions already on the first sat of `utxo`, formatted as `<txid>i<index>`
+    pub inscription_ids: Vec<String>,
+}
+
+/// Inscribe `file` to `to_addr`.
+///
+/// With a `parent` the reveal spends the parent output and sends it back to the same script, the
+/// child is inscribed with the parent tag. The parent input can only be signed by the wallet, so
+/// the reveal is then returned as `reveal_psbt` with the inscription input already finalized.
+///
+/// `descriptor` is the descriptor of `utxos` and the parent output, fees are sized by its largest
+/// satisfaction. Without it every input is assumed to be a single key spend of its script type.
+#[allow(clippy::too_many_arguments)]
+#[uniffi::export(default(parent = None, options = None, descriptor = None))]
 pub async fn mint(
     network: Network,
     utxos: Vec<LocalOutput>,
@@ -94,89 +251,657 @@ pub async fn mint(
     to_addr: &str,
     fee_rate: u64,
     postage: Option<u64>,
+    parent: Option<InscriptionParent>,
+    options: Option<InscriptionOptions>,
+    descriptor: Option<Arc<Descriptor>>,
 ) -> Result<Output, MintError> {
-    let destination = Address::from_str(to_addr)?.require_network(network)?;
+    let funding = CommitFunding::Utxos {
+        pay_address: Address::from_str(pay_address)?.require_network(network)?,
+        utxos,
+    };
 
-    // 1. Legacy (P2PKH) 以 1 开始的地址限制输出为 546 sats
-    // 2. Nested Segwit (P2SH-P2WPKH) 以 3 开始的地址限制输出为 540 sats
-    // 3. NativeSegwit (P2WPKH) 以 bc1q 开始的地址限制输出为 294 sats
-    // 4. Taproot (P2TR) 以 bc1p 开始的地址限制输出为 330 sats
-    let postage = if let Some(postage) = postage {
-        postage
-    } else {
-        match destination.address_type() {
-            None => 546,
-            Some(typ) => match typ {
-                AddressType::P2pkh => 546,
-                AddressType::P2sh => 540,
-                AddressType::P2wpkh => 294,
-                AddressType::P2wsh => 546,
-                AddressType::P2tr => 330,
-                _ => 546,
-            },
+    Inscribe::for_file(
+        network,
+        funding,
+        file,
+        to_addr,
+        fee_rate,
+        postage,
+        parent,
+        options,
+        descriptor.map(|descriptor| descriptor.extended_descriptor.clone()),
+    )?
+    .run(network)
+    .await
+    .map_err(|e| MintError::AnyError(e.to_string()))
+}
+
+/// Inscribe `file` to `to_addr`, funding the commit from `wallet`.
+///
+/// The commit goes through the wallet coin selection with change to the internal keychain, so the
+/// returned commit PSBT carries the derivation info needed to sign it. The wallet knows nothing
+/// about inscriptions and runes, outputs holding them must be listed in `unspendable`.
+///
+/// The reveal fee covers the largest satisfaction of `descriptor` for the parent input, which
+/// defaults to the wallet descriptor of the parent keychain.
+#[allow(clippy::too_many_arguments)]
+#[uniffi::export(default(postage = None, parent = None, options = None, descriptor = None))]
+pub async fn mint_with_wallet(
+    wallet: Arc<Wallet>,
+    file: NamedFile,
+    to_addr: &str,
+    fee_rate: u64,
+    unspendable: Vec<crate::bitcoin::OutPoint>,
+    postage: Option<u64>,
+    parent: Option<InscriptionParent>,
+    options: Option<InscriptionOptions>,
+    descriptor: Option<Arc<Descriptor>>,
+) -> Result<Output, MintError> {
+    let network = wallet.network();
+    let descriptor = wallet_descriptor(&wallet, descriptor, parent.as_ref());
+    let funding = CommitFunding::Wallet {
+        wallet,
+        unspendable: unspendable.into_iter().map(OutPoint::from).collect(),
+    };
+
+    Inscribe::for_file(
+        network, funding, file, to_addr, fee_rate, postage, parent, options, descriptor,
+    )?
+    .run(network)
+    .await
+    .map_err(|e| MintError::AnyError(e.to_string()))
+}
+
+// 1. Legacy (P2PKH) 以 1 开始的地址限制输出为 546 sats
+// 2. Nested Segwit (P2SH-P2WPKH) 以 3 开始的地址限制输出为 540 sats
+// 3. NativeSegwit (P2WPKH) 以 bc1q 开始的地址限制输出为 294 sats
+// 4. Taproot (P2TR) 以 bc1p 开始的地址限制输出为 330 sats
+pub(crate) fn default_postage(destination: &Address) -> Amount {
+    let postage = match destination.address_type() {
+        None => 546,
+        Some(typ) => match typ {
+            AddressType::P2pkh => 546,
+            AddressType::P2sh => 540,
+            AddressType::P2wpkh => 294,
+            AddressType::P2wsh => 546,
+            AddressType::P2tr => 330,
+            _ => 546,
+        },
+    };
+    Amount::from_sat(postage)
+}
+
+/// How the inscriptions of a batch are laid out in the reveal outputs.
+#[derive(uniffi::Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchMode {
+    /// all inscriptions on the first sat of a single output
+    SameSat,
+    /// one output per inscription
+    SeparateOutputs,
+    /// a single output holding every inscription at its own offset
+    SharedOutput,
+}
+
+impl From<BatchMode> for Mode {
+    fn from(mode: BatchMode) -> Self {
+        match mode {
+            BatchMode::SameSat => Mode::SameSat,
+            BatchMode::SeparateOutputs => Mode::SeparateOutputs,
+            BatchMode::SharedOutput => Mode::SharedOutput,
         }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct BatchEntry {
+    pub file: NamedFile,
+    /// destination of this inscription, only in `SeparateOutputs` mode
+    pub destination: Option<String>,
+    pub metadata: Option<InscriptionMetadata>,
+    /// postage of this inscription, not in `SameSat` mode
+    pub postage: Option<u64>,
+}
+
+/// `descriptor`, or the wallet descriptor of the parent keychain when a parent is spent.
+fn wallet_descriptor(
+    wallet: &Wallet,
+    descriptor: Option<Arc<Descriptor>>,
+    parent: Option<&InscriptionParent>,
+) -> Option<ExtendedDescriptor> {
+    match (descriptor, parent) {
+        (Some(descriptor), _) => Some(descriptor.extended_descriptor.clone()),
+        (None, Some(parent)) => Some(
+            wallet
+                .get_wallet()
+                .public_descriptor(parent.utxo.keychain)
+                .clone(),
+        ),
+        (None, None) => None,
+    }
+}
+
+/// Inscribe `entries` with a single commit/reveal pair.
+///
+/// Entries without a destination go to `to_addr`, entries without postage use `postage` or the
+/// dust limit of their destination. The returned inscriptions are listed in entry order with
+/// their satpoints in the reveal transaction. Fees are sized by `descriptor` as in [`mint`].
+#[allow(clippy::too_many_arguments)]
+#[uniffi::export(default(postage = None, parent = None, descriptor = None))]
+pub async fn mint_batch(
+    network: Network,
+    utxos: Vec<LocalOutput>,
+    entries: Vec<BatchEntry>,
+    mode: BatchMode,
+    pay_address: &str,
+    to_addr: &str,
+    fee_rate: u64,
+    postage: Option<u64>,
+    parent: Option<InscriptionParent>,
+    descriptor: Option<Arc<Descriptor>>,
+) -> Result<Output, MintError> {
+    let funding = CommitFunding::Utxos {
+        pay_address: Address::from_str(pay_address)?.require_network(network)?,
+        utxos,
     };
+    inscribe_batch(
+        network,
+        funding,
+        entries,
+        mode,
+        to_addr,
+        fee_rate,
+        postage,
+        parent,
+        descriptor.map(|descriptor| descriptor.extended_descriptor.clone()),
+    )
+    .await
+}
+
+/// [`mint_batch`] funding the commit from `wallet`, as [`mint_with_wallet`] does.
+#[allow(clippy::too_many_arguments)]
+#[uniffi::export(default(postage = None, parent = None, descriptor = None))]
+pub async fn mint_batch_with_wallet(
+    wallet: Arc<Wallet>,
+    entries: Vec<BatchEntry>,
+    mode: BatchMode,
+    to_addr: &str,
+    fee_rate: u64,
+    unspendable: Vec<crate::bitcoin::OutPoint>,
+    postage: Option<u64>,
+    parent: Option<InscriptionParent>,
+    descriptor: Option<Arc<Descriptor>>,
+) -> Result<Output, MintError> {
+    let network = wallet.network();
+    let descriptor = wallet_descriptor(&wallet, descriptor, parent.as_ref());
+    let funding = CommitFunding::Wallet {
+        wallet,
+        unspendable: unspendable.into_iter().map(OutPoint::from).collect(),
+    };
+    inscribe_batch(
+        network, funding, entries, mode, to_addr, fee_rate, postage, parent, descriptor,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn inscribe_batch(
+    network: Network,
+    funding: CommitFunding,
+    entries: Vec<BatchEntry>,
+    mode: BatchMode,
+    to_addr: &str,
+    fee_rate: u64,
+    postage: Option<u64>,
+    parent: Option<InscriptionParent>,
+    descriptor: Option<ExtendedDescriptor>,
+) -> Result<Output, MintError> {
+    let destination = Address::from_str(to_addr)?.require_network(network)?;
+    let (parent, parent_utxo) = match parent {
+        Some(parent) => (
+            Some(InscriptionId::from_str(&parent.inscription_id)?),
+            Some(parent.utxo),
+        ),
+        None => (None, None),
+    };
+
+    let mut inscriptions = Vec::new();
+    for entry in entries {
+        inscriptions.push(batch::Entry {
+            destination: entry
+                .destination
+                .map(|destination| Address::from_str(&destination)?.require_network(network))
+                .transpose()?,
+            metadata: entry
+                .metadata
+                .map(Inscribe::metadata_to_cbor)
+                .transpose()
+                .map_err(|e| MintError::AnyError(e.to_string()))?,
+            postage: entry.postage.map(Amount::from_sat),
+            file: entry.file,
+        });
+    }
+    let postage = postage.map(Amount::from_sat);
 
     Inscribe {
-        pay_address: Address::from_str(pay_address)?.require_network(network)?,
-        destination: Address::from_str(to_addr)?.require_network(network)?,
+        funding,
         fee_rate: FeeRate::from_sat_per_vb_unchecked(fee_rate),
-        file: Some((file.name.to_string(), file.data)),
-        postage: Amount::from_sat(postage),
+        file: None,
+        batch: Some(batch::Batchfile {
+            mode: mode.into(),
+            postage,
+            inscriptions,
+        }),
+        postage: postage.unwrap_or_else(|| default_postage(&destination)),
+        destination,
 
         json_metadata: None,
+        cbor_metadata: None,
+        metadata: None,
         metaprotocol: None,
+        pointer: None,
+        compress: false,
+        delegate: None,
+        reinscribe: false,
+        satpoint_utxo: None,
+        descriptor,
         dry_run: false,
-        batch: None,
-        cbor_metadata: None,
         commit_fee_rate: None,
-        compress: false,
         no_backup: false,
         no_limit: false,
-        parent: None,
-        reinscribe: false,
+        parent,
+        parent_utxo,
         satpoint: None,
         sat: None,
     }
-    .run(
-        // api,
-        network, utxos,
-    )
+    .run(network)
     .await
     .map_err(|e| MintError::AnyError(e.to_string()))
 }
 
+/// Sizes and fees of an inscription commit/reveal pair, fees and postage in sats.
+#[derive(uniffi::Record, Debug, Clone, PartialEq, Eq)]
+pub struct InscriptionCost {
+    pub commit_vsize: u64,
+    pub reveal_vsize: u64,
+    pub commit_fee: u64,
+    pub reveal_fee: u64,
+    /// value of the inscription outputs
+    pub postage: u64,
+    /// commit and reveal fee
+    pub total_fee: u64,
+}
+
+/// Estimate the cost of inscribing `files` to `destination_type` outputs without wallet UTXOs.
+///
+/// The reveal is built as by `mint` for a single file and as by `mint_batch` otherwise, the commit
+/// is assumed to spend a single input of `destination_type` with change. Postage defaults to the
+/// dust limit of the destination.
+#[allow(clippy::too_many_arguments)]
+#[uniffi::export(default(postage = None))]
+pub fn estimate_inscription_cost(
+    network: Network,
+    files: Vec<NamedFile>,
+    destination_type: AddressType,
+    commit_fee_rate: u64,
+    reveal_fee_rate: u64,
+    postage: Option<u64>,
+    mode: BatchMode,
+) -> Result<InscriptionCost, MintError> {
+    let script_pubkey = match destination_type {
+        AddressType::P2pkh => ScriptBuf::new_p2pkh(&PubkeyHash::all_zeros()),
+        AddressType::P2sh => ScriptBuf::new_p2sh(&ScriptHash::all_zeros()),
+        AddressType::P2wpkh => ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+        AddressType::P2wsh => ScriptBuf::new_p2wsh(&WScriptHash::all_zeros()),
+        AddressType::P2tr => {
+            ScriptBuf::new_witness_program(&WitnessProgram::new(WitnessVersion::V1, &[0; 32])?)
+        }
+        _ => {
+            return Err(MintError::AnyError(format!(
+                "unsupported destination type {destination_type}"
+            )));
+        }
+    };
+    let destination = Address::from_script(&script_pubkey, network)?;
+    let postage = postage.map(Amount::from_sat);
+
+    let (inscriptions, destinations, postages, mode) = if files.len() == 1 {
+        let postage = postage.unwrap_or_else(|| default_postage(&destination));
+        let inscription = Inscription::from_bytes(
+            network,
+            files.into_iter().next().unwrap(),
+            None,
+            None,
+            None,
+            None,
+            false,
+            None,
+        )
+        .map_err(|e| MintError::AnyError(e.to_string()))?;
+        (
+            vec![inscription],
+            vec![destination],
+            vec![postage],
+            Mode::SeparateOutputs,
+        )
+    } else {
+        let mode = Mode::from(mode);
+        let (inscriptions, destinations, postages) = batch::Batchfile {
+            mode,
+            postage,
+            inscriptions: files
+                .into_iter()
+                .map(|file| batch::Entry {
+                    file,
+                    destination: None,
+                    metadata: None,
+                    postage: None,
+                })
+                .collect(),
+        }
+        .inscriptions(network, None, None, &destination, false)
+        .map_err(|e| MintError::AnyError(e.to_string()))?;
+        (inscriptions, destinations, postages, mode)
+    };
+
+    Batch {
+        commit_fee_rate: FeeRate::from_sat_per_vb_unchecked(commit_fee_rate),
+        reveal_fee_rate: FeeRate::from_sat_per_vb_unchecked(reveal_fee_rate),
+        destinations,
+        inscriptions,
+        mode,
+        postage: postages.first().copied().unwrap_or_default(),
+        postages,
+        dry_run: true,
+        ..Default::default()
+    }
+    .estimate(script_pubkey, network)
+    .map_err(|e| MintError::AnyError(e.to_string()))
+}
+
+#[derive(uniffi::Record)]
+pub struct EtchOutput {
+    pub commit_psbt_tx: Arc<crate::Psbt>,
+    pub reveal_tx: Arc<crate::Transaction>,
+    pub reveal_private_key: String,
+    /// network fees of the commit and reveal transactions in sat
+    pub total_fees: u64,
+    /// etched rune with spacers, e.g. `UNCOMMON•GOODS`
+    pub rune: String,
+    pub inscription_id: Option<String>,
+    /// confirmations the commit transaction needs before the reveal can be mined
+    pub commit_confirmations: u16,
+    pub session: InscriptionSession,
+}
+
+/// Etch a rune with a commit/reveal pair.
+///
+/// The reveal tapscript carries the rune commitment, the reveal transaction carries the runestone
+/// with the etching and, when `file` is given, an inscription sent to `to_addr`. Any premine is
+/// sent to `to_addr` as well. The reveal is only valid once the commit transaction has
+/// `commit_confirmations` confirmations, so it must be kept until then. When `block_height` is
+/// given the rune name is checked to be unlocked at the height the reveal can be mined. Commit
+/// fees are sized by `descriptor` as in [`mint`].
+#[allow(clippy::too_many_arguments)]
+#[uniffi::export(default(postage = None, block_height = None, descriptor = None))]
+pub async fn etch_rune(
+    network: Network,
+    utxos: Vec<LocalOutput>,
+    etching: crate::ordinal::rune::Etching,
+    file: Option<NamedFile>,
+    pay_address: &str,
+    to_addr: &str,
+    fee_rate: u64,
+    postage: Option<u64>,
+    block_height: Option<u32>,
+    descriptor: Option<Arc<Descriptor>>,
+) -> Result<EtchOutput, MintError> {
+    let funding = CommitFunding::Utxos {
+        pay_address: Address::from_str(pay_address)?.require_network(network)?,
+        utxos,
+    };
+    etch(
+        network,
+        funding,
+        etching,
+        file,
+        to_addr,
+        fee_rate,
+        postage,
+        block_height,
+        descriptor.map(|descriptor| descriptor.extended_descriptor.clone()),
+    )
+    .await
+}
+
+/// [`etch_rune`] funding the commit from `wallet`, as [`mint_with_wallet`] does.
+#[allow(clippy::too_many_arguments)]
+#[uniffi::export(default(postage = None, block_height = None))]
+pub async fn etch_rune_with_wallet(
+    wallet: Arc<Wallet>,
+    etching: crate::ordinal::rune::Etching,
+    file: Option<NamedFile>,
+    to_addr: &str,
+    fee_rate: u64,
+    unspendable: Vec<crate::bitcoin::OutPoint>,
+    postage: Option<u64>,
+    block_height: Option<u32>,
+) -> Result<EtchOutput, MintError> {
+    let network = wallet.network();
+    let funding = CommitFunding::Wallet {
+        wallet,
+        unspendable: unspendable.into_iter().map(OutPoint::from).collect(),
+    };
+    etch(
+        network,
+        funding,
+        etching,
+        file,
+        to_addr,
+        fee_rate,
+        postage,
+        block_height,
+        None,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn etch(
+    network: Network,
+    funding: CommitFunding,
+    etching: crate::ordinal::rune::Etching,
+    file: Option<NamedFile>,
+    to_addr: &str,
+    fee_rate: u64,
+    postage: Option<u64>,
+    block_height: Option<u32>,
+    descriptor: Option<ExtendedDescriptor>,
+) -> Result<EtchOutput, MintError> {
+    let destination = Address::from_str(to_addr)?.require_network(network)?;
+    let etching = ordinals::Etching::try_from(&etching)?;
+
+    let Some(rune) = etching.rune else {
+        return Err(MintError::AnyError(
+            "etching must specify a rune".to_string(),
+        ));
+    };
+
+    if rune.is_reserved() {
+        return Err(MintError::AnyError(format!("rune `{rune}` is reserved")));
+    }
+
+    if let Some(block_height) = block_height {
+        let reveal_height = block_height + u32::from(ordinals::Runestone::COMMIT_CONFIRMATIONS);
+        let minimum = ordinals::Rune::minimum_at_height(network, ordinals::Height(reveal_height));
+        if rune < minimum {
+            return Err(MintError::AnyError(format!(
+                "rune `{rune}` is not unlocked at height {reveal_height}, minimum is `{minimum}`"
+            )));
+        }
+    }
+
+    let postage = postage
+        .map(Amount::from_sat)
+        .unwrap_or(destination.script_pubkey().minimal_non_dust());
+
+    let inscriptions = file
+        .map(|file| Inscription::from_bytes(network, file, None, None, None, None, false, None))
+        .transpose()
+        .map_err(|e| MintError::AnyError(e.to_string()))?
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let has_inscription = !inscriptions.is_empty();
+    let fee_rate = FeeRate::from_sat_per_vb_unchecked(fee_rate);
+    let output = Batch {
+        commit_fee_rate: fee_rate,
+        destinations: inscriptions.iter().map(|_| destination.clone()).collect(),
+        etching: Some(EtchingInfo {
+            destination: destination.clone(),
+            etching,
+        }),
+        inscriptions,
+        mode: Mode::SeparateOutputs,
+        postage,
+        reveal_fee_rate: fee_rate,
+        descriptor,
+        ..Default::default()
+    }
+    .inscribe(funding, network)
+    .await
+    .map_err(|e| MintError::AnyError(e.to_string()))?;
+
+    let reveal_txid = output.reveal_tx.0.compute_txid();
+
+    Ok(EtchOutput {
+        inscription_id: has_inscription.then(|| {
+            InscriptionId {
+                txid: reveal_txid,
+                index: 0,
+            }
+            .to_string()
+        }),
+        rune: ordinals::SpacedRune::new(rune, etching.spacers.unwrap_or_default()).to_string(),
+        commit_confirmations: ordinals::Runestone::COMMIT_CONFIRMATIONS,
+        commit_psbt_tx: output.commit_psbt_tx,
+        reveal_tx: output.reveal_tx,
+        reveal_private_key: output.reveal_private_key,
+        total_fees: output.total_fees,
+        session: output.session,
+    })
+}
+
 pub(crate) struct Inscribe {
-    pub(crate) pay_address: Address<NetworkChecked>,
+    pub(crate) funding: CommitFunding,
     pub(crate) destination: Address<NetworkChecked>, // 接收地址
     pub(crate) fee_rate: FeeRate,                    // 费率
-    pub(crate) file: Option<(String, Vec<u8>)>,      // 文件名-文件数据
-    pub(crate) batch: Option<Vec<(String, Vec<u8>)>>,
+    pub(crate) file: Option<NamedFile>,
+    pub(crate) batch: Option<batch::Batchfile>,
     pub(crate) json_metadata: Option<PathBuf>,
+    pub(crate) cbor_metadata: Option<PathBuf>,
+    pub(crate) metadata: Option<InscriptionMetadata>, // 内存中的元数据, 优先于文件
     pub(crate) metaprotocol: Option<String>,
+    pub(crate) pointer: Option<u64>,
+    pub(crate) compress: bool,
+    pub(crate) delegate: Option<InscriptionId>,
+    pub(crate) reinscribe: bool,
+    pub(crate) satpoint_utxo: Option<(LocalOutput, Vec<InscriptionId>)>, // 铭刻到该 utxo 的第一个 sat, 及其上已有的铭文
+    pub(crate) descriptor: Option<ExtendedDescriptor>, // 支付 utxo 和父铭文的描述符, 用于估算手续费
     pub(crate) dry_run: bool,
     pub(crate) postage: Amount, // 默认 546
 
     // 下面暂不可用
-    pub(crate) cbor_metadata: Option<PathBuf>,
     pub(crate) commit_fee_rate: Option<FeeRate>,
-    pub(crate) compress: bool,
     pub(crate) no_backup: bool,
     pub(crate) no_limit: bool,
     pub(crate) parent: Option<InscriptionId>,
-    pub(crate) reinscribe: bool,
+    pub(crate) parent_utxo: Option<LocalOutput>, // 父铭文所在 utxo
     pub(crate) satpoint: Option<SatPoint>,
     pub(crate) sat: Option<Sat>,
 }
 
 impl Inscribe {
+    #[allow(clippy::too_many_arguments)]
+    fn for_file(
+        network: Network,
+        funding: CommitFunding,
+        file: NamedFile,
+        to_addr: &str,
+        fee_rate: u64,
+        postage: Option<u64>,
+        parent: Option<InscriptionParent>,
+        options: Option<InscriptionOptions>,
+        descriptor: Option<ExtendedDescriptor>,
+    ) -> Result<Self, MintError> {
+        let options = options.unwrap_or_default();
+        let destination = Address::from_str(to_addr)?.require_network(network)?;
+        let (parent, parent_utxo) = match parent {
+            Some(parent) => (
+                Some(InscriptionId::from_str(&parent.inscription_id)?),
+                Some(parent.utxo),
+            ),
+            None => (None, None),
+        };
+
+        let postage = postage
+            .map(Amount::from_sat)
+            .unwrap_or_else(|| default_postage(&destination));
+
+        let delegate = options
+            .delegate
+            .map(|delegate| InscriptionId::from_str(&delegate))
+            .transpose()?;
+
+        let satpoint_utxo = match options.satpoint {
+            Some(satpoint) => Some((
+                satpoint.utxo,
+                satpoint
+                    .inscription_ids
+                    .iter()
+                    .map(|id| InscriptionId::from_str(id))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            None => None,
+        };
+
+        Ok(Inscribe {
+            funding,
+            destination,
+            fee_rate: FeeRate::from_sat_per_vb_unchecked(fee_rate),
+            file: Some(file),
+            postage,
+
+            json_metadata: None,
+            cbor_metadata: None,
+            metadata: options.metadata,
+            metaprotocol: options.metaprotocol,
+            pointer: options.pointer,
+            compress: options.compress,
+            delegate,
+            reinscribe: options.reinscribe,
+            satpoint_utxo,
+            descriptor,
+            dry_run: false,
+            batch: None,
+            commit_fee_rate: None,
+            no_backup: false,
+            no_limit: false,
+            parent,
+            parent_utxo,
+            satpoint: None,
+            sat: None,
+        })
+    }
+
     pub(crate) async fn run(
         self,
         // cli: impl RemoteClient,
         network: Network,
-        utxos: Vec<LocalOutput>,
     ) -> Result<Output> {
-        let metadata = Inscribe::parse_metadata(self.cbor_metadata, self.json_metadata)?;
+        let metadata = match self.metadata {
+            Some(metadata) => Some(Inscribe::metadata_to_cbor(metadata)?),
+            None => Inscribe::parse_metadata(self.cbor_metadata, self.json_metadata)?,
+        };
 
         // let utxos_net = cli
         //     .get_utxo(&self.pay_address.to_string())
@@ -196,6 +921,7 @@ impl Inscribe {
         // }));
 
         let postage;
+        let postages;
         let destinations;
         let inscriptions;
         let mode;
@@ -204,9 +930,8 @@ impl Inscribe {
 
         match (self.file, self.batch) {
             (Some(file), None) => {
-                //todo 暂时不支持父子铭文
-                // parent_info = Inscribe::get_parent_info(self.parent, &index, &utxos, &client, chain)?; // todo
-                parent_info = None;
+                parent_info =
+                    Inscribe::get_parent_info(self.parent, self.parent_utxo.as_ref(), network)?;
 
                 postage = self.postage;
 
@@ -214,49 +939,38 @@ impl Inscribe {
                     network,
                     file,
                     self.parent,
-                    None,
+                    self.pointer,
                     self.metaprotocol,
                     metadata,
                     self.compress,
+                    self.delegate,
                 )?];
 
                 mode = Mode::SeparateOutputs;
 
+                postages = vec![postage];
+
                 sat = self.sat;
 
                 destinations = vec![self.destination.clone()];
             }
-            (None, Some(batch)) => {
-                // 暂不支持批量
-                unreachable!();
-                // let batchfile = Batchfile::load(&batch)?;
-                //
-                // // todo batch
-                // parent_info = Inscribe::get_parent_info(batchfile.parent, &index, &utxos, &client, chain)?;
-                //
-                // postage = batchfile
-                //   .postage
-                //   .map(Amount::from_sat)
-                //   .unwrap_or(TARGET_POSTAGE);
-                //
-                // (inscriptions, destinations) = batchfile.inscriptions(
-                //   &client,
-                //   chain,
-                //   parent_info.as_ref().map(|info| info.tx_out.value),
-                //   metadata,
-                //   postage,
-                //   self.compress,
-                // )?;
-                //
-                // mode = batchfile.mode;
-                //
-                // if batchfile.sat.is_some()
-                //   && mode != crate::subcommand::wallet::inscribe::batch::Mode::SameSat
-                // {
-                //   return Err(anyhow!("`sat` can only be set in `same-sat` mode"));
-                // }
-                //
-                // sat = batchfile.sat;
+            (None, Some(batchfile)) => {
+                parent_info =
+                    Inscribe::get_parent_info(self.parent, self.parent_utxo.as_ref(), network)?;
+
+                postage = batchfile.postage.unwrap_or(self.postage);
+
+                mode = batchfile.mode;
+
+                (inscriptions, destinations, postages) = batchfile.inscriptions(
+                    network,
+                    self.parent,
+                    parent_info.as_ref().map(|info| info.tx_out.value),
+                    &self.destination,
+                    self.compress,
+                )?;
+
+                sat = None;
             }
             _ => unreachable!(),
         }
@@ -277,21 +991,51 @@ impl Inscribe {
         //   self.satpoint
         // };
 
+        // 重复铭刻需要显式开启
+        let satpoint_utxo = match self.satpoint_utxo {
+            Some((utxo, inscriptions)) => {
+                let outpoint = OutPoint::from(utxo.outpoint.clone());
+                if !inscriptions.is_empty() && !self.reinscribe {
+                    bail!(
+                        "sat at {outpoint}:0 is already inscribed with {}, set `reinscribe` to inscribe it again",
+                        inscriptions
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
+                if inscriptions.is_empty() && self.reinscribe {
+                    bail!("`reinscribe` set but sat at {outpoint}:0 is not inscribed");
+                }
+                Some(utxo)
+            }
+            None if self.reinscribe => bail!("`reinscribe` requires the inscribed output"),
+            None => None,
+        };
+
         Batch {
             commit_fee_rate: self.commit_fee_rate.unwrap_or(self.fee_rate),
             destinations,
             dry_run: self.dry_run,
+            etching: None,
             inscriptions,
             mode,
             no_backup: self.no_backup,
             no_limit: self.no_limit,
             parent_info,
             postage,
+            postages,
             reinscribe: self.reinscribe,
             reveal_fee_rate: self.fee_rate,
-            satpoint: self.satpoint,
+            satpoint: satpoint_utxo.as_ref().map(|utxo| SatPoint {
+                outpoint: utxo.outpoint.clone().into(),
+                offset: 0,
+            }),
+            satpoint_utxo,
+            descriptor: self.descriptor,
         }
-        .inscribe(&self.pay_address, utxos, network)
+        .inscribe(self.funding, network)
         .await
     }
 
@@ -317,45 +1061,63 @@ impl Inscribe {
         }
     }
 
-    // fn get_parent_info(
-    //   parent: Option<InscriptionId>,
-    //   index: &Index,
-    //   utxos: &BTreeMap<OutPoint, Amount>,
-    //   client: &btc_api::Client,
-    //   chain: Chain,
-    //   to_addr: Address,
-    // ) -> crate::Result<Option<ParentInfo>> {
-    //   if let Some(parent_id) = parent {
-    //     if let Some(satpoint) = index.get_inscription_satpoint_by_id(parent_id)? {
-    //       if !utxos.contains_key(&satpoint.outpoint) {
-    //         return Err(anyhow!(format!("parent {parent_id} not in wallet")));
-    //       }
-    //
-    //       Ok(Some(ParentInfo {
-    //         destination: to_addr, //todo
-    //         id: parent_id,
-    //         location: satpoint,
-    //         tx_out: index
-    //           .get_transaction(satpoint.outpoint.txid)?
-    //           .expect("parent transaction not found in index")
-    //           .output
-    //           .into_iter()
-    //           .nth(satpoint.outpoint.vout.try_into().unwrap())
-    //           .expect("current transaction output"),
-    //       }))
-    //     } else {
-    //       Err(anyhow!(format!("parent {parent_id} does not exist")))
-    //     }
-    //   } else {
-    //     Ok(None)
-    //   }
-    // }
+    fn metadata_to_cbor(metadata: InscriptionMetadata) -> anyhow::Result<Vec<u8>> {
+        match metadata {
+            InscriptionMetadata::Json { json } => Inscribe::json_to_cbor(&json),
+            InscriptionMetadata::Cbor { cbor } => {
+                let _value: Value = ciborium::from_reader(Cursor::new(&cbor))
+                    .context("failed to parse CBOR metadata")?;
+
+                Ok(cbor)
+            }
+        }
+    }
+
+    fn json_to_cbor(json: &str) -> anyhow::Result<Vec<u8>> {
+        let value: serde_json::Value =
+            serde_json::from_str(json).context("failed to parse JSON metadata")?;
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&value, &mut cbor)?;
+
+        Ok(cbor)
+    }
+
+    fn get_parent_info(
+        parent: Option<InscriptionId>,
+        parent_utxo: Option<&LocalOutput>,
+        network: Network,
+    ) -> Result<Option<ParentInfo>> {
+        let Some(parent_id) = parent else {
+            return Ok(None);
+        };
+        let Some(utxo) = parent_utxo else {
+            bail!("parent {parent_id} output not given");
+        };
+
+        let tx_out: TxOut = (&utxo.txout).into();
+
+        // 父铭文原路返回
+        let destination = Address::from_script(&tx_out.script_pubkey, network)
+            .with_context(|| format!("parent {parent_id} output has no address"))?;
+
+        Ok(Some(ParentInfo {
+            destination,
+            id: parent_id,
+            location: SatPoint {
+                outpoint: utxo.outpoint.clone().into(),
+                offset: 0,
+            },
+            tx_out,
+        }))
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(uniffi::Record, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct InscriptionInfo {
-    pub id: InscriptionId,
-    pub location: SatPoint,
+    /// inscription id formatted as `<txid>i<index>`
+    pub id: String,
+    /// satpoint formatted as `<txid>:<vout>:<offset>`
+    pub location: String,
 }
 
 #[derive(uniffi::Record)]
@@ -365,9 +1127,15 @@ pub struct Output {
     pub reveal_tx: Arc<crate::Transaction>,
     pub reveal_private_key: String,
 
-    // pub parent: Option<InscriptionId>,
-    // pub inscriptions: Vec<InscriptionInfo>,
+    /// reveal spending a parent inscription, the parent input is left for the wallet to sign
+    pub reveal_psbt: Option<Arc<crate::Psbt>>,
+    /// parent inscription id formatted as `<txid>i<index>`
+    pub parent: Option<String>,
+    pub inscriptions: Vec<InscriptionInfo>,
+    /// network fees of the commit and reveal transactions in sat
     pub total_fees: u64,
+    /// store before broadcasting the commit to resume or recover the inscription
+    pub session: InscriptionSession,
 }
 
 #[derive(Clone, Debug)]
@@ -378,19 +1146,57 @@ pub(crate) struct ParentInfo {
     pub(crate) tx_out: TxOut,
 }
 
+#[derive(Clone, Debug)]
+pub(crate) struct EtchingInfo {
+    pub(crate) destination: Address, // premine
+    pub(crate) etching: ordinals::Etching,
+}
+
+/// Outputs paying for the commit transaction.
+pub(crate) enum CommitFunding {
+    /// spend `utxos` in order, change to `pay_address`
+    Utxos {
+        pay_address: Address,
+        utxos: Vec<LocalOutput>,
+    },
+    /// wallet coin selection, change to the internal keychain
+    Wallet {
+        wallet: Arc<Wallet>,
+        unspendable: Vec<OutPoint>,
+    },
+}
+
+struct RevealTemplate {
+    reveal_script: ScriptBuf,
+    taproot_spend_info: TaprootSpendInfo,
+    control_block: ControlBlock,
+    mint_addr: Address,
+    commit_input: usize,
+    reveal_inputs: Vec<OutPoint>,
+    reveal_outputs: Vec<TxOut>,
+    total_postage: Amount,
+    etching_postage: Amount,
+    reveal_fee: Amount,
+}
+
 pub(crate) struct Batch {
     pub(crate) commit_fee_rate: FeeRate,
     pub(crate) destinations: Vec<Address>,
     pub(crate) dry_run: bool,
+    pub(crate) etching: Option<EtchingInfo>,
     pub(crate) inscriptions: Vec<Inscription>,
     pub(crate) mode: Mode,
     pub(crate) no_backup: bool,
     pub(crate) no_limit: bool,
     pub(crate) parent_info: Option<ParentInfo>,
     pub(crate) postage: Amount,
+    pub(crate) postages: Vec<Amount>, // 每个铭文的 postage, 为空时使用 postage
     pub(crate) reinscribe: bool,
     pub(crate) reveal_fee_rate: FeeRate,
     pub(crate) satpoint: Option<SatPoint>,
+    pub(crate) satpoint_utxo: Option<LocalOutput>,
+    /// descriptor of the commit inputs and the parent output, sizes their satisfaction
+    pub(crate) descriptor: Option<ExtendedDescriptor>,
 }
 
 impl Default for Batch {
@@ -399,15 +1205,19 @@ impl Default for Batch {
             commit_fee_rate: FeeRate::from_sat_per_vb_unchecked(1),
             destinations: Vec::new(),
             dry_run: false,
+            etching: None,
             inscriptions: Vec::new(),
             mode: Mode::SharedOutput,
             no_backup: false,
             no_limit: false,
             parent_info: None,
             postage: Amount::from_sat(10_000),
+            postages: Vec::new(),
             reinscribe: false,
             reveal_fee_rate: FeeRate::from_sat_per_vb_unchecked(1),
             satpoint: None,
+            satpoint_utxo: None,
+            descriptor: None,
         }
     }
 }
@@ -415,132 +1225,415 @@ impl Default for Batch {
 impl Batch {
     pub(crate) async fn inscribe(
         &self,
-        pay_address: &Address,
-        utxos: Vec<LocalOutput>,
+        funding: CommitFunding,
         // client: impl RemoteClient,
         network: Network,
     ) -> Result<Output> {
         let (commit_tx, reveal_tx, recovery_key_pair, total_fees) = self
-            .create_batch_inscription_transactions(pay_address, utxos, network)
+            .create_batch_inscription_transactions(funding, network)
             .await?;
 
-        let signed_reveal_tx = if self.parent_info.is_some() {
-            // todo
-            // let input_utxos = commit_tx
-            //   .output
-            //   .iter()
-            //   .enumerate()
-            //   .map(|(vout, output)| SignRawTransactionInput {
-            //     txid: commit_tx.txid(),
-            //     vout: vout.try_into().unwrap(),
-            //     script_pub_key: output.script_pubkey.clone(),
-            //     redeem_script: None,
-            //     amount: Some(Amount::from_sat(output.value)),
-            //   })
-            //   .collect::<Vec<SignRawTransactionInput>>();
-            // vec![]
-            String::new()
-            // client
-            //   .sign_raw_transaction_with_wallet(
-            //     //tdo
-            //     &reveal_tx,
-            //     Some(
-            //       &commit_tx
-            //         .output
-            //         .iter()
-            //         .enumerate()
-            //         .map(|(vout, output)| SignRawTransactionInput {
-            //           txid: commit_tx.txid(),
-            //           vout: vout.try_into().unwrap(),
-            //           script_pub_key: output.script_pubkey.clone(),
-            //           redeem_script: None,
-            //           amount: Some(Amount::from_sat(output.value)),
-            //         })
-            //         .collect::<Vec<SignRawTransactionInput>>(),
-            //     ),
-            //     None,
-            //   )?
-            //   .hex
-        } else {
-            bitcoin::consensus::encode::serialize_hex(&reveal_tx)
+        let reveal_psbt = match &self.parent_info {
+            Some(parent_info) => Some(Self::reveal_psbt(&commit_tx, &reveal_tx, parent_info)?),
+            None => None,
         };
-
-        let commit_tx_hex = commit_tx.serialize_hex();
+        let locations = self.locations(&reveal_tx)?;
 
         Ok(self.output(
             commit_tx, // 未签名 传回给用户签名
             reveal_tx, // 已签名  存储到缓存或者数据库 等用户签名广播 commit 再广播
+            reveal_psbt,
+            locations,
             total_fees,
-            self.inscriptions.clone(),
             recovery_key_pair, //
+            network,
         ))
     }
 
+    fn postage_of(&self, index: usize) -> Amount {
+        self.postages.get(index).copied().unwrap_or(self.postage)
+    }
+
+    /// Reveal as a PSBT with the inscription input finalized and the parent input to be signed.
+    fn reveal_psbt(commit: &Psbt, reveal: &Transaction, parent_info: &ParentInfo) -> Result<Psbt> {
+        let mut unsigned_tx = reveal.clone();
+        for txin in unsigned_tx.input.iter_mut() {
+            txin.witness = Witness::new();
+        }
+
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx)?;
+        for (input, txin) in psbt.inputs.iter_mut().zip(reveal.input.iter()) {
+            if txin.previous_output == parent_info.location.outpoint {
+                input.witness_utxo = Some(parent_info.tx_out.clone());
+            } else {
+                input.witness_utxo = commit
+                    .unsigned_tx
+                    .output
+                    .get(txin.previous_output.vout as usize)
+                    .cloned();
+                input.final_script_witness = Some(txin.witness.clone());
+            }
+        }
+
+        Ok(psbt)
+    }
+
+    /// Satpoint of each inscription in the reveal.
+    ///
+    /// An inscription with a pointer lands on the sat it points to, which must be in one of the
+    /// reveal outputs after the parent output.
+    fn locations(&self, reveal: &Transaction) -> Result<Vec<SatPoint>> {
+        let txid = reveal.compute_txid();
+        let parent_value = match &self.parent_info {
+            Some(_) => reveal.output[0].value.to_sat(),
+            None => 0,
+        };
+        let total_value = reveal
+            .output
+            .iter()
+            .map(|txout| txout.value.to_sat())
+            .sum::<u64>();
+
+        let mut locations = Vec::new();
+        let mut shared_offset = 0;
+        for (index, inscription) in self.inscriptions.iter().enumerate() {
+            let location = match inscription.pointer() {
+                Some(pointer) => {
+                    if pointer < parent_value {
+                        bail!("pointer {pointer} of inscription {index} is in the parent output");
+                    }
+                    if pointer >= total_value {
+                        bail!(
+                            "pointer {pointer} of inscription {index} is outside the reveal \
+                             outputs of {total_value} sats"
+                        );
+                    }
+                    let mut start = 0;
+                    let mut location = None;
+                    for (vout, txout) in reveal.output.iter().enumerate() {
+                        let end = start + txout.value.to_sat();
+                        if pointer < end {
+                            location = Some(SatPoint {
+                                outpoint: OutPoint {
+                                    txid,
+                                    vout: u32::try_from(vout).unwrap(),
+                                },
+                                offset: pointer - start,
+                            });
+                            break;
+                        }
+                        start = end;
+                    }
+                    location.expect("pointer is within the reveal outputs")
+                }
+                None => {
+                    let vout = match self.mode {
+                        Mode::SharedOutput | Mode::SameSat => 0,
+                        Mode::SeparateOutputs => index,
+                    } + usize::from(self.parent_info.is_some());
+                    let offset = match self.mode {
+                        Mode::SharedOutput => shared_offset,
+                        Mode::SeparateOutputs | Mode::SameSat => 0,
+                    };
+                    SatPoint {
+                        outpoint: OutPoint {
+                            txid,
+                            vout: u32::try_from(vout).unwrap(),
+                        },
+                        offset,
+                    }
+                }
+            };
+            shared_offset += self.postage_of(index).to_sat();
+            locations.push(location);
+        }
+        Ok(locations)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn output(
         &self,
         commit: Psbt,
         reveal: Transaction,
+        reveal_psbt: Option<Psbt>,
+        locations: Vec<SatPoint>,
         total_fees: u64,
-        inscriptions: Vec<Inscription>,
         reveal_private_key: String,
+        network: Network,
     ) -> Output {
-        // let mut inscriptions_output = Vec::new();
-        // for index in 0..inscriptions.len() {
-        //     let index = u32::try_from(index).unwrap();
-        //
-        //     let vout = match self.mode {
-        //         Mode::SharedOutput | Mode::SameSat => {
-        //             if self.parent_info.is_some() {
-        //                 1
-        //             } else {
-        //                 0
-        //             }
-        //         }
-        //         Mode::SeparateOutputs => {
-        //             if self.parent_info.is_some() {
-        //                 index + 1
-        //             } else {
-        //                 index
-        //             }
-        //         }
-        //     };
-        //
-        //     let offset = match self.mode {
-        //         Mode::SharedOutput => u64::from(index) * self.postage.to_sat(),
-        //         Mode::SeparateOutputs | Mode::SameSat => 0,
-        //     };
-        //
-        //     inscriptions_output.push(InscriptionInfo {
-        //         id: InscriptionId {
-        //             txid: reveal.0,
-        //             index,
-        //         },
-        //         location: SatPoint {
-        //             outpoint: OutPoint {
-        //                 txid: reveal.0,
-        //                 vout,
-        //             },
-        //             offset,
-        //         },
-        //     });
-        // }
+        let txid = reveal.compute_txid();
+        let inscriptions_output: Vec<_> = locations
+            .into_iter()
+            .enumerate()
+            .map(|(index, location)| InscriptionInfo {
+                id: InscriptionId {
+                    txid,
+                    index: u32::try_from(index).unwrap(),
+                }
+                .to_string(),
+                location: location.to_string(),
+            })
+            .collect();
+
+        let parent = self.parent_info.as_ref().map(|info| info.id.to_string());
+        let parent_input = self.parent_info.as_ref().and_then(|info| {
+            reveal
+                .input
+                .iter()
+                .position(|txin| txin.previous_output == info.location.outpoint)
+                .map(|index| u32::try_from(index).unwrap())
+        });
+        let session = InscriptionSession::new(
+            network,
+            &commit,
+            &reveal,
+            reveal_psbt.as_ref(),
+            usize::from(self.parent_info.is_some()),
+            parent_input,
+            reveal_private_key.clone(),
+            parent.clone(),
+            inscriptions_output.clone(),
+        );
 
         Output {
             // commit: commit.0,
             // reveal: reveal.0,
             reveal_tx: Arc::new(crate::Transaction::from(reveal)),
+            reveal_psbt: reveal_psbt.map(|psbt| Arc::new(crate::Psbt::from(psbt))),
             total_fees,
-            // parent: self.parent_info.clone().map(|info| info.id),
-            // inscriptions: inscriptions_output,
+            parent,
+            inscriptions: inscriptions_output,
             commit_psbt_tx: Arc::new(crate::Psbt::from(commit)),
             reveal_private_key,
+            session,
+        }
+    }
+
+    /// Reveal script, commit address and reveal inputs/outputs, with the commit input still null.
+    fn reveal_template(
+        &self,
+        secp256k1: &Secp256k1<secp256k1::All>,
+        public_key: XOnlyPublicKey,
+        network: Network,
+    ) -> Result<RevealTemplate> {
+        let mut reveal_script = Inscription::append_batch_reveal_script_to_builder(
+            &self.inscriptions,
+            ScriptBuf::builder()
+                .push_slice(public_key.serialize())
+                .push_opcode(opcodes::all::OP_CHECKSIG),
+        );
+
+        // 符文承诺 reveal 脚本中必须包含 rune commitment
+        if let Some(EtchingInfo { etching, .. }) = &self.etching {
+            let rune = etching.rune.context("etching must specify a rune")?;
+            reveal_script = reveal_script
+                .push_opcode(opcodes::OP_FALSE)
+                .push_opcode(opcodes::all::OP_IF)
+                .push_slice(script::PushBytesBuf::try_from(rune.commitment())?)
+                .push_opcode(opcodes::all::OP_ENDIF);
+        }
+
+        let reveal_script = reveal_script.into_script();
+
+        let taproot_spend_info = TaprootBuilder::new()
+            .add_leaf(0, reveal_script.clone())
+            .expect("adding leaf should work")
+            .finalize(secp256k1, public_key)
+            .expect("finalizing taproot builder should work");
+
+        let control_block = taproot_spend_info
+            .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
+            .expect("should compute control block");
+
+        let mint_addr = Address::p2tr_tweaked(taproot_spend_info.output_key(), network);
+
+        let total_postage = match self.mode {
+            Mode::SameSat => self.postage,
+            Mode::SharedOutput | Mode::SeparateOutputs => (0..self.inscriptions.len())
+                .map(|index| self.postage_of(index))
+                .sum(),
+        };
+
+        let mut reveal_inputs = vec![OutPoint::null()];
+        let mut reveal_outputs = self
+            .destinations
+            .iter()
+            .enumerate()
+            .map(|(index, destination)| TxOut {
+                script_pubkey: destination.script_pubkey(),
+                value: match self.mode {
+                    Mode::SeparateOutputs => self.postage_of(index),
+                    Mode::SharedOutput | Mode::SameSat => total_postage,
+                },
+            })
+            .collect::<Vec<TxOut>>();
+
+        if let Some(ParentInfo {
+            location,
+            id: _,
+            destination,
+            tx_out,
+        }) = self.parent_info.clone()
+        {
+            reveal_inputs.insert(0, location.outpoint);
+            reveal_outputs.insert(
+                0,
+                TxOut {
+                    script_pubkey: destination.script_pubkey(),
+                    value: tx_out.value,
+                },
+            );
+        }
+
+        let mut etching_postage = Amount::ZERO;
+        if let Some(EtchingInfo {
+            destination,
+            etching,
+        }) = &self.etching
+        {
+            let premine = etching.premine.unwrap_or_default() > 0;
+            if premine {
+                reveal_outputs.push(TxOut {
+                    script_pubkey: destination.script_pubkey(),
+                    value: self.postage,
+                });
+                etching_postage = self.postage;
+            }
+
+            let runestone = ordinals::Runestone {
+                edicts: Vec::new(),
+                etching: Some(*etching),
+                mint: None,
+                pointer: premine.then(|| u32::try_from(reveal_outputs.len() - 1).unwrap()),
+            }
+            .encipher();
+
+            if !self.no_limit && runestone.len() > MAX_RUNESTONE_SCRIPT_SIZE {
+                bail!(
+                    "runestone greater than maximum OP_RETURN size: {} > {MAX_RUNESTONE_SCRIPT_SIZE}",
+                    runestone.len()
+                );
+            }
+
+            reveal_outputs.push(TxOut {
+                script_pubkey: runestone,
+                value: Amount::ZERO,
+            });
+        }
+
+        let commit_input = if self.parent_info.is_some() { 1 } else { 0 };
+
+        let (_, reveal_fee) = Self::build_reveal_transaction(
+            &control_block,
+            self.reveal_fee_rate,
+            reveal_inputs.clone(),
+            commit_input,
+            reveal_outputs.clone(),
+            &reveal_script,
+            self.parent_satisfaction_weight()?,
+        );
+
+        Ok(RevealTemplate {
+            reveal_script,
+            taproot_spend_info,
+            control_block,
+            mint_addr,
+            commit_input,
+            reveal_inputs,
+            reveal_outputs,
+            total_postage,
+            etching_postage,
+            reveal_fee,
+        })
+    }
+
+    /// Sizes and fees of the commit and reveal, the commit spending a single `pay_script` input
+    /// with change back to `pay_script`.
+    pub(crate) fn estimate(
+        &self,
+        pay_script: ScriptBuf,
+        network: Network,
+    ) -> Result<InscriptionCost> {
+        let secp256k1 = Secp256k1::new();
+        let key_pair = UntweakedKeypair::new(&secp256k1, &mut thread_rng());
+        let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
+
+        let RevealTemplate {
+            reveal_script,
+            control_block,
+            mint_addr,
+            commit_input,
+            reveal_inputs,
+            reveal_outputs,
+            total_postage,
+            etching_postage,
+            reveal_fee,
+            ..
+        } = self.reveal_template(&secp256k1, public_key, network)?;
+
+        let parent_weight = self.parent_satisfaction_weight()?;
+        let (reveal_tx, _fee) = Self::build_reveal_transaction(
+            &control_block,
+            self.reveal_fee_rate,
+            reveal_inputs,
+            commit_input,
+            reveal_outputs,
+            &reveal_script,
+            parent_weight,
+        );
+        let reveal_weight = Self::reveal_weight(
+            &reveal_tx,
+            &control_block,
+            commit_input,
+            &reveal_script,
+            parent_weight,
+        );
+
+        let mut commit_tx = DummyTransaction::new();
+        commit_tx.append_input(pay_script.clone(), None, None);
+        commit_tx.append_output(mint_addr.script_pubkey());
+        commit_tx.append_output(pay_script);
+        let commit_fee = self.commit_fee_rate * commit_tx.weight();
+
+        Ok(InscriptionCost {
+            commit_vsize: commit_tx.weight().to_vbytes_ceil(),
+            reveal_vsize: reveal_weight.to_vbytes_ceil(),
+            commit_fee: commit_fee.to_sat(),
+            reveal_fee: reveal_fee.to_sat(),
+            postage: (total_postage + etching_postage).to_sat(),
+            total_fee: (commit_fee + reveal_fee).to_sat(),
+        })
+    }
+
+    /// Commit paying `amount` to `mint_addr`, selected by the wallet from outputs not in
+    /// `unspendable`.
+    fn wallet_commit(
+        &self,
+        wallet: &Wallet,
+        unspendable: Vec<OutPoint>,
+        mint_addr: &Address,
+        amount: Amount,
+    ) -> Result<Psbt> {
+        let mut wallet = wallet.get_wallet();
+        let mut tx_builder = wallet.build_tx();
+
+        // 指定 sat 时其 utxo 为第一个输入, 铭刻地址为第一个输出
+        tx_builder
+            .ordering(bdk_wallet::TxOrdering::Untouched)
+            .add_recipient(mint_addr.script_pubkey(), amount)
+            .fee_rate(self.commit_fee_rate)
+            .unspendable(unspendable);
+
+        if let Some(utxo) = &self.satpoint_utxo {
+            tx_builder.add_utxo(utxo.outpoint.clone().into())?;
         }
+
+        Ok(tx_builder.finish()?)
     }
 
     pub(crate) async fn create_batch_inscription_transactions(
         &self,
-        pay_address: &Address,
-        utxos: Vec<LocalOutput>,
+        funding: CommitFunding,
         // client: impl RemoteClient,
         network: Network,
     ) -> Result<(Psbt, Transaction, String, u64)> {
@@ -574,83 +1667,60 @@ impl Batch {
         let key_pair = UntweakedKeypair::new(&secp256k1, &mut thread_rng());
         let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
 
-        let reveal_script = Inscription::append_batch_reveal_script(
-            &self.inscriptions,
-            ScriptBuf::builder()
-                .push_slice(public_key.serialize())
-                .push_opcode(opcodes::all::OP_CHECKSIG),
-        );
-
-        let taproot_spend_info = TaprootBuilder::new()
-            .add_leaf(0, reveal_script.clone())
-            .expect("adding leaf should work")
-            .finalize(&secp256k1, public_key)
-            .expect("finalizing taproot builder should work");
-
-        let control_block = taproot_spend_info
-            .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
-            .expect("should compute control block");
-
-        let mint_addr = Address::p2tr_tweaked(taproot_spend_info.output_key(), network);
+        let RevealTemplate {
+            reveal_script,
+            taproot_spend_info,
+            control_block,
+            mint_addr,
+            commit_input,
+            mut reveal_inputs,
+            reveal_outputs,
+            total_postage,
+            etching_postage,
+            reveal_fee,
+        } = self.reveal_template(&secp256k1, public_key, network)?;
 
-        let total_postage = match self.mode {
-            Mode::SameSat => self.postage,
-            Mode::SharedOutput | Mode::SeparateOutputs => {
-                self.postage * u64::try_from(self.inscriptions.len()).unwrap()
+        // 父铭文和被铭刻的 utxo 不能用于支付
+        let reserved = self
+            .parent_info
+            .iter()
+            .map(|parent_info| parent_info.location.outpoint)
+            .chain(
+                self.satpoint_utxo
+                    .iter()
+                    .map(|utxo| OutPoint::from(utxo.outpoint.clone())),
+            )
+            .collect::<Vec<_>>();
+
+        let commit_amount = reveal_fee + total_postage + etching_postage; // reveal 费用 total_postage会转回去
+
+        let psbt_tx = match funding {
+            CommitFunding::Utxos { pay_address, utxos } => {
+                let utxos = utxos
+                    .into_iter()
+                    .filter(|utxo| !reserved.contains(&OutPoint::from(utxo.outpoint.clone())))
+                    .collect();
+
+                let mut commit_builder = CommitPsbtBuilder::new(
+                    pay_address,
+                    utxos,
+                    mint_addr.clone(),
+                    self.commit_fee_rate, // 提交费率
+                    commit_amount,
+                );
+                commit_builder.satpoint_utxo = self.satpoint_utxo.clone();
+                commit_builder.descriptor = self.descriptor.clone();
+                commit_builder.build_transaction().await?
+            }
+            CommitFunding::Wallet {
+                wallet,
+                mut unspendable,
+            } => {
+                unspendable.extend(reserved);
+                self.wallet_commit(&wallet, unspendable, &mint_addr, commit_amount)?
             }
         };
 
-        let mut reveal_inputs = vec![OutPoint::null()];
-        let mut reveal_outputs = self
-            .destinations
-            .iter()
-            .map(|destination| TxOut {
-                script_pubkey: destination.script_pubkey(),
-                value: match self.mode {
-                    Mode::SeparateOutputs => self.postage,
-                    Mode::SharedOutput | Mode::SameSat => total_postage,
-                },
-            })
-            .collect::<Vec<TxOut>>();
-
-        if let Some(ParentInfo {
-            location,
-            id: _,
-            destination,
-            tx_out,
-        }) = self.parent_info.clone()
-        {
-            reveal_inputs.insert(0, location.outpoint);
-            reveal_outputs.insert(
-                0,
-                TxOut {
-                    script_pubkey: destination.script_pubkey(),
-                    value: tx_out.value,
-                },
-            );
-        }
-
-        let commit_input = if self.parent_info.is_some() { 1 } else { 0 };
-
-        let (_, reveal_fee) = Self::build_reveal_transaction(
-            &control_block,
-            self.reveal_fee_rate,
-            reveal_inputs.clone(),
-            commit_input,
-            reveal_outputs.clone(),
-            &reveal_script,
-        );
-
-        let psbt_tx = CommitPsbtBuilder::new(
-            pay_address.clone(),
-            utxos,
-            mint_addr.clone(),
-            self.commit_fee_rate,       // 提交费率
-            reveal_fee + total_postage, // reveal 费用 total_postage会转回去
-        )
-        .build_transaction()
-        .await?;
-
         let (vout, _commit_output) = psbt_tx
             .unsigned_tx
             .output
@@ -671,8 +1741,15 @@ impl Batch {
             commit_input,
             reveal_outputs.clone(),
             &reveal_script,
+            self.parent_satisfaction_weight()?,
         );
 
+        if self.etching.is_some() {
+            // reveal 需要等待 commit 确认足够区块
+            reveal_tx.input[commit_input].sequence =
+                Sequence::from_height(ordinals::Runestone::COMMIT_CONFIRMATIONS - 1);
+        }
+
         if reveal_tx.output[commit_input].value
             < reveal_tx.output[commit_input]
                 .script_pubkey
@@ -749,17 +1826,27 @@ impl Batch {
 
         // 构建 psbt
 
-        // let total_fees = Self::calculate_fee(&psbt_tx.unsigned_tx, &utxos)
-        //     + Self::calculate_fee(&reveal_tx, &utxos);
+        let commit_fee = psbt_tx
+            .fee()
+            .context("commit transaction is missing previous outputs")?;
+        let total_fees = (commit_fee + reveal_fee).to_sat();
 
         Ok((
             psbt_tx,
             reveal_tx,
             PrivateKey::new(key_pair.secret_key(), network).to_wif(),
-            0,
+            total_fees,
         ))
     }
 
+    /// Largest satisfaction of the parent input when its descriptor is known.
+    fn parent_satisfaction_weight(&self) -> Result<Option<bitcoin::Weight>> {
+        match (&self.parent_info, &self.descriptor) {
+            (Some(_), Some(descriptor)) => Ok(Some(descriptor.max_weight_to_satisfy()?)),
+            _ => Ok(None),
+        }
+    }
+
     fn build_reveal_transaction(
         control_block: &ControlBlock,
         fee_rate: FeeRate,
@@ -767,6 +1854,7 @@ impl Batch {
         commit_input_index: usize,
         outputs: Vec<TxOut>,
         script: &Script,
+        parent_weight: Option<bitcoin::Weight>,
     ) -> (Transaction, Amount) {
         let reveal_tx = Transaction {
             input: inputs
@@ -783,28 +1871,48 @@ impl Batch {
             version: Version::TWO,
         };
 
-        let fee = {
-            let mut reveal_tx = reveal_tx.clone();
+        let fee = fee_rate
+            * Self::reveal_weight(
+                &reveal_tx,
+                control_block,
+                commit_input_index,
+                script,
+                parent_weight,
+            );
 
-            for (current_index, txin) in reveal_tx.input.iter_mut().enumerate() {
-                // add dummy inscription witness for reveal input/commit output
-                if current_index == commit_input_index {
-                    txin.witness.push(
-                        Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-                            .unwrap()
-                            .to_vec(),
-                    );
-                    txin.witness.push(script);
-                    txin.witness.push(control_block.serialize());
-                } else {
-                    txin.witness = Witness::from_slice(&[&[0; SCHNORR_SIGNATURE_SIZE]]);
-                }
+        (reveal_tx, fee)
+    }
+
+    /// Weight of `reveal_tx` once signed, the parent input taking `parent_weight` to satisfy
+    /// when given and a single schnorr signature otherwise.
+    fn reveal_weight(
+        reveal_tx: &Transaction,
+        control_block: &ControlBlock,
+        commit_input_index: usize,
+        script: &Script,
+        parent_weight: Option<bitcoin::Weight>,
+    ) -> bitcoin::Weight {
+        let mut reveal_tx = reveal_tx.clone();
+        let mut satisfaction = bitcoin::Weight::ZERO;
+
+        for (current_index, txin) in reveal_tx.input.iter_mut().enumerate() {
+            // add dummy inscription witness for reveal input/commit output
+            if current_index == commit_input_index {
+                txin.witness.push(
+                    Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
+                        .unwrap()
+                        .to_vec(),
+                );
+                txin.witness.push(script);
+                txin.witness.push(control_block.serialize());
+            } else if let Some(weight) = parent_weight {
+                satisfaction += weight;
+            } else {
+                txin.witness = Witness::from_slice(&[&[0; SCHNORR_SIGNATURE_SIZE]]);
             }
-            fee_rate * reveal_tx.weight()
-            // fee_rate.fee(reveal_tx.vsize())
-        };
+        }
 
-        (reveal_tx, fee)
+        reveal_tx.weight() + satisfaction
     }
 
     fn calculate_fee(tx: &Transaction, utxos: &BTreeMap<OutPoint, Amount>) -> u64 {
@@ -887,6 +1995,9 @@ pub struct CommitPsbtBuilder {
 
     pub(crate) inputs: Vec<OutPoint>,           // utxo
     pub(crate) outputs: Vec<(Address, Amount)>, // 输出
+
+    pub(crate) satpoint_utxo: Option<LocalOutput>, // 第一个输入, 其第一个 sat 进入铭刻地址
+    pub(crate) descriptor: Option<ExtendedDescriptor>, // 输入的描述符, 用于估算签名大小
 }
 
 type BuildResult<T> = std::result::Result<T, Error>;
@@ -912,6 +2023,8 @@ impl CommitPsbtBuilder {
             reveal_fee,
             inputs: Vec::new(),
             outputs: Vec::new(),
+            satpoint_utxo: None,
+            descriptor: None,
         }
     }
 
@@ -928,6 +2041,9 @@ impl CommitPsbtBuilder {
 
         let mut transfer_amount = 0;
 
+        // 指定 sat 时铭刻地址必须是第一个输出, 找零放在最后
+        let change_index = if self.satpoint_utxo.is_some() { 1 } else { 0 };
+
         // 初始化找零
         transaction.output.push(TxOut {
             value: Amount::ZERO,
@@ -935,7 +2051,7 @@ impl CommitPsbtBuilder {
         });
 
         // 其他费用
-        let  to_addrs = vec![(self.mint_address, self.reveal_fee)];
+        let to_addrs = vec![(self.mint_address, self.reveal_fee)];
 
         for (addr, amount) in to_addrs {
             transaction.output.push(TxOut {
@@ -945,12 +2061,23 @@ impl CommitPsbtBuilder {
             transfer_amount += amount.to_sat();
         }
 
+        if change_index != 0 {
+            let change = transaction.output.remove(0);
+            transaction.output.push(change);
+        }
+
+        // 按签名后的大小计算手续费
+        let mut dummy_tx = DummyTransaction::new();
+        for output in &transaction.output {
+            dummy_tx.append_output(output.script_pubkey.clone());
+        }
+
         let mut psbt_inputs = vec![];
 
         let mut amount = 0;
 
         let mut ok = false;
-        for utxo in self.cardinal_utxos {
+        for utxo in self.satpoint_utxo.into_iter().chain(self.cardinal_utxos) {
             amount += utxo.txout.value.to_sat();
 
             // let utxo_tx = client.get_transaction(&utxo.outpoint.txid.to_string()).await?;
@@ -995,16 +2122,20 @@ impl CommitPsbtBuilder {
 
             let witness_utxo = TxOut {
                 value: utxo.txout.value.0,
-                script_pubkey: from_script_pub_key.clone(),
+                script_pubkey: utxo.txout.script_pubkey.0.clone(),
             };
             psbt_input.witness_utxo = Some(witness_utxo);
 
             psbt_inputs.push(psbt_input);
 
-            let network_fee = (self.fee_rate * transaction.weight()).to_sat();
+            match &self.descriptor {
+                Some(descriptor) => dummy_tx.append_descriptor_input(descriptor)?,
+                None => dummy_tx.append_input(utxo.txout.script_pubkey.0.clone(), None, None),
+            }
+            let network_fee = (self.fee_rate * dummy_tx.weight()).to_sat();
 
             if let Some(unfilled) = amount.checked_sub(network_fee + transfer_amount) {
-                transaction.output[0].value = Amount::from_sat(unfilled); // 找零
+                transaction.output[change_index].value = Amount::from_sat(unfilled); // 找零
 
                 ok = true;
                 break;
@@ -1013,15 +2144,12 @@ impl CommitPsbtBuilder {
         if !ok {
             return Err(anyhow::anyhow!("no utxo or utxo not enough"));
         }
-        if transaction.output.first().unwrap().value
-            < transaction
-                .output
-                .first()
-                .unwrap()
+        if transaction.output[change_index].value
+            < transaction.output[change_index]
                 .script_pubkey
                 .minimal_non_dust()
         {
-            transaction.output.remove(0);
+            transaction.output.remove(change_index);
         }
 
         let o_len = transaction.output.len();
@@ -1151,3 +2279,768 @@ impl CommitPsbtBuilder {
 //         println!("{}", tx.vsize());
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ordinal::rune::RuneAmount;
+    use crate::testing::{
+        block_on, funded_wallet, local_output, multisig_descriptor, TAPROOT_ADDRESS,
+    };
+    use bdk_wallet::KeychainKind;
+
+    fn etching(rune: &str) -> crate::ordinal::rune::Etching {
+        crate::ordinal::rune::Etching {
+            divisibility: Some(2),
+            premine: Some(Arc::new(RuneAmount(1_000))),
+            rune: Some(rune.into()),
+            spaced_rune: None,
+            spacers: None,
+            symbol: Some("R".into()),
+            terms: None,
+            turbo: false,
+        }
+    }
+
+    #[test]
+    fn etch_rune_commits_to_rune() {
+        let output = block_on(etch_rune(
+            Network::Bitcoin,
+            vec![local_output(0, 100_000)],
+            etching("UNCOMMON•GOODS"),
+            Some(NamedFile {
+                name: "rune.txt".into(),
+                data: b"rune".to_vec(),
+                content_type: None,
+            }),
+            TAPROOT_ADDRESS,
+            TAPROOT_ADDRESS,
+            2,
+            None,
+            Some(840_000),
+            None,
+        ))
+        .unwrap();
+
+        assert_eq!(output.rune, "UNCOMMON•GOODS");
+        assert_eq!(output.commit_confirmations, 6);
+
+        let reveal = &output.reveal_tx.0;
+        assert_eq!(
+            output.inscription_id,
+            Some(format!("{}i0", reveal.compute_txid()))
+        );
+        assert_eq!(reveal.input[0].sequence, Sequence::from_height(5));
+
+        // inscription, premine, runestone
+        assert_eq!(reveal.output.len(), 3);
+        let Some(ordinals::Artifact::Runestone(runestone)) = ordinals::Runestone::decipher(reveal)
+        else {
+            panic!("expected runestone");
+        };
+        assert_eq!(runestone.pointer, Some(1));
+        let rune = runestone.etching.unwrap().rune.unwrap();
+        assert_eq!(rune.to_string(), "UNCOMMONGOODS");
+
+        let tapscript = reveal.input[0]
+            .witness
+            .taproot_leaf_script()
+            .unwrap()
+            .script;
+        assert!(tapscript.instructions().any(|instruction| {
+            instruction
+                .unwrap()
+                .push_bytes()
+                .map(|push| push.as_bytes())
+                == Some(rune.commitment().as_slice())
+        }));
+
+        assert_eq!(envelope::ParsedEnvelope::from_transaction(reveal).len(), 1);
+
+        let commit = output.commit_psbt_tx.0.lock().unwrap();
+        let reveal_fee = commit.unsigned_tx.output[reveal.input[0].previous_output.vout as usize]
+            .value
+            - reveal.output.iter().map(|output| output.value).sum();
+        assert_eq!(
+            output.total_fees,
+            (commit.fee().unwrap() + reveal_fee).to_sat()
+        );
+    }
+
+    #[test]
+    fn etch_rune_rejects_locked_and_reserved_runes() {
+        let etch = |rune: &str| {
+            block_on(etch_rune(
+                Network::Bitcoin,
+                vec![local_output(0, 100_000)],
+                etching(rune),
+                None,
+                TAPROOT_ADDRESS,
+                TAPROOT_ADDRESS,
+                2,
+                None,
+                Some(840_000),
+                None,
+            ))
+        };
+
+        assert!(etch("A").is_err());
+        assert!(etch("AAAAAAAAAAAAAAAAAAAAAAAAAAA").is_err());
+        assert!(etch("UNCOMMONGOODS").is_ok());
+    }
+
+    #[test]
+    fn mint_with_parent_returns_parent() {
+        let parent_utxo = local_output(7, 10_000);
+        let parent_id = format!("{}i0", parent_utxo.outpoint.txid.0);
+        let output = block_on(mint(
+            Network::Bitcoin,
+            vec![parent_utxo.clone(), local_output(0, 100_000)],
+            NamedFile {
+                name: "child.txt".into(),
+                data: b"child".to_vec(),
+                content_type: None,
+            },
+            TAPROOT_ADDRESS,
+            TAPROOT_ADDRESS,
+            2,
+            None,
+            Some(InscriptionParent {
+                inscription_id: parent_id.clone(),
+                utxo: parent_utxo.clone(),
+            }),
+            None,
+            None,
+        ))
+        .unwrap();
+
+        assert_eq!(output.parent, Some(parent_id.clone()));
+
+        // the parent is not spent by the commit
+        let commit = &output.commit_psbt_tx.0.lock().unwrap().unsigned_tx;
+        assert!(commit
+            .input
+            .iter()
+            .all(|txin| txin.previous_output.vout != 7));
+
+        let reveal = &output.reveal_tx.0;
+        assert_eq!(reveal.input[0].previous_output.vout, 7);
+        assert_eq!(reveal.output[0].value.to_sat(), 10_000);
+        assert_eq!(
+            reveal.output[0].script_pubkey,
+            parent_utxo.txout.script_pubkey.0
+        );
+
+        let psbt = output.reveal_psbt.unwrap().0.lock().unwrap().clone();
+        assert_eq!(psbt.unsigned_tx.compute_txid(), reveal.compute_txid());
+        assert!(psbt.inputs[0].final_script_witness.is_none());
+        assert_eq!(
+            psbt.inputs[0].witness_utxo.as_ref().unwrap().value.to_sat(),
+            10_000
+        );
+        assert!(psbt.inputs[1].final_script_witness.is_some());
+        assert!(psbt.inputs[1].witness_utxo.is_some());
+
+        let inscriptions = envelope::ParsedEnvelope::from_transaction(reveal);
+        assert_eq!(inscriptions.len(), 1);
+        assert_eq!(inscriptions[0].input, 1);
+        assert_eq!(
+            inscriptions[0]
+                .payload
+                .parents()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![parent_id]
+        );
+    }
+
+    #[test]
+    fn mint_fees_cover_the_descriptor_satisfaction() {
+        let parent_utxo = local_output(7, 10_000);
+        let mint_with_descriptor = |descriptor| {
+            block_on(mint(
+                Network::Bitcoin,
+                vec![parent_utxo.clone(), local_output(0, 100_000)],
+                NamedFile {
+                    name: "child.txt".into(),
+                    data: b"child".to_vec(),
+                    content_type: None,
+                },
+                TAPROOT_ADDRESS,
+                TAPROOT_ADDRESS,
+                2,
+                None,
+                Some(InscriptionParent {
+                    inscription_id: format!("{}i0", parent_utxo.outpoint.txid.0),
+                    utxo: parent_utxo.clone(),
+                }),
+                None,
+                descriptor,
+            ))
+            .unwrap()
+        };
+        let commit_value = |output: &Output| {
+            let reveal = &output.reveal_tx.0;
+            let commit = output.commit_psbt_tx.0.lock().unwrap();
+            commit.unsigned_tx.output[reveal.input[1].previous_output.vout as usize].value
+        };
+
+        let single_key = mint_with_descriptor(None);
+        let multisig = mint_with_descriptor(Some(multisig_descriptor()));
+
+        // the commit funds the reveal, whose parent input now needs two signatures and a script
+        assert!(commit_value(&multisig) > commit_value(&single_key));
+        let satisfaction = multisig_descriptor()
+            .extended_descriptor
+            .max_weight_to_satisfy()
+            .unwrap();
+        let schnorr = bitcoin::Weight::from_wu(1 + 1 + SCHNORR_SIGNATURE_SIZE as u64);
+        assert_eq!(
+            commit_value(&multisig) - commit_value(&single_key),
+            FeeRate::from_sat_per_vb_unchecked(2) * (satisfaction - schnorr)
+        );
+
+        // so does every commit input
+        let commit_fee = |output: &Output| {
+            let commit = output.commit_psbt_tx.0.lock().unwrap();
+            commit.fee().unwrap()
+        };
+        assert!(commit_fee(&multisig) > commit_fee(&single_key));
+    }
+
+    #[test]
+    fn mint_rejects_pointer_outside_the_inscription_outputs() {
+        let parent_utxo = local_output(7, 10_000);
+        let mint_with_pointer = |pointer| {
+            block_on(mint(
+                Network::Bitcoin,
+                vec![parent_utxo.clone(), local_output(0, 100_000)],
+                NamedFile {
+                    name: "child.txt".into(),
+                    data: b"child".to_vec(),
+                    content_type: None,
+                },
+                TAPROOT_ADDRESS,
+                TAPROOT_ADDRESS,
+                2,
+                None,
+                Some(InscriptionParent {
+                    inscription_id: format!("{}i0", parent_utxo.outpoint.txid.0),
+                    utxo: parent_utxo.clone(),
+                }),
+                Some(InscriptionOptions {
+                    pointer: Some(pointer),
+                    ..Default::default()
+                }),
+                None,
+            ))
+        };
+
+        let output = mint_with_pointer(10_005).unwrap();
+        assert_eq!(
+            output.inscriptions[0].location,
+            format!("{}:1:5", output.reveal_tx.0.compute_txid())
+        );
+        assert!(mint_with_pointer(0).is_err());
+        assert!(mint_with_pointer(9_999).is_err());
+        assert!(mint_with_pointer(10_000 + 330).is_err());
+    }
+
+    const P2WPKH_ADDRESS: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+    fn entry(name: &str, destination: Option<&str>, postage: Option<u64>) -> BatchEntry {
+        BatchEntry {
+            file: NamedFile {
+                name: name.into(),
+                data: name.as_bytes().to_vec(),
+                content_type: None,
+            },
+            destination: destination.map(str::to_string),
+            metadata: None,
+            postage,
+        }
+    }
+
+    fn mint_batch_with(entries: Vec<BatchEntry>, mode: BatchMode) -> Result<Output, MintError> {
+        block_on(mint_batch(
+            Network::Bitcoin,
+            vec![local_output(0, 100_000)],
+            entries,
+            mode,
+            TAPROOT_ADDRESS,
+            TAPROOT_ADDRESS,
+            2,
+            None,
+            None,
+            None,
+        ))
+    }
+
+    #[test]
+    fn batch_and_etch_commit_fees_cover_the_descriptor() {
+        let commit_fee = |psbt: &Arc<crate::Psbt>| psbt.0.lock().unwrap().fee().unwrap();
+        let batch = |descriptor| {
+            block_on(mint_batch(
+                Network::Bitcoin,
+                vec![local_output(0, 100_000)],
+                vec![entry("a.txt", None, None)],
+                BatchMode::SeparateOutputs,
+                TAPROOT_ADDRESS,
+                TAPROOT_ADDRESS,
+                2,
+                None,
+                None,
+                descriptor,
+            ))
+            .unwrap()
+            .commit_psbt_tx
+        };
+        assert!(commit_fee(&batch(Some(multisig_descriptor()))) > commit_fee(&batch(None)));
+
+        let etch = |descriptor| {
+            block_on(etch_rune(
+                Network::Bitcoin,
+                vec![local_output(0, 100_000)],
+                etching("UNCOMMONGOODS"),
+                None,
+                TAPROOT_ADDRESS,
+                TAPROOT_ADDRESS,
+                2,
+                None,
+                None,
+                descriptor,
+            ))
+            .unwrap()
+            .commit_psbt_tx
+        };
+        assert!(commit_fee(&etch(Some(multisig_descriptor()))) > commit_fee(&etch(None)));
+    }
+
+    #[test]
+    fn mint_batch_separate_outputs() {
+        let mut first = entry("a.txt", None, Some(1_000));
+        first.metadata = Some(InscriptionMetadata::Json {
+            json: r#"{"name":"a"}"#.into(),
+        });
+        let output = mint_batch_with(
+            vec![first, entry("b.txt", Some(P2WPKH_ADDRESS), None)],
+            BatchMode::SeparateOutputs,
+        )
+        .unwrap();
+
+        let reveal = &output.reveal_tx.0;
+        let txid = reveal.compute_txid();
+        assert_eq!(reveal.output.len(), 2);
+        assert_eq!(reveal.output[0].value.to_sat(), 1_000);
+        assert_eq!(reveal.output[1].value.to_sat(), 294);
+        assert_eq!(
+            output.inscriptions,
+            vec![
+                InscriptionInfo {
+                    id: format!("{txid}i0"),
+                    location: format!("{txid}:0:0"),
+                },
+                InscriptionInfo {
+                    id: format!("{txid}i1"),
+                    location: format!("{txid}:1:0"),
+                },
+            ]
+        );
+
+        let records = parse_inscriptions(&crate::Transaction(reveal.clone()));
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].metadata.as_deref(), Some(r#"{"name":"a"}"#));
+        assert_eq!(records[1].pointer, Some(1_000));
+    }
+
+    #[test]
+    fn mint_batch_shared_output_and_same_sat() {
+        let output = mint_batch_with(
+            vec![entry("a.txt", None, Some(500)), entry("b.txt", None, None)],
+            BatchMode::SharedOutput,
+        )
+        .unwrap();
+        let txid = output.reveal_tx.0.compute_txid();
+        assert_eq!(output.reveal_tx.0.output.len(), 1);
+        assert_eq!(output.reveal_tx.0.output[0].value.to_sat(), 830);
+        assert_eq!(output.inscriptions[1].location, format!("{txid}:0:500"));
+
+        let output = mint_batch_with(
+            vec![entry("a.txt", None, None), entry("b.txt", None, None)],
+            BatchMode::SameSat,
+        )
+        .unwrap();
+        let txid = output.reveal_tx.0.compute_txid();
+        assert_eq!(output.reveal_tx.0.output.len(), 1);
+        assert_eq!(output.reveal_tx.0.output[0].value.to_sat(), 330);
+        assert!(output
+            .inscriptions
+            .iter()
+            .all(|info| info.location == format!("{txid}:0:0")));
+
+        assert!(
+            mint_batch_with(vec![entry("a.txt", None, Some(500))], BatchMode::SameSat).is_err()
+        );
+        assert!(mint_batch_with(
+            vec![entry("a.txt", Some(P2WPKH_ADDRESS), None)],
+            BatchMode::SharedOutput
+        )
+        .is_err());
+        assert!(mint_batch_with(vec![], BatchMode::SeparateOutputs).is_err());
+    }
+
+    fn mint_with_options(options: InscriptionOptions) -> Result<Output, MintError> {
+        block_on(mint(
+            Network::Bitcoin,
+            vec![local_output(0, 100_000)],
+            NamedFile {
+                name: "a.txt".into(),
+                data: "a".repeat(1_000).into_bytes(),
+                content_type: None,
+            },
+            TAPROOT_ADDRESS,
+            TAPROOT_ADDRESS,
+            2,
+            None,
+            None,
+            Some(options),
+            None,
+        ))
+    }
+
+    #[test]
+    fn mint_with_metadata_metaprotocol_pointer_and_compression() {
+        let output = mint_with_options(InscriptionOptions {
+            metadata: Some(InscriptionMetadata::Cbor {
+                cbor: vec![0xa1, 0x61, 0x61, 0x01],
+            }),
+            metaprotocol: Some("brc-20".into()),
+            pointer: Some(10),
+            compress: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let records = parse_inscriptions(&output.reveal_tx);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].metadata.as_deref(), Some(r#"{"a":1}"#));
+        assert_eq!(records[0].metaprotocol.as_deref(), Some("brc-20"));
+        assert_eq!(records[0].pointer, Some(10));
+        assert_eq!(
+            output.inscriptions[0].location,
+            format!("{}:0:10", output.reveal_tx.0.compute_txid())
+        );
+        assert_eq!(records[0].content_encoding.as_deref(), Some("br"));
+        assert!(records[0].body.as_ref().unwrap().len() < 1_000);
+
+        let output = mint_with_options(InscriptionOptions {
+            metadata: Some(InscriptionMetadata::Json {
+                json: r#"{"a":1}"#.into(),
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+        let records = parse_inscriptions(&output.reveal_tx);
+        assert_eq!(records[0].metadata.as_deref(), Some(r#"{"a":1}"#));
+        assert_eq!(records[0].content_encoding, None);
+
+        assert!(mint_with_options(InscriptionOptions {
+            metadata: Some(InscriptionMetadata::Cbor { cbor: vec![0xff] }),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(mint_with_options(InscriptionOptions {
+            metadata: Some(InscriptionMetadata::Json { json: "{".into() }),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn mint_delegate_without_body() {
+        let delegate = format!("{}i3", "0".repeat(64));
+        let output = block_on(mint(
+            Network::Bitcoin,
+            vec![local_output(0, 100_000)],
+            NamedFile {
+                name: String::new(),
+                data: Vec::new(),
+                content_type: None,
+            },
+            TAPROOT_ADDRESS,
+            TAPROOT_ADDRESS,
+            2,
+            None,
+            None,
+            Some(InscriptionOptions {
+                delegate: Some(delegate.clone()),
+                ..Default::default()
+            }),
+            None,
+        ))
+        .unwrap();
+
+        let records = parse_inscriptions(&output.reveal_tx);
+        assert_eq!(records[0].delegate, Some(delegate));
+        assert_eq!(records[0].body, None);
+        assert_eq!(records[0].content_type, None);
+    }
+
+    fn reinscribe(inscription_ids: Vec<String>, reinscribe: bool) -> Result<Output, MintError> {
+        block_on(mint(
+            Network::Bitcoin,
+            vec![local_output(5, 10_000), local_output(0, 100_000)],
+            NamedFile {
+                name: "a.txt".into(),
+                data: b"a".to_vec(),
+                content_type: None,
+            },
+            TAPROOT_ADDRESS,
+            TAPROOT_ADDRESS,
+            2,
+            None,
+            None,
+            Some(InscriptionOptions {
+                satpoint: Some(InscriptionSatpoint {
+                    utxo: local_output(5, 10_000),
+                    inscription_ids,
+                }),
+                reinscribe,
+                ..Default::default()
+            }),
+            None,
+        ))
+    }
+
+    #[test]
+    fn mint_reinscribe_guard_rails() {
+        let inscribed = vec![format!("{}i0", "0".repeat(64))];
+
+        assert!(reinscribe(inscribed.clone(), false).is_err());
+        assert!(reinscribe(vec![], true).is_err());
+
+        let output = reinscribe(inscribed, true).unwrap();
+        let commit = output.commit_psbt_tx.0.lock().unwrap().unsigned_tx.clone();
+        // the inscribed sat is the first sat of the commit output spent by the reveal
+        assert_eq!(commit.input[0].previous_output.vout, 5);
+        assert_eq!(output.reveal_tx.0.input[0].previous_output.vout, 0);
+        assert_eq!(
+            output.reveal_tx.0.input[0].previous_output.txid,
+            commit.compute_txid()
+        );
+        assert_eq!(commit.output.len(), 2);
+        assert_eq!(
+            commit.output[1].script_pubkey,
+            crate::testing::address().script_pubkey()
+        );
+
+        // a fresh sat needs no flag
+        assert!(reinscribe(vec![], false).is_ok());
+    }
+
+    #[test]
+    fn estimate_inscription_cost_matches_mint() {
+        let file = || NamedFile {
+            name: "a.txt".into(),
+            data: b"a".to_vec(),
+            content_type: None,
+        };
+
+        let cost = estimate_inscription_cost(
+            Network::Bitcoin,
+            vec![file()],
+            AddressType::P2tr,
+            3,
+            2,
+            None,
+            BatchMode::SeparateOutputs,
+        )
+        .unwrap();
+
+        let output = block_on(mint(
+            Network::Bitcoin,
+            vec![local_output(0, 100_000)],
+            file(),
+            TAPROOT_ADDRESS,
+            TAPROOT_ADDRESS,
+            2,
+            None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+
+        let commit = output.commit_psbt_tx.0.lock().unwrap().unsigned_tx.clone();
+        let commit_fee = 100_000
+            - commit
+                .output
+                .iter()
+                .map(|output| output.value.to_sat())
+                .sum::<u64>();
+        let reveal = &output.reveal_tx.0;
+        let reveal_fee = commit.output[reveal.input[0].previous_output.vout as usize]
+            .value
+            .to_sat()
+            - reveal.output[0].value.to_sat();
+
+        assert_eq!(cost.reveal_vsize, reveal.vsize() as u64);
+        assert_eq!(cost.reveal_fee, reveal_fee);
+        assert_eq!(cost.postage, 330);
+        // mint pays the commit at the reveal fee rate
+        assert_eq!(cost.commit_fee, commit_fee / 2 * 3);
+        assert_eq!(cost.total_fee, cost.commit_fee + cost.reveal_fee);
+
+        let batch = estimate_inscription_cost(
+            Network::Bitcoin,
+            vec![file(), file()],
+            AddressType::P2tr,
+            2,
+            2,
+            Some(1_000),
+            BatchMode::SharedOutput,
+        )
+        .unwrap();
+        assert_eq!(batch.postage, 2_000);
+        assert!(batch.reveal_vsize > cost.reveal_vsize);
+
+        assert!(estimate_inscription_cost(
+            Network::Bitcoin,
+            Vec::new(),
+            AddressType::P2tr,
+            2,
+            2,
+            None,
+            BatchMode::SharedOutput,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn mint_with_wallet_spends_only_spendable_outputs() {
+        let wallet = funded_wallet(&[100_000, 50_000]);
+        let outpoints = wallet
+            .list_unspent()
+            .into_iter()
+            .map(|utxo| (utxo.txout.value.to_sat(), utxo.outpoint))
+            .collect::<BTreeMap<_, _>>();
+        let destination = wallet.reveal_next_address(KeychainKind::External).address;
+
+        let output = block_on(mint_with_wallet(
+            wallet.clone(),
+            NamedFile {
+                name: "a.txt".into(),
+                data: b"a".to_vec(),
+                content_type: None,
+            },
+            &destination.to_string(),
+            2,
+            vec![outpoints[&100_000].clone()],
+            None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+
+        let commit = output.commit_psbt_tx.0.lock().unwrap().clone();
+        assert_eq!(commit.unsigned_tx.input.len(), 1);
+        assert_eq!(
+            commit.unsigned_tx.input[0].previous_output,
+            OutPoint::from(outpoints[&50_000].clone())
+        );
+        assert!(!commit.inputs[0].tap_key_origins.is_empty());
+
+        let reveal = &output.reveal_tx.0;
+        assert_eq!(
+            reveal.input[0].previous_output.txid,
+            commit.unsigned_tx.compute_txid()
+        );
+        let change = commit
+            .unsigned_tx
+            .output
+            .iter()
+            .enumerate()
+            .find(|(vout, _)| *vout != reveal.input[0].previous_output.vout as usize)
+            .map(|(_, output)| output.script_pubkey.clone())
+            .unwrap();
+        assert_eq!(
+            wallet
+                .derivation_of_spk(Arc::new(crate::bitcoin::Script(change)))
+                .unwrap()
+                .keychain,
+            KeychainKind::Internal
+        );
+
+        assert!(block_on(mint_with_wallet(
+            wallet,
+            NamedFile {
+                name: "a.txt".into(),
+                data: b"a".to_vec(),
+                content_type: None,
+            },
+            &destination.to_string(),
+            2,
+            outpoints.into_values().collect(),
+            None,
+            None,
+            None,
+            None,
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn batch_and_etch_with_wallet_use_wallet_funding() {
+        let wallet = funded_wallet(&[100_000, 50_000]);
+        let outpoints = wallet
+            .list_unspent()
+            .into_iter()
+            .map(|utxo| (utxo.txout.value.to_sat(), utxo.outpoint))
+            .collect::<BTreeMap<_, _>>();
+        let destination = wallet
+            .reveal_next_address(KeychainKind::External)
+            .address
+            .to_string();
+        let unspendable = vec![outpoints[&100_000].clone()];
+        let assert_wallet_funded = |commit: &Arc<crate::Psbt>| {
+            let commit = commit.0.lock().unwrap();
+            assert_eq!(commit.unsigned_tx.input.len(), 1);
+            assert_eq!(
+                commit.unsigned_tx.input[0].previous_output,
+                OutPoint::from(outpoints[&50_000].clone())
+            );
+            assert!(!commit.inputs[0].tap_key_origins.is_empty());
+        };
+
+        let output = block_on(mint_batch_with_wallet(
+            wallet.clone(),
+            vec![entry("a", None, None), entry("b", None, None)],
+            BatchMode::SeparateOutputs,
+            &destination,
+            2,
+            unspendable.clone(),
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_wallet_funded(&output.commit_psbt_tx);
+        assert_eq!(output.inscriptions.len(), 2);
+
+        let output = block_on(etch_rune_with_wallet(
+            wallet,
+            etching("UNCOMMONGOODS"),
+            None,
+            &destination,
+            2,
+            unspendable,
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_wallet_funded(&output.commit_psbt_tx);
+        assert_eq!(output.rune, "UNCOMMONGOODS");
+    }
+}
@@ -9,7 +9,7 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::ordinal::inscription::{degree::Degree, deserialize_from_str::DeserializeFromStr, sat::Sat};
 
-#[derive(Debug, PartialEq, PartialOrd, Copy, Clone)]
+#[derive(uniffi::Enum, Debug, PartialEq, PartialOrd, Copy, Clone)]
 pub enum Rarity {
     Common,
     Uncommon,
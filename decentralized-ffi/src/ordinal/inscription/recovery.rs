@@ -0,0 +1,439 @@
+use super::{InscriptionInfo, MintError};
+use bdk_wallet::bitcoin::{
+    absolute::LockTime,
+    consensus::encode::deserialize_hex,
+    key::{constants::SCHNORR_SIGNATURE_SIZE, TapTweak, UntweakedKeypair},
+    secp256k1::{self, Secp256k1},
+    sighash::{Prevouts, SighashCache, TapSighashType},
+    taproot::{LeafVersion, Signature, TapLeafHash, TaprootBuilder},
+    transaction::Version,
+    Address, Amount, FeeRate, Network, OutPoint, PrivateKey, Psbt, ScriptBuf, Sequence,
+    Transaction, TxIn, TxOut, Witness,
+};
+use bdk_wallet::serde_json;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Everything needed to resume an inscription after the commit is signed, or to sweep the commit
+/// output back if the reveal is lost.
+///
+/// Store it, e.g. with [`inscription_session_to_json`], before broadcasting the commit.
+#[derive(uniffi::Record, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InscriptionSession {
+    pub network: Network,
+    /// base64 commit PSBT
+    pub commit_psbt: String,
+    /// hex encoded reveal transaction, the parent input is unsigned when `parent` is set
+    pub reveal_tx: String,
+    /// base64 reveal PSBT with the inscription input finalized, present when `parent` is set
+    #[serde(default)]
+    pub reveal_psbt: Option<String>,
+    /// reveal input spending the parent inscription, which the wallet holding it must sign
+    #[serde(default)]
+    pub parent_input: Option<u32>,
+    /// WIF of the untweaked reveal key
+    pub reveal_private_key: String,
+    /// hex encoded reveal tapscript, the only leaf of the commit output
+    pub reveal_script: String,
+    /// commit output spent by the reveal, formatted as `<txid>:<vout>`
+    pub commit_outpoint: String,
+    pub commit_value: u64,
+    pub parent: Option<String>,
+    pub inscriptions: Vec<InscriptionInfo>,
+}
+
+impl InscriptionSession {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        network: Network,
+        commit: &Psbt,
+        reveal: &Transaction,
+        reveal_psbt: Option<&Psbt>,
+        commit_input: usize,
+        parent_input: Option<u32>,
+        reveal_private_key: String,
+        parent: Option<String>,
+        inscriptions: Vec<InscriptionInfo>,
+    ) -> Self {
+        let commit_outpoint = reveal.input[commit_input].previous_output;
+        let reveal_script = reveal.input[commit_input]
+            .witness
+            .taproot_leaf_script()
+            .map(|leaf| leaf.script.to_hex_string())
+            .unwrap_or_default();
+
+        InscriptionSession {
+            network,
+            commit_psbt: commit.to_string(),
+            reveal_tx: bdk_wallet::bitcoin::consensus::encode::serialize_hex(reveal),
+            reveal_psbt: reveal_psbt.map(ToString::to_string),
+            parent_input,
+            reveal_private_key,
+            reveal_script,
+            commit_outpoint: commit_outpoint.to_string(),
+            commit_value: commit
+                .unsigned_tx
+                .output
+                .get(commit_outpoint.vout as usize)
+                .map(|txout| txout.value.to_sat())
+                .unwrap_or_default(),
+            parent,
+            inscriptions,
+        }
+    }
+}
+
+#[uniffi::export]
+pub fn inscription_session_to_json(session: InscriptionSession) -> String {
+    serde_json::to_string(&session).expect("session serializes to json")
+}
+
+#[uniffi::export]
+pub fn inscription_session_from_json(json: String) -> Result<InscriptionSession, MintError> {
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Spend the commit output of an interrupted inscription to `destination`.
+///
+/// The key path spend only needs the reveal key and script and does not inscribe anything. With
+/// `script_path` the output is spent through the reveal script instead, which inscribes the
+/// envelope on `destination`.
+#[uniffi::export(default(script_path = false))]
+pub fn recover_commit_output(
+    reveal_private_key: String,
+    reveal_script: Arc<crate::Script>,
+    commit_outpoint: crate::OutPoint,
+    commit_value: Arc<crate::Amount>,
+    destination: Arc<crate::Address>,
+    fee_rate: Arc<crate::FeeRate>,
+    script_path: bool,
+) -> Result<Arc<crate::Transaction>, MintError> {
+    let tx = build_recovery_transaction(
+        &reveal_private_key,
+        reveal_script.0.clone(),
+        commit_outpoint.into(),
+        commit_value.0,
+        &destination.0,
+        fee_rate.0,
+        script_path,
+    )?;
+    Ok(Arc::new(tx.into()))
+}
+
+/// [`recover_commit_output`] for a stored session.
+#[uniffi::export(default(script_path = false))]
+pub fn recover_inscription_session(
+    session: InscriptionSession,
+    destination: Arc<crate::Address>,
+    fee_rate: Arc<crate::FeeRate>,
+    script_path: bool,
+) -> Result<Arc<crate::Transaction>, MintError> {
+    let reveal_script = ScriptBuf::from_hex(&session.reveal_script)?;
+    let tx = build_recovery_transaction(
+        &session.reveal_private_key,
+        reveal_script,
+        OutPoint::from_str(&session.commit_outpoint)?,
+        Amount::from_sat(session.commit_value),
+        &destination.0,
+        fee_rate.0,
+        script_path,
+    )?;
+    Ok(Arc::new(tx.into()))
+}
+
+/// Reveal transaction of a stored session, ready to broadcast once the commit is.
+///
+/// Fails for a session with a parent, its reveal spends the parent inscription and has to be
+/// signed from [`inscription_session_reveal_psbt`] first.
+#[uniffi::export]
+pub fn inscription_session_reveal_tx(
+    session: InscriptionSession,
+) -> Result<Arc<crate::Transaction>, MintError> {
+    if let Some(parent_input) = session.parent_input {
+        return Err(MintError::AnyError(format!(
+            "reveal input {parent_input} spends the parent inscription and must be signed, \
+             sign the session reveal PSBT instead"
+        )));
+    }
+    let tx: Transaction = deserialize_hex(&session.reveal_tx)?;
+    Ok(Arc::new(tx.into()))
+}
+
+/// Reveal PSBT of a stored session with a parent.
+///
+/// The inscription input is already finalized, the wallet holding the parent signs
+/// `parent_input` and finalizes the PSBT before broadcasting it.
+#[uniffi::export]
+pub fn inscription_session_reveal_psbt(
+    session: InscriptionSession,
+) -> Result<Arc<crate::Psbt>, MintError> {
+    let psbt: Psbt = session
+        .reveal_psbt
+        .ok_or_else(|| MintError::AnyError("session has no reveal PSBT".to_string()))?
+        .parse()?;
+    Ok(Arc::new(psbt.into()))
+}
+
+fn build_recovery_transaction(
+    reveal_private_key: &str,
+    reveal_script: ScriptBuf,
+    commit_outpoint: OutPoint,
+    commit_value: Amount,
+    destination: &Address,
+    fee_rate: FeeRate,
+    script_path: bool,
+) -> Result<Transaction, MintError> {
+    let secp256k1 = Secp256k1::new();
+    let private_key = PrivateKey::from_wif(reveal_private_key)?;
+    let key_pair = UntweakedKeypair::from_secret_key(&secp256k1, &private_key.inner);
+    let (public_key, _parity) = key_pair.x_only_public_key();
+
+    let taproot_spend_info = TaprootBuilder::new()
+        .add_leaf(0, reveal_script.clone())
+        .map_err(|e| MintError::AnyError(e.to_string()))?
+        .finalize(&secp256k1, public_key)
+        .map_err(|_| MintError::AnyError("reveal script is not a valid leaf".to_string()))?;
+    let control_block = taproot_spend_info
+        .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
+        .expect("should compute control block");
+
+    let prevout = TxOut {
+        value: commit_value,
+        script_pubkey: ScriptBuf::new_p2tr_tweaked(taproot_spend_info.output_key()),
+    };
+
+    let mut tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: commit_outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: destination.script_pubkey(),
+        }],
+    };
+
+    // 按签名后的大小估算手续费
+    let fee = {
+        let mut tx = tx.clone();
+        tx.input[0].witness.push([0; SCHNORR_SIGNATURE_SIZE]);
+        if script_path {
+            tx.input[0].witness.push(&reveal_script);
+            tx.input[0].witness.push(control_block.serialize());
+        }
+        fee_rate * tx.weight()
+    };
+
+    let value = commit_value
+        .checked_sub(fee)
+        .filter(|value| *value >= destination.script_pubkey().minimal_non_dust())
+        .ok_or_else(|| {
+            MintError::AnyError(format!(
+                "commit output {commit_value} does not cover fee {fee} and dust"
+            ))
+        })?;
+    tx.output[0].value = value;
+
+    let mut sighash_cache = SighashCache::new(&mut tx);
+    let prevouts = [prevout];
+    let signature = if script_path {
+        let sighash = sighash_cache
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&prevouts),
+                TapLeafHash::from_script(&reveal_script, LeafVersion::TapScript),
+                TapSighashType::Default,
+            )
+            .expect("signature hash should compute");
+        secp256k1.sign_schnorr(&secp256k1::Message::from(sighash), &key_pair)
+    } else {
+        let sighash = sighash_cache
+            .taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), TapSighashType::Default)
+            .expect("signature hash should compute");
+        let tweaked = key_pair.tap_tweak(&secp256k1, taproot_spend_info.merkle_root());
+        secp256k1.sign_schnorr(&secp256k1::Message::from(sighash), &tweaked.to_keypair())
+    };
+
+    let witness = sighash_cache
+        .witness_mut(0)
+        .expect("getting mutable witness reference should work");
+    witness.push(
+        Signature {
+            signature,
+            sighash_type: TapSighashType::Default,
+        }
+        .to_vec(),
+    );
+    if script_path {
+        witness.push(reveal_script);
+        witness.push(control_block.serialize());
+    }
+
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ordinal::inscription::{mint, InscriptionParent, NamedFile};
+    use crate::testing::{address, block_on, local_output, TAPROOT_ADDRESS};
+    use bdk_wallet::bitcoin::XOnlyPublicKey;
+
+    fn session() -> InscriptionSession {
+        block_on(mint(
+            Network::Bitcoin,
+            vec![local_output(0, 100_000)],
+            NamedFile {
+                name: "a.txt".into(),
+                data: b"a".to_vec(),
+                content_type: None,
+            },
+            TAPROOT_ADDRESS,
+            TAPROOT_ADDRESS,
+            2,
+            None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .session
+    }
+
+    #[track_caller]
+    fn assert_spends_commit(session: &InscriptionSession, tx: &Transaction, script_path: bool) {
+        let commit: Psbt = session.commit_psbt.parse().unwrap();
+        let outpoint = OutPoint::from_str(&session.commit_outpoint).unwrap();
+        let prevout = commit.unsigned_tx.output[outpoint.vout as usize].clone();
+        assert_eq!(prevout.value.to_sat(), session.commit_value);
+        assert_eq!(tx.input[0].previous_output, outpoint);
+
+        let mut cache = SighashCache::new(tx);
+        let prevouts = [prevout.clone()];
+        let (sighash, public_key) = if script_path {
+            let leaf = tx.input[0].witness.taproot_leaf_script().unwrap();
+            let sighash = cache
+                .taproot_script_spend_signature_hash(
+                    0,
+                    &Prevouts::All(&prevouts),
+                    TapLeafHash::from_script(leaf.script, leaf.version),
+                    TapSighashType::Default,
+                )
+                .unwrap();
+            let public_key = XOnlyPublicKey::from_slice(&leaf.script.as_bytes()[1..33]).unwrap();
+            (sighash, public_key)
+        } else {
+            assert_eq!(tx.input[0].witness.len(), 1);
+            let sighash = cache
+                .taproot_key_spend_signature_hash(
+                    0,
+                    &Prevouts::All(&prevouts),
+                    TapSighashType::Default,
+                )
+                .unwrap();
+            let public_key =
+                XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..]).unwrap();
+            (sighash, public_key)
+        };
+
+        let signature = Signature::from_slice(&tx.input[0].witness[0]).unwrap();
+        Secp256k1::verification_only()
+            .verify_schnorr(
+                &signature.signature,
+                &secp256k1::Message::from(sighash),
+                &public_key,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn session_json_roundtrip() {
+        let session = session();
+        let json = inscription_session_to_json(session.clone());
+        assert_eq!(inscription_session_from_json(json).unwrap(), session);
+        assert_eq!(session.reveal_psbt, None);
+        assert!(inscription_session_reveal_psbt(session.clone()).is_err());
+
+        let reveal = inscription_session_reveal_tx(session.clone()).unwrap();
+        assert_eq!(
+            reveal.0.compute_txid().to_string(),
+            session.inscriptions[0].id.trim_end_matches("i0")
+        );
+    }
+
+    #[test]
+    fn parent_session_reveals_from_psbt() {
+        let parent_utxo = local_output(7, 10_000);
+        let session = block_on(mint(
+            Network::Bitcoin,
+            vec![parent_utxo.clone(), local_output(0, 100_000)],
+            NamedFile {
+                name: "child.txt".into(),
+                data: b"child".to_vec(),
+                content_type: None,
+            },
+            TAPROOT_ADDRESS,
+            TAPROOT_ADDRESS,
+            2,
+            None,
+            Some(InscriptionParent {
+                inscription_id: format!("{}i0", parent_utxo.outpoint.txid.0),
+                utxo: parent_utxo,
+            }),
+            None,
+            None,
+        ))
+        .unwrap()
+        .session;
+        assert_eq!(session.parent_input, Some(0));
+
+        let session = inscription_session_from_json(inscription_session_to_json(session)).unwrap();
+        assert!(inscription_session_reveal_tx(session.clone()).is_err());
+
+        let psbt = inscription_session_reveal_psbt(session.clone()).unwrap();
+        let psbt = psbt.0.lock().unwrap();
+        let reveal: Transaction = deserialize_hex(&session.reveal_tx).unwrap();
+        assert_eq!(psbt.unsigned_tx.compute_txid(), reveal.compute_txid());
+        assert!(psbt.inputs[0].final_script_witness.is_none());
+        assert!(psbt.inputs[1].final_script_witness.is_some());
+    }
+
+    #[test]
+    fn recover_key_path_and_script_path() {
+        let session = session();
+        let destination = Arc::new(crate::Address(address()));
+        let fee_rate = Arc::new(crate::FeeRate(FeeRate::from_sat_per_vb_unchecked(1)));
+
+        let tx = recover_inscription_session(
+            session.clone(),
+            destination.clone(),
+            fee_rate.clone(),
+            false,
+        )
+        .unwrap();
+        assert_spends_commit(&session, &tx.0, false);
+
+        let tx = recover_commit_output(
+            session.reveal_private_key.clone(),
+            Arc::new(crate::Script(
+                ScriptBuf::from_hex(&session.reveal_script).unwrap(),
+            )),
+            OutPoint::from_str(&session.commit_outpoint).unwrap().into(),
+            Arc::new(crate::Amount(Amount::from_sat(session.commit_value))),
+            destination.clone(),
+            fee_rate,
+            true,
+        )
+        .unwrap();
+        assert_spends_commit(&session, &tx.0, true);
+        assert_eq!(tx.0.output[0].script_pubkey, address().script_pubkey());
+
+        let high_fee_rate = Arc::new(crate::FeeRate(FeeRate::from_sat_per_vb_unchecked(1_000)));
+        assert!(recover_inscription_session(session, destination, high_fee_rate, false).is_err());
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     cmp,
     ops::{Add, AddAssign},
     str::FromStr,
+    sync::Arc,
 };
 use std::convert::TryFrom;
 use anyhow::{anyhow, bail, Error, Result};
@@ -13,78 +14,126 @@ use crate::ordinal::inscription::{
     common::CYCLE_EPOCHS, decimal_sat::DecimalSat, degree::Degree, epoch::Epoch, height::Height,
     rarity::Rarity,
 };
+use crate::bitcoin::Transaction;
 use crate::ordinal::inscription::common::COIN_VALUE;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Ord, PartialOrd, Deserialize, Serialize)]
+/// A single satoshi, numbered in the order it was mined.
+#[derive(
+    uniffi::Object,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    Debug,
+    Display,
+    Ord,
+    PartialOrd,
+    Deserialize,
+    Serialize,
+)]
+#[uniffi::export(Debug, Display, Eq, Hash)]
 #[serde(transparent)]
 pub struct Sat(pub u64);
 
+#[derive(thiserror::Error, uniffi::Error, Debug)]
+pub enum ParseSatError {
+    #[error("invalid sat:{error_message}")]
+    InvalidSat { error_message: String },
+    #[error("sat {sat} is beyond the last sat")]
+    OutOfRange { sat: u64 },
+}
+
+#[uniffi::export]
 impl Sat {
-    pub(crate) const LAST: Self = Self(Self::SUPPLY - 1);
-    pub(crate) const SUPPLY: u64 = 2099999997690000;
+    #[uniffi::constructor]
+    pub fn new(n: u64) -> Result<Self, ParseSatError> {
+        if n > Self::LAST.0 {
+            return Err(ParseSatError::OutOfRange { sat: n });
+        }
+        Ok(Sat(n))
+    }
+
+    /// Parse a sat from any of its notations: integer (`2099994106992659`), decimal
+    /// (`3891094.16797`), degree (`3°111094′214″16797‴`), percentile (`99.99971949060254%`)
+    /// or name (`satoshi`).
+    #[uniffi::constructor]
+    pub fn from_string(s: &str) -> Result<Self, ParseSatError> {
+        s.parse().map_err(|e: Error| ParseSatError::InvalidSat {
+            error_message: e.to_string(),
+        })
+    }
 
-    pub(crate) fn n(self) -> u64 {
+    pub fn n(&self) -> u64 {
         self.0
     }
 
-    pub(crate) fn degree(self) -> Degree {
-        self.into()
+    pub fn degree(&self) -> Degree {
+        (*self).into()
     }
 
-    pub(crate) fn height(self) -> Height {
+    /// Height of the block that mined this sat.
+    pub fn height(&self) -> Height {
         self.epoch().starting_height()
             + u32::try_from(self.epoch_position() / self.epoch().subsidy()).unwrap()
     }
 
-    pub(crate) fn cycle(self) -> u32 {
-        Epoch::from(self).0 / CYCLE_EPOCHS
+    pub fn cycle(&self) -> u32 {
+        Epoch::from(*self).0 / CYCLE_EPOCHS
     }
 
-    pub(crate) fn nineball(self) -> bool {
+    pub fn nineball(&self) -> bool {
         self.n() >= 50 * COIN_VALUE * 9 && self.n() < 50 * COIN_VALUE * 10
     }
 
-    pub(crate) fn percentile(self) -> String {
+    pub fn percentile(&self) -> String {
         format!("{}%", (self.0 as f64 / Self::LAST.0 as f64) * 100.0)
     }
 
-    pub(crate) fn epoch(self) -> Epoch {
-        self.into()
+    pub fn epoch(&self) -> Epoch {
+        (*self).into()
     }
 
-    pub(crate) fn period(self) -> u32 {
+    pub fn period(&self) -> u32 {
         self.height().n() / DIFFCHANGE_INTERVAL
     }
 
-    pub(crate) fn third(self) -> u64 {
+    /// Offset of this sat within the subsidy of the block that mined it.
+    pub fn third(&self) -> u64 {
         self.epoch_position() % self.epoch().subsidy()
     }
 
-    pub(crate) fn epoch_position(self) -> u64 {
-        self.0 - self.epoch().starting_sat().0
-    }
-
-    pub(crate) fn decimal(self) -> DecimalSat {
-        self.into()
+    pub fn decimal(&self) -> DecimalSat {
+        (*self).into()
     }
 
-    pub(crate) fn rarity(self) -> Rarity {
-        self.into()
+    pub fn rarity(&self) -> Rarity {
+        (*self).into()
     }
 
     /// `Sat::rarity` is expensive and is called frequently when indexing.
     /// Sat::is_common only checks if self is `Rarity::Common` but is
     /// much faster.
-    pub(crate) fn common(self) -> bool {
+    pub fn common(&self) -> bool {
         let epoch = self.epoch();
-        (self.0 - epoch.starting_sat().0) % epoch.subsidy() != 0
+        !(self.0 - epoch.starting_sat().0).is_multiple_of(epoch.subsidy())
+    }
+
+    pub fn coin(&self) -> bool {
+        self.n().is_multiple_of(COIN_VALUE)
+    }
+
+    /// Degree notation, e.g. `0°0′0″0‴`.
+    pub fn degree_string(&self) -> String {
+        self.degree().to_string()
     }
 
-    pub(crate) fn coin(self) -> bool {
-        self.n() % COIN_VALUE == 0
+    /// Decimal notation, e.g. `0.0`.
+    pub fn decimal_string(&self) -> String {
+        self.decimal().to_string()
     }
 
-    pub(crate) fn name(self) -> String {
+    pub fn name(&self) -> String {
         let mut x = Self::SUPPLY - self.0;
         let mut name = String::new();
         while x > 0 {
@@ -98,6 +147,15 @@ impl Sat {
         }
         name.chars().rev().collect()
     }
+}
+
+impl Sat {
+    pub(crate) const LAST: Self = Self(Self::SUPPLY - 1);
+    pub(crate) const SUPPLY: u64 = 2099999997690000;
+
+    pub(crate) fn epoch_position(self) -> u64 {
+        self.0 - self.epoch().starting_sat().0
+    }
 
     fn from_name(s: &str) -> Result<Self> {
         let mut x = 0;
@@ -145,7 +203,7 @@ impl Sat {
         // will increment by 336 every halving.
         let relationship = period_offset + SUBSIDY_HALVING_INTERVAL * CYCLE_EPOCHS - epoch_offset;
 
-        if relationship % HALVING_INCREMENT != 0 {
+        if !relationship.is_multiple_of(HALVING_INCREMENT) {
             bail!("relationship between epoch offset and period offset must be multiple of 336");
         }
 
@@ -257,3 +315,143 @@ impl FromStr for Sat {
         }
     }
 }
+
+/// Half-open range of sats, `[start, end)`.
+#[derive(uniffi::Record, Debug, Clone, PartialEq)]
+pub struct SatRange {
+    pub start: u64,
+    pub end: u64,
+    /// Rarity of `start`. Only the first sat of a block can be uncommon or rarer, so the rest of
+    /// the range is common.
+    pub rarity: Rarity,
+}
+
+#[derive(uniffi::Record, Debug, Clone, PartialEq)]
+pub struct CoinbaseOutputSats {
+    pub vout: u32,
+    /// Newly mined sats assigned to this output.
+    pub ranges: Vec<SatRange>,
+    /// Sats of this output that come from the block's fees. Which sats those are depends on the
+    /// inputs spent in the block, so they cannot be numbered from the height alone.
+    pub fee_sats: u64,
+}
+
+/// Number the sats the coinbase of block `height` assigns to each of its outputs.
+///
+/// The subsidy is handed out first-in-first-out across the outputs, followed by the fees.
+#[uniffi::export]
+pub fn coinbase_sat_ranges(height: u32, coinbase: &Transaction) -> Vec<CoinbaseOutputSats> {
+    let height = Height(height);
+    let mut next = height.starting_sat().n();
+    let mut subsidy = height.subsidy();
+
+    coinbase
+        .0
+        .output
+        .iter()
+        .enumerate()
+        .map(|(vout, output)| {
+            let value = output.value.to_sat();
+            let mined = value.min(subsidy);
+            let ranges = if mined > 0 {
+                vec![SatRange {
+                    start: next,
+                    end: next + mined,
+                    rarity: Sat(next).rarity(),
+                }]
+            } else {
+                Vec::new()
+            };
+            next += mined;
+            subsidy -= mined;
+            CoinbaseOutputSats {
+                vout: vout as u32,
+                ranges,
+                fee_sats: value - mined,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk_wallet::bitcoin;
+    use bdk_wallet::bitcoin::{absolute::LockTime, transaction::Version, Amount, ScriptBuf, TxOut};
+
+    #[test]
+    fn parse_all_notations() {
+        for s in [
+            "2099994106992659",
+            "3891094.16797",
+            "3°111094′214″16797‴",
+            "satoshi",
+        ] {
+            assert_eq!(Sat::from_string(s).unwrap(), Sat(2099994106992659), "{s}");
+        }
+        assert_eq!(Sat::from_string("0%").unwrap(), Sat(0));
+        assert_eq!(Sat::from_string("100%").unwrap(), Sat::LAST);
+
+        let sat = Sat(2099994106992659);
+        assert_eq!(sat.name(), "satoshi");
+        assert_eq!(sat.degree_string(), "3°111094′214″16797‴");
+        assert_eq!(sat.decimal_string(), "3891094.16797");
+        assert_eq!(sat.height(), Height(3891094));
+        assert_eq!(sat.epoch(), Epoch(18));
+        assert_eq!(sat.cycle(), 3);
+        assert_eq!(sat.rarity(), Rarity::Common);
+        assert_eq!(Sat::from_string(&sat.percentile()).unwrap(), sat);
+
+        Sat::new(Sat::SUPPLY).unwrap_err();
+        Sat::from_string("3891094.1000000000").unwrap_err();
+        Sat::from_string("1°0′0″0‴").unwrap();
+        Sat::from_string("0°1′0″0‴").unwrap_err();
+    }
+
+    #[test]
+    fn coinbase_ranges() {
+        let coinbase = Transaction(bitcoin::Transaction {
+            version: Version::ONE,
+            lock_time: LockTime::ZERO,
+            input: Vec::new(),
+            output: [2_000_000_000, 1_200_000_000, 100_000]
+                .into_iter()
+                .map(|value| TxOut {
+                    value: Amount::from_sat(value),
+                    script_pubkey: ScriptBuf::new(),
+                })
+                .collect(),
+        });
+
+        let height = SUBSIDY_HALVING_INTERVAL;
+        let start = Height(height).starting_sat().n();
+        assert_eq!(
+            coinbase_sat_ranges(height, &coinbase),
+            vec![
+                CoinbaseOutputSats {
+                    vout: 0,
+                    ranges: vec![SatRange {
+                        start,
+                        end: start + 2_000_000_000,
+                        rarity: Rarity::Epic,
+                    }],
+                    fee_sats: 0,
+                },
+                CoinbaseOutputSats {
+                    vout: 1,
+                    ranges: vec![SatRange {
+                        start: start + 2_000_000_000,
+                        end: start + 25 * COIN_VALUE,
+                        rarity: Rarity::Common,
+                    }],
+                    fee_sats: 700_000_000,
+                },
+                CoinbaseOutputSats {
+                    vout: 2,
+                    ranges: Vec::new(),
+                    fee_sats: 100_000,
+                },
+            ]
+        );
+    }
+}
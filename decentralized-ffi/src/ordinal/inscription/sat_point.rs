@@ -27,8 +27,6 @@ use {super::*, bitcoin::transaction::ParseOutPointError};
     Ord,
     Default,
     Hash,
-    DeserializeFromStr,
-    SerializeDisplay,
 )]
 pub struct SatPoint {
     pub outpoint: OutPoint,
@@ -41,6 +39,24 @@ impl Display for SatPoint {
     }
 }
 
+impl Serialize for SatPoint {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SatPoint {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(DeserializeFromStr::deserialize(deserializer)?.0)
+    }
+}
+
 impl Encodable for SatPoint {
     fn consensus_encode<S: bitcoin::io::Write + ?Sized>(
         &self,
@@ -52,7 +68,7 @@ impl Encodable for SatPoint {
 }
 
 impl Decodable for SatPoint {
-    fn consensus_decode<D: bitcoin::io::Read + bitcoin::io::BufRead + ?Sized>(
+    fn consensus_decode<D: bitcoin::io::Read + ?Sized>(
         d: &mut D,
     ) -> Result<Self, bitcoin::consensus::encode::Error> {
         Ok(SatPoint {
@@ -0,0 +1,342 @@
+use bdk_wallet::bitcoin::absolute::LockTime;
+use bdk_wallet::bitcoin::psbt::Input;
+use bdk_wallet::bitcoin::secp256k1::{Message, Secp256k1, XOnlyPublicKey};
+use bdk_wallet::bitcoin::sighash::{Prevouts, SighashCache};
+use bdk_wallet::bitcoin::transaction::Version;
+use bdk_wallet::bitcoin::{
+    ecdsa, taproot, Address, Amount, CompressedPublicKey, EcdsaSighashType, Psbt, ScriptBuf,
+    Sequence, TapSighashType, Transaction, TxIn, TxOut, Witness,
+};
+
+use crate::types::LocalOutput;
+
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum ListingError {
+    #[error("price {price} sat is below the dust limit {dust} sat")]
+    DustPrice { price: u64, dust: u64 },
+
+    #[error("output is not owned by the wallet")]
+    NotOwned,
+
+    #[error("signer error: {error_message}")]
+    Signer { error_message: String },
+
+    #[error("wallet could not sign the listing")]
+    NotSigned,
+
+    #[error("listing has no inputs")]
+    NoInputs,
+
+    #[error("listing input {index} is missing its previous output")]
+    MissingPrevout { index: u32 },
+
+    #[error("listing input {index} is not signed")]
+    Unsigned { index: u32 },
+
+    #[error("listing input {index} has no payout output")]
+    MissingPayout { index: u32 },
+
+    #[error("payout {value} sat is below the dust limit {dust} sat")]
+    DustPayout { value: u64, dust: u64 },
+
+    #[error("payout {value} sat exceeds the maximum money supply")]
+    PayoutTooHigh { value: u64 },
+
+    #[error("listing price overflows")]
+    PriceOverflow,
+
+    #[error("listing price {price} sat exceeds {max_price} sat")]
+    PriceTooHigh { price: u64, max_price: u64 },
+
+    #[error("unsupported listing script: {script}")]
+    UnsupportedScript { script: String },
+
+    #[error("listing is signed with {sighash} instead of SIGHASH_SINGLE|ANYONECANPAY")]
+    Sighash { sighash: String },
+
+    #[error("invalid listing signature: {error_message}")]
+    InvalidSignature { error_message: String },
+}
+
+impl ListingError {
+    fn invalid_signature(e: impl ToString) -> Self {
+        Self::InvalidSignature {
+            error_message: e.to_string(),
+        }
+    }
+}
+
+/// Unsigned seller half of a listing: the ordinal output as the only input, paid for by the
+/// output at the same index.
+pub(crate) struct ListingPsbtBuilder {
+    pub(crate) output: LocalOutput,
+    pub(crate) price: Amount,
+    pub(crate) payout_addr: Address,
+}
+
+impl ListingPsbtBuilder {
+    pub(crate) fn build(self) -> Result<Psbt, ListingError> {
+        let payout_script = self.payout_addr.script_pubkey();
+        let dust = payout_script.minimal_non_dust();
+        if self.price < dust {
+            return Err(ListingError::DustPrice {
+                price: self.price.to_sat(),
+                dust: dust.to_sat(),
+            });
+        }
+
+        let prevout: TxOut = (&self.output.txout).into();
+        let sighash_type = if prevout.script_pubkey.is_p2tr() {
+            TapSighashType::SinglePlusAnyoneCanPay.into()
+        } else {
+            EcdsaSighashType::SinglePlusAnyoneCanPay.into()
+        };
+
+        let unsigned_tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: self.output.outpoint.into(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: self.price,
+                script_pubkey: payout_script,
+            }],
+        };
+
+        Ok(Psbt {
+            unsigned_tx,
+            version: 0,
+            xpub: Default::default(),
+            proprietary: Default::default(),
+            unknown: Default::default(),
+            inputs: vec![Input {
+                witness_utxo: Some(prevout),
+                sighash_type: Some(sighash_type),
+                ..Default::default()
+            }],
+            outputs: vec![Default::default()],
+        })
+    }
+}
+
+/// Check that `txin` is signed `SIGHASH_SINGLE|ANYONECANPAY` over `payout` by the owner of
+/// `prevout`.
+///
+/// The signature is checked against the version 2, zero locktime transaction the snipe builders
+/// produce, so listings signed for anything else are rejected as well.
+pub(crate) fn verify_listing_input(
+    txin: &TxIn,
+    prevout: &TxOut,
+    payout: &TxOut,
+) -> Result<(), ListingError> {
+    let dust = payout.script_pubkey.minimal_non_dust();
+    if payout.script_pubkey.is_op_return() || payout.value < dust {
+        return Err(ListingError::DustPayout {
+            value: payout.value.to_sat(),
+            dust: dust.to_sat(),
+        });
+    }
+    if payout.value > Amount::MAX_MONEY {
+        return Err(ListingError::PayoutTooHigh {
+            value: payout.value.to_sat(),
+        });
+    }
+
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: txin.previous_output,
+            script_sig: ScriptBuf::new(),
+            sequence: txin.sequence,
+            witness: Witness::new(),
+        }],
+        output: vec![payout.clone()],
+    };
+    let mut cache = SighashCache::new(&tx);
+    let secp = Secp256k1::verification_only();
+    let script = &prevout.script_pubkey;
+    let unsupported = || ListingError::UnsupportedScript {
+        script: script.to_asm_string(),
+    };
+
+    if script.is_p2tr() {
+        let (Some(signature), 1) = (txin.witness.nth(0), txin.witness.len()) else {
+            return Err(unsupported());
+        };
+        let signature =
+            taproot::Signature::from_slice(signature).map_err(ListingError::invalid_signature)?;
+        if signature.sighash_type != TapSighashType::SinglePlusAnyoneCanPay {
+            return Err(ListingError::Sighash {
+                sighash: signature.sighash_type.to_string(),
+            });
+        }
+        let key = XOnlyPublicKey::from_slice(&script.as_bytes()[2..])
+            .map_err(ListingError::invalid_signature)?;
+        let sighash = cache
+            .taproot_key_spend_signature_hash(
+                0,
+                &Prevouts::One(0, prevout),
+                signature.sighash_type,
+            )
+            .map_err(ListingError::invalid_signature)?;
+        return secp
+            .verify_schnorr(&signature.signature, &Message::from(sighash), &key)
+            .map_err(ListingError::invalid_signature);
+    }
+
+    // p2wpkh, bare or nested in p2sh
+    let script_code = if script.is_p2wpkh() && txin.script_sig.is_empty() {
+        script.clone()
+    } else if script.is_p2sh() {
+        match txin.script_sig.redeem_script() {
+            Some(redeem)
+                if redeem.is_p2wpkh() && ScriptBuf::new_p2sh(&redeem.script_hash()) == *script =>
+            {
+                redeem.to_owned()
+            }
+            _ => return Err(unsupported()),
+        }
+    } else {
+        return Err(unsupported());
+    };
+
+    let (Some(signature), Some(public_key), 2) =
+        (txin.witness.nth(0), txin.witness.nth(1), txin.witness.len())
+    else {
+        return Err(unsupported());
+    };
+    let signature =
+        ecdsa::Signature::from_slice(signature).map_err(ListingError::invalid_signature)?;
+    if signature.sighash_type != EcdsaSighashType::SinglePlusAnyoneCanPay {
+        return Err(ListingError::Sighash {
+            sighash: signature.sighash_type.to_string(),
+        });
+    }
+    let public_key =
+        CompressedPublicKey::from_slice(public_key).map_err(ListingError::invalid_signature)?;
+    if ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash()) != script_code {
+        return Err(ListingError::invalid_signature(
+            "public key does not match the previous output",
+        ));
+    }
+    let sighash = cache
+        .p2wpkh_signature_hash(0, &script_code, prevout.value, signature.sighash_type)
+        .map_err(ListingError::invalid_signature)?;
+    secp.verify_ecdsa(&Message::from(sighash), &signature.signature, &public_key.0)
+        .map_err(ListingError::invalid_signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::Psbt as FfiPsbt;
+    use crate::testing::{address, funded_wallet};
+    use crate::ordinal::{build_listing_psbt, verify_listing_psbt};
+    use bdk_wallet::bitcoin::secp256k1::SecretKey;
+    use bdk_wallet::bitcoin::{OutPoint, PublicKey};
+    use std::sync::Arc;
+
+    fn ffi_amount(sat: u64) -> Arc<crate::bitcoin::Amount> {
+        Arc::new(crate::bitcoin::Amount(Amount::from_sat(sat)))
+    }
+
+    #[test]
+    fn wallet_listing_verifies() {
+        let wallet = funded_wallet(&[10_000]);
+        let output = wallet.list_unspent().remove(0);
+        let listing = build_listing_psbt(
+            wallet,
+            output,
+            ffi_amount(50_000),
+            Arc::new(address().into()),
+        )
+        .unwrap();
+
+        let listings = verify_listing_psbt(listing.clone(), None).unwrap();
+        assert_eq!(listings.len(), 1);
+        assert_eq!(listings[0].payout.value.0, Amount::from_sat(50_000));
+        assert_eq!(listings[0].prevout.value.0, Amount::from_sat(10_000));
+
+        assert!(matches!(
+            verify_listing_psbt(listing.clone(), Some(ffi_amount(40_000))),
+            Err(ListingError::PriceTooHigh { price: 50_000, max_price: 40_000 })
+        ));
+
+        let mut tampered = listing.0.lock().unwrap().clone();
+        tampered.unsigned_tx.output[0].value = Amount::from_sat(5_000);
+        assert!(matches!(
+            verify_listing_psbt(Arc::new(FfiPsbt::from(tampered)), None),
+            Err(ListingError::InvalidSignature { .. })
+        ));
+    }
+
+    #[test]
+    fn p2wpkh_listing_sighash() {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(&[7; 32]).unwrap();
+        let public_key = CompressedPublicKey(PublicKey::from_private_key(
+            &secp,
+            &bdk_wallet::bitcoin::PrivateKey::new(secret, bdk_wallet::bitcoin::Network::Bitcoin),
+        )
+        .inner);
+        let prevout = TxOut {
+            value: Amount::from_sat(546),
+            script_pubkey: ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash()),
+        };
+        let payout = TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: address().script_pubkey(),
+        };
+
+        let sign = |sighash_type: EcdsaSighashType| {
+            let mut txin = TxIn {
+                previous_output: OutPoint::null(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            };
+            let tx = Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: vec![txin.clone()],
+                output: vec![payout.clone()],
+            };
+            let sighash = SighashCache::new(&tx)
+                .p2wpkh_signature_hash(0, &prevout.script_pubkey, prevout.value, sighash_type)
+                .unwrap();
+            let signature = ecdsa::Signature {
+                signature: secp.sign_ecdsa(&Message::from(sighash), &secret),
+                sighash_type,
+            };
+            txin.witness = Witness::p2wpkh(&signature, &public_key.0);
+            txin
+        };
+
+        verify_listing_input(
+            &sign(EcdsaSighashType::SinglePlusAnyoneCanPay),
+            &prevout,
+            &payout,
+        )
+        .unwrap();
+        assert!(matches!(
+            verify_listing_input(&sign(EcdsaSighashType::All), &prevout, &payout),
+            Err(ListingError::Sighash { .. })
+        ));
+
+        let too_high = TxOut {
+            value: Amount::MAX_MONEY + Amount::from_sat(1),
+            ..payout.clone()
+        };
+        assert!(matches!(
+            verify_listing_input(
+                &sign(EcdsaSighashType::SinglePlusAnyoneCanPay),
+                &prevout,
+                &too_high
+            ),
+            Err(ListingError::PayoutTooHigh { .. })
+        ));
+    }
+}
@@ -0,0 +1,366 @@
+use bdk_wallet::bitcoin::absolute::LockTime;
+use bdk_wallet::bitcoin::transaction::Version;
+use bdk_wallet::bitcoin::{Address, Amount, FeeRate, OutPoint, Psbt, Transaction, TxOut};
+
+use crate::ordinal::dummy_transaction::DummyTransaction;
+use crate::ordinal::rune::{RuneId, Runestone, RunestoneError};
+use crate::ordinal::transfer::{InsufficientFunds, fund_psbt, push_prevout};
+use crate::types::LocalOutput;
+use std::sync::Arc;
+
+/// Longest chain of unconfirmed mints, the default mempool ancestor limit.
+pub(crate) const MAX_MINT_CHAIN: u32 = 25;
+
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum RuneMintError {
+    #[error("repeat {repeat} is out of range 1..={max}")]
+    InvalidRepeat { repeat: u32, max: u32 },
+
+    #[error("mint has not started, starts at height {start}")]
+    MintNotStarted { start: u64 },
+
+    #[error("mint has ended at height {end}")]
+    MintEnded { end: u64 },
+
+    #[error("mint cap {cap} reached, {remaining} mints remaining")]
+    CapExceeded { cap: String, remaining: String },
+
+    #[error("insufficient funds: required {required} sat, available {available} sat")]
+    InsufficientFunds { required: u64, available: u64 },
+
+    #[error("postage {postage} sat is below the dust limit {dust} sat")]
+    DustPostage { postage: u64, dust: u64 },
+
+    #[error("runestone error: {error_message}")]
+    Runestone { error_message: String },
+}
+
+impl From<RunestoneError> for RuneMintError {
+    fn from(e: RunestoneError) -> Self {
+        Self::Runestone {
+            error_message: e.to_string(),
+        }
+    }
+}
+
+impl From<InsufficientFunds> for RuneMintError {
+    fn from(e: InsufficientFunds) -> Self {
+        Self::InsufficientFunds {
+            required: e.required.to_sat(),
+            available: e.available.to_sat(),
+        }
+    }
+}
+
+/// Open mint of an etching, as known by the caller.
+pub(crate) struct MintState {
+    pub(crate) terms: ordinals::Terms,
+    pub(crate) mints: u128,
+    /// height of the block the mints are expected in
+    pub(crate) height: u64,
+}
+
+impl MintState {
+    /// Same rules as ord's `RuneEntry::mintable`, for `repeat` mints in a row.
+    fn check(&self, id: ordinals::RuneId, repeat: u32) -> Result<(), RuneMintError> {
+        let (height_start, height_end) = self.terms.height;
+        let (offset_start, offset_end) = self.terms.offset;
+
+        let start = [
+            height_start,
+            offset_start.map(|offset| id.block.saturating_add(offset)),
+        ]
+        .into_iter()
+        .flatten()
+        .max();
+        let end = [
+            height_end,
+            offset_end.map(|offset| id.block.saturating_add(offset)),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+
+        if let Some(start) = start
+            && self.height < start
+        {
+            return Err(RuneMintError::MintNotStarted { start });
+        }
+        if let Some(end) = end
+            && self.height >= end
+        {
+            return Err(RuneMintError::MintEnded { end });
+        }
+
+        let cap = self.terms.cap.unwrap_or_default();
+        let remaining = cap.saturating_sub(self.mints);
+        if remaining < u128::from(repeat) {
+            return Err(RuneMintError::CapExceeded {
+                cap: cap.to_string(),
+                remaining: remaining.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) struct RuneMintPsbtBuilder {
+    pub(crate) cardinal_utxos: Vec<LocalOutput>,
+    pub(crate) rune_id: ordinals::RuneId,
+    pub(crate) destination: Address,
+    pub(crate) change_addr: Address,
+    pub(crate) repeat: u32,
+    pub(crate) postage: Option<Amount>,
+    pub(crate) fee_rate: FeeRate,
+    pub(crate) mint_state: Option<MintState>,
+}
+
+impl RuneMintPsbtBuilder {
+    /// Build `repeat` mint transactions, each one spending the change of the previous one.
+    ///
+    /// The first transaction is funded from the cardinal utxos and keeps enough change to pay for
+    /// the rest of the chain.
+    pub(crate) fn build(self) -> Result<Vec<Psbt>, RuneMintError> {
+        if !(1..=MAX_MINT_CHAIN).contains(&self.repeat) {
+            return Err(RuneMintError::InvalidRepeat {
+                repeat: self.repeat,
+                max: MAX_MINT_CHAIN,
+            });
+        }
+        if let Some(mint_state) = &self.mint_state {
+            mint_state.check(self.rune_id, self.repeat)?;
+        }
+
+        let destination = self.destination.script_pubkey();
+        let dust = destination.minimal_non_dust();
+        let postage = match self.postage {
+            None => dust,
+            Some(postage) if postage < dust => {
+                return Err(RuneMintError::DustPostage {
+                    postage: postage.to_sat(),
+                    dust: dust.to_sat(),
+                });
+            }
+            Some(postage) => postage,
+        };
+
+        let runestone = Runestone {
+            edicts: vec![],
+            etching: None,
+            mint: Some(Arc::new(RuneId::from(self.rune_id))),
+            pointer: None,
+        }
+        .encipher()?;
+        let change_script = self.change_addr.script_pubkey();
+
+        // every chained mint spends one change output and creates the same outputs
+        let chained_cost = {
+            let mut dummy_tx = DummyTransaction::new();
+            dummy_tx.append_input(change_script.clone(), None, None);
+            dummy_tx.append_output(destination.clone());
+            dummy_tx.append_output(runestone.clone());
+            dummy_tx.append_output(change_script.clone());
+            self.fee_rate
+                .fee_wu(dummy_tx.weight())
+                .and_then(|fee| postage.checked_add(fee))
+                .unwrap_or(Amount::MAX_MONEY)
+        };
+
+        let mut psbts: Vec<Psbt> = Vec::new();
+        for i in 0..self.repeat {
+            let mut unsigned_tx = Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: vec![],
+                output: vec![],
+            };
+            let mut dummy_tx = DummyTransaction::new();
+            let mut psbt_inputs = Vec::new();
+            let mut input_amount = Amount::ZERO;
+
+            // spend the change of the previous mint
+            if let Some(previous) = psbts.last() {
+                let vout = previous.unsigned_tx.output.len() - 1;
+                let prevout = previous.unsigned_tx.output[vout].clone();
+                input_amount = prevout.value;
+                push_prevout(
+                    &mut unsigned_tx,
+                    &mut psbt_inputs,
+                    &mut dummy_tx,
+                    OutPoint {
+                        txid: previous.unsigned_tx.compute_txid(),
+                        vout: vout as u32,
+                    },
+                    prevout,
+                );
+            }
+
+            // minted runes go to the first non OP_RETURN output
+            unsigned_tx.output.push(TxOut {
+                value: postage,
+                script_pubkey: destination.clone(),
+            });
+            dummy_tx.append_output(destination.clone());
+            unsigned_tx.output.push(TxOut {
+                value: Amount::ZERO,
+                script_pubkey: runestone.clone(),
+            });
+            dummy_tx.append_output(runestone.clone());
+
+            let cardinal_utxos = if i == 0 {
+                self.cardinal_utxos.clone()
+            } else {
+                vec![]
+            };
+            let reserved = chained_cost
+                .checked_mul(u64::from(self.repeat - i - 1))
+                .unwrap_or(Amount::MAX_MONEY);
+            fund_psbt(
+                &mut unsigned_tx,
+                &mut psbt_inputs,
+                &mut dummy_tx,
+                input_amount,
+                cardinal_utxos,
+                TxOut {
+                    value: reserved,
+                    script_pubkey: change_script.clone(),
+                },
+                self.fee_rate,
+            )?;
+
+            let o_len = unsigned_tx.output.len();
+            psbts.push(Psbt {
+                unsigned_tx,
+                version: 0,
+                xpub: Default::default(),
+                proprietary: Default::default(),
+                unknown: Default::default(),
+                inputs: psbt_inputs,
+                outputs: vec![Default::default(); o_len],
+            });
+        }
+
+        Ok(psbts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{address, local_output as utxo};
+
+    const ID: ordinals::RuneId = ordinals::RuneId {
+        block: 840_000,
+        tx: 1,
+    };
+
+    fn builder(repeat: u32, mint_state: Option<MintState>) -> RuneMintPsbtBuilder {
+        RuneMintPsbtBuilder {
+            cardinal_utxos: vec![utxo(0, 20_000), utxo(1, 20_000)],
+            rune_id: ID,
+            destination: address(),
+            change_addr: address(),
+            repeat,
+            postage: None,
+            fee_rate: FeeRate::from_sat_per_vb_unchecked(5),
+            mint_state,
+        }
+    }
+
+    fn mint_state(mints: u128, height: u64) -> MintState {
+        MintState {
+            terms: ordinals::Terms {
+                amount: Some(1_000),
+                cap: Some(10),
+                height: (Some(840_010), None),
+                offset: (None, Some(100)),
+            },
+            mints,
+            height,
+        }
+    }
+
+    #[test]
+    fn mint_chain() {
+        let psbts = builder(3, Some(mint_state(0, 840_050))).build().unwrap();
+        assert_eq!(psbts.len(), 3);
+
+        for (i, psbt) in psbts.iter().enumerate() {
+            let tx = &psbt.unsigned_tx;
+            match ordinals::Runestone::decipher(tx).unwrap() {
+                ordinals::Artifact::Runestone(runestone) => {
+                    assert_eq!(runestone.mint, Some(ID));
+                    assert!(runestone.edicts.is_empty());
+                }
+                ordinals::Artifact::Cenotaph(cenotaph) => panic!("cenotaph: {cenotaph:?}"),
+            }
+            assert_eq!(tx.output[0].value, Amount::from_sat(330));
+            assert_eq!(tx.output[0].script_pubkey, address().script_pubkey());
+
+            let input_amount = psbt
+                .inputs
+                .iter()
+                .map(|input| input.witness_utxo.as_ref().unwrap().value)
+                .sum::<Amount>();
+            let fee = input_amount - tx.output.iter().map(|txout| txout.value).sum::<Amount>();
+            assert!(
+                fee >= FeeRate::from_sat_per_vb_unchecked(5)
+                    .fee_vb(tx.vsize() as u64)
+                    .unwrap()
+            );
+
+            if i > 0 {
+                let previous = &psbts[i - 1].unsigned_tx;
+                assert_eq!(tx.input.len(), 1);
+                assert_eq!(
+                    tx.input[0].previous_output,
+                    OutPoint {
+                        txid: previous.compute_txid(),
+                        vout: 2
+                    }
+                );
+            }
+        }
+
+        // the first utxo covers the whole chain
+        assert_eq!(psbts[0].unsigned_tx.input.len(), 1);
+    }
+
+    #[test]
+    fn mint_errors() {
+        assert!(matches!(
+            builder(0, None).build(),
+            Err(RuneMintError::InvalidRepeat { .. })
+        ));
+        assert!(matches!(
+            builder(MAX_MINT_CHAIN + 1, None).build(),
+            Err(RuneMintError::InvalidRepeat { .. })
+        ));
+        assert!(matches!(
+            builder(1, Some(mint_state(0, 840_009))).build(),
+            Err(RuneMintError::MintNotStarted { start: 840_010 })
+        ));
+        assert!(matches!(
+            builder(1, Some(mint_state(0, 840_100))).build(),
+            Err(RuneMintError::MintEnded { end: 840_100 })
+        ));
+        assert!(matches!(
+            builder(3, Some(mint_state(8, 840_050))).build(),
+            Err(RuneMintError::CapExceeded { .. })
+        ));
+        assert!(builder(2, Some(mint_state(8, 840_050))).build().is_ok());
+        let mut small = builder(2, None);
+        small.cardinal_utxos = vec![utxo(0, 1_500)];
+        assert!(matches!(
+            small.build(),
+            Err(RuneMintError::InsufficientFunds { .. })
+        ));
+        let mut huge_postage = builder(2, None);
+        huge_postage.postage = Some(Amount::from_sat(u64::MAX));
+        assert!(matches!(
+            huge_postage.build(),
+            Err(RuneMintError::InsufficientFunds { .. })
+        ));
+    }
+}
@@ -1,7 +1,15 @@
 use crate::bitcoin::{Address, Amount, FeeRate, Psbt, Transaction, TxIn, TxOut};
-use crate::ordinal::rune::RuneId;
-use crate::ordinal::snipe::SnipeError;
-use crate::types::LocalOutput;
+use crate::ordinal::listing::ListingError;
+use crate::ordinal::mint::RuneMintError;
+use crate::ordinal::rune::{RuneAmount, RuneId, Terms};
+use crate::ordinal::transfer::RuneTransferError;
+use crate::ordinal::unbundle::UnbundleError;
+use crate::descriptor::Descriptor;
+use crate::esplora::EsploraClient;
+use crate::ordinal::snipe::{SnipeError, SnipePairReport};
+use crate::ordinal::tracker::OrdinalTracker;
+use crate::types::{LocalOutput, SignOptions};
+use crate::wallet::Wallet;
 use bdk_wallet::bitcoin;
 use bdk_wallet::bitcoin::TapSighashType;
 use std::collections::HashMap;
@@ -11,7 +19,13 @@ use std::sync::Arc;
 use uniffi::export;
 
 mod dummy_transaction;
+pub(crate) mod listing;
+pub(crate) mod mint;
 pub(crate) mod snipe;
+pub(crate) mod tracker;
+pub(crate) mod transfer;
+pub(crate) mod unbundle;
+
 
 pub(crate) mod inscription;
 pub(crate) mod rune;
@@ -22,23 +36,30 @@ pub(crate) struct TxInAndTxOut {
     pub txout: TxOut,
 }
 
+/// Sighash type declared by a signature: its trailing byte, or `SIGHASH_DEFAULT` for a bare
+/// 64 byte schnorr signature.
+pub(crate) fn declared_sighash(signature: &[u8]) -> Option<TapSighashType> {
+    if signature.len() == 64 {
+        return Some(TapSighashType::Default);
+    }
+    TapSighashType::from_consensus_u8(*signature.last()?).ok()
+}
+
 #[uniffi::export]
 pub fn get_single_anyone_pay_tx_pair(tx: &Transaction) -> Vec<TxInAndTxOut> {
     let mut pair = Vec::new();
     for (i, txin) in tx.input().iter().enumerate() {
-        if let Some(sign) = txin.witness.first() {
-            if let Some(sign_type) = sign.last() {
-                if let Ok(t) = TapSighashType::from_consensus_u8(*sign_type) {
-                    if t == TapSighashType::SinglePlusAnyoneCanPay {
-                        if let Some(txout) = tx.output().get(i) {
-                            pair.push(TxInAndTxOut {
-                                txin: txin.clone(),
-                                txout: txout.clone(),
-                            });
-                        }
-                    }
-                }
-            }
+        let sighash = txin
+            .witness
+            .first()
+            .and_then(|signature| declared_sighash(signature));
+        if sighash == Some(TapSighashType::SinglePlusAnyoneCanPay)
+            && let Some(txout) = tx.output().get(i)
+        {
+            pair.push(TxInAndTxOut {
+                txin: txin.clone(),
+                txout: txout.clone(),
+            });
         }
     }
     pair
@@ -50,7 +71,22 @@ pub struct SnipeRuneUtxoPair {
     pub prevout: TxOut,
     pub txout: TxOut,
     pub rune_id: Arc<RuneId>,
-    pub amount: String,
+    pub amount: Arc<RuneAmount>,
+}
+
+/// Validate rune listings before passing them to [`build_rune_snipe_psbt`].
+#[uniffi::export(default(esplora = None))]
+pub fn validate_rune_snipe_pairs(
+    snipe_utxo_pairs: Vec<SnipeRuneUtxoPair>,
+    esplora: Option<Arc<EsploraClient>>,
+) -> Vec<SnipePairReport> {
+    snipe::validate_snipe_pairs(
+        snipe_utxo_pairs
+            .iter()
+            .map(|x| ((&x.txin).into(), (&x.prevout).into(), (&x.txout).into()))
+            .collect(),
+        esplora.as_deref(),
+    )
 }
 
 #[derive(uniffi::Record)]
@@ -73,7 +109,7 @@ pub fn build_rune_snipe_psbt(
 ) -> Result<SnipePsbtPair, snipe::SnipeError> {
     let mut runes_map = HashMap::new();
     for rune in snipe_utxo_pairs.iter() {
-        let amount = u128::from_str(&rune.amount)?;
+        let amount = rune.amount.0;
         let rune_id = ordinals::RuneId {
             block: rune.rune_id.block,
             tx: rune.rune_id.tx,
@@ -101,18 +137,18 @@ pub fn build_rune_snipe_psbt(
     let tx = snipe_psbt.clone().extract_tx_unchecked_fee_rate();
     let outpoint = tx.output.first().unwrap();
 
-    let split_psbt = snipe::SplitRunePsbtBuilder {
-        ordi_addr_outpoint_with_amount: (
-            ordi_addr.0.clone(),
-            bitcoin::OutPoint {
-                txid: tx.compute_txid(),
-                vout: 0,
-            },
-            outpoint.value,
-        ),
-        runes: runes_map,
-        recv_addr: rune_recv_addr.unwrap_or(ordi_addr).0.clone(),
+    let split_psbt = unbundle::UnbundlePsbtBuilder {
+        outpoint: bitcoin::OutPoint {
+            txid: tx.compute_txid(),
+            vout: 0,
+        },
+        prevout: outpoint.clone(),
+        inscription_offsets: vec![],
+        runes: runes_map.into_iter().collect(),
+        cardinal_utxos: vec![],
+        destination: rune_recv_addr.unwrap_or(ordi_addr).0.clone(),
         change_addr: pay_addr.0.clone(),
+        postage: None,
         fee_rate: split_rate.0,
     }
     .build()?;
@@ -123,6 +159,178 @@ pub fn build_rune_snipe_psbt(
     })
 }
 
+#[derive(uniffi::Record, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RuneBalance {
+    pub rune_id: Arc<RuneId>,
+    pub amount: Arc<RuneAmount>,
+}
+
+/// Wallet output together with the runes it holds.
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct RuneUtxo {
+    pub output: LocalOutput,
+    pub balances: Vec<RuneBalance>,
+}
+
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct RuneTransfer {
+    pub rune_id: Arc<RuneId>,
+    pub amount: Arc<RuneAmount>,
+    pub destination: Arc<Address>,
+}
+
+/// Build a PSBT sending runes held by `rune_utxos` to the `transfers` destinations.
+///
+/// Every transfer gets its own output of `postage` sats (dust limit if none) with an edict, runes
+/// left over are sent by the runestone pointer to `rune_change_addr` (`change_addr` if none), fees
+/// are paid from `cardinal_utxos` and BTC change goes to `change_addr`.
+#[uniffi::export(default(postage = None, rune_change_addr = None))]
+pub fn build_rune_transfer_psbt(
+    rune_utxos: Vec<RuneUtxo>,
+    cardinal_utxos: Vec<LocalOutput>,
+    transfers: Vec<RuneTransfer>,
+    change_addr: Arc<Address>,
+    fee_rate: Arc<FeeRate>,
+    postage: Option<Arc<Amount>>,
+    rune_change_addr: Option<Arc<Address>>,
+) -> Result<Arc<Psbt>, RuneTransferError> {
+    let psbt = transfer::RuneTransferPsbtBuilder {
+        rune_utxos: rune_utxos
+            .into_iter()
+            .map(|utxo| {
+                let mut balances = HashMap::new();
+                for balance in utxo.balances {
+                    let amount: &mut u128 = balances
+                        .entry(balance.rune_id.as_ref().into())
+                        .or_default();
+                    *amount = amount.checked_add(balance.amount.0).ok_or_else(|| {
+                        RuneTransferError::AmountOverflow {
+                            rune_id: balance.rune_id.to_string(),
+                        }
+                    })?;
+                }
+                Ok((utxo.output, balances))
+            })
+            .collect::<Result<_, RuneTransferError>>()?,
+        cardinal_utxos,
+        transfers: transfers
+            .into_iter()
+            .map(|transfer| {
+                (
+                    transfer.rune_id.as_ref().into(),
+                    transfer.amount.0,
+                    transfer.destination.0.clone(),
+                )
+            })
+            .collect(),
+        rune_change_addr: rune_change_addr.unwrap_or(change_addr.clone()).0.clone(),
+        change_addr: change_addr.0.clone(),
+        postage: postage.map(|postage| postage.0),
+        fee_rate: fee_rate.0,
+    }
+    .build()?;
+
+    Ok(Arc::new(Psbt::from(psbt)))
+}
+
+/// Build a PSBT splitting `output` into one output per inscription and rune balance it holds.
+///
+/// `inscription_offsets` are the sat offsets of the inscriptions inside `output`, each of them
+/// ends up at offset 0 of its own output of `postage` sats (dust limit if none) to `destination`.
+/// `runes` must list every balance of `output`, each one is moved by an edict to its own output.
+/// Sats in between go to `change_addr`, fees are paid from `output` first and then from
+/// `cardinal_utxos`.
+#[allow(clippy::too_many_arguments)]
+#[uniffi::export(default(postage = None))]
+pub fn build_unbundle_psbt(
+    output: LocalOutput,
+    inscription_offsets: Vec<u64>,
+    runes: Vec<RuneBalance>,
+    cardinal_utxos: Vec<LocalOutput>,
+    destination: Arc<Address>,
+    change_addr: Arc<Address>,
+    fee_rate: Arc<FeeRate>,
+    postage: Option<Arc<Amount>>,
+) -> Result<Arc<Psbt>, UnbundleError> {
+    let mut balances = HashMap::new();
+    for balance in runes {
+        let amount: &mut u128 = balances
+            .entry(balance.rune_id.as_ref().into())
+            .or_default();
+        *amount = amount
+            .checked_add(balance.amount.0)
+            .ok_or_else(|| UnbundleError::AmountOverflow {
+                rune_id: balance.rune_id.to_string(),
+            })?;
+    }
+
+    let psbt = unbundle::UnbundlePsbtBuilder {
+        outpoint: output.outpoint.clone().into(),
+        prevout: (&output.txout).into(),
+        inscription_offsets,
+        runes: balances.into_iter().collect(),
+        cardinal_utxos,
+        destination: destination.0.clone(),
+        change_addr: change_addr.0.clone(),
+        postage: postage.map(|postage| postage.0),
+        fee_rate: fee_rate.0,
+    }
+    .build()?;
+
+    Ok(Arc::new(Psbt::from(psbt)))
+}
+
+/// Mint terms of a rune together with its current mint progress.
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct RuneMintTerms {
+    pub terms: Terms,
+    /// number of mints so far
+    pub mints: Arc<RuneAmount>,
+    /// height of the block the mints are expected in
+    pub height: u64,
+}
+
+/// Build `repeat` PSBTs minting `rune_id` to `destination`, each spending the change of the
+/// previous one.
+///
+/// Fees for the whole chain are paid from `cardinal_utxos` by the first PSBT and change goes to
+/// `change_addr`; every PSBT after the first can only be signed once the previous transaction is
+/// known to the wallet. When `terms` is given the mints are checked against the cap and the
+/// height and offset windows.
+#[allow(clippy::too_many_arguments)]
+#[uniffi::export(default(postage = None, terms = None))]
+pub fn build_rune_mint_psbt(
+    cardinal_utxos: Vec<LocalOutput>,
+    rune_id: Arc<RuneId>,
+    destination: Arc<Address>,
+    change_addr: Arc<Address>,
+    repeat: u32,
+    fee_rate: Arc<FeeRate>,
+    postage: Option<Arc<Amount>>,
+    terms: Option<RuneMintTerms>,
+) -> Result<Vec<Arc<Psbt>>, RuneMintError> {
+    let psbts = mint::RuneMintPsbtBuilder {
+        cardinal_utxos,
+        rune_id: rune_id.as_ref().into(),
+        destination: destination.0.clone(),
+        change_addr: change_addr.0.clone(),
+        repeat,
+        postage: postage.map(|postage| postage.0),
+        fee_rate: fee_rate.0,
+        mint_state: terms.map(|terms| mint::MintState {
+            terms: (&terms.terms).into(),
+            mints: terms.mints.0,
+            height: terms.height,
+        }),
+    }
+    .build()?;
+
+    Ok(psbts
+        .into_iter()
+        .map(|psbt| Arc::new(Psbt::from(psbt)))
+        .collect())
+}
+
 #[derive(uniffi::Record, Debug, Clone)]
 pub struct SnipeInscriptionPair {
     pub txin: TxIn,
@@ -130,6 +338,77 @@ pub struct SnipeInscriptionPair {
     pub txout: TxOut,
 }
 
+/// Build a PSBT splitting `utxo` into `count` padding outputs of `dummy_value` (600 sat if none)
+/// for [`build_inscription_snipe_psbt`], with the rest going to `change_addr`.
+///
+/// The fee covers the largest satisfaction of `descriptor` when given, otherwise `utxo` is taken
+/// to be a single key spend of its script type.
+#[uniffi::export(default(dummy_value = None, descriptor = None))]
+pub fn build_dummy_utxo_psbt(
+    utxo: LocalOutput,
+    count: u32,
+    dummy_addr: Arc<Address>,
+    change_addr: Arc<Address>,
+    fee_rate: Arc<FeeRate>,
+    dummy_value: Option<Arc<Amount>>,
+    descriptor: Option<Arc<Descriptor>>,
+) -> Result<Arc<Psbt>, SnipeError> {
+    let psbt = snipe::DummyUtxoPsbtBuilder {
+        utxo,
+        count,
+        dummy_value: dummy_value.map_or(snipe::DUMMY_UTXO, |value| value.0),
+        dummy_addr: dummy_addr.0.clone(),
+        change_addr: change_addr.0.clone(),
+        fee_rate: fee_rate.0,
+        descriptor: descriptor.map(|descriptor| descriptor.extended_descriptor.clone()),
+    }
+    .build()?;
+
+    Ok(Arc::new(Psbt::from(psbt)))
+}
+
+/// Unspent wallet outputs worth at most `max_value` (600 sat if none), smallest first.
+///
+/// Small outputs often carry inscriptions, so pass `tracker` to leave out everything it knows to
+/// hold inscriptions or runes.
+#[uniffi::export(default(max_value = None, tracker = None))]
+pub fn select_dummy_utxos(
+    wallet: Arc<Wallet>,
+    max_value: Option<Arc<Amount>>,
+    tracker: Option<Arc<OrdinalTracker>>,
+) -> Vec<LocalOutput> {
+    let max_value = max_value.map_or(snipe::DUMMY_UTXO, |value| value.0);
+    let non_cardinal = tracker
+        .map(|tracker| tracker.non_cardinal())
+        .unwrap_or_default();
+
+    let mut utxos: Vec<LocalOutput> = wallet
+        .list_unspent()
+        .into_iter()
+        .filter(|utxo| {
+            utxo.txout.value.0 <= max_value
+                && !non_cardinal.contains(&utxo.outpoint.clone().into())
+        })
+        .collect();
+    utxos.sort_by_key(|utxo| utxo.txout.value.0);
+    utxos
+}
+
+/// Validate inscription listings before passing them to [`build_inscription_snipe_psbt`].
+#[uniffi::export(default(esplora = None))]
+pub fn validate_inscription_snipe_pairs(
+    snipe_utxo_pairs: Vec<SnipeInscriptionPair>,
+    esplora: Option<Arc<EsploraClient>>,
+) -> Vec<SnipePairReport> {
+    snipe::validate_snipe_pairs(
+        snipe_utxo_pairs
+            .iter()
+            .map(|x| ((&x.txin).into(), (&x.prevout).into(), (&x.txout).into()))
+            .collect(),
+        esplora.as_deref(),
+    )
+}
+
 #[uniffi::export]
 pub fn build_inscription_snipe_psbt(
     cardinal_utxos: Vec<LocalOutput>,
@@ -142,6 +421,9 @@ pub fn build_inscription_snipe_psbt(
     split_rate: Arc<FeeRate>,
     inscription_recv_addr: Option<Arc<Address>>, // ordi addr if none
 ) -> Result<SnipePsbtPair, SnipeError> {
+    let dummy_value = dummy_utxos
+        .first()
+        .map_or(0, |utxo| utxo.txout.value.0.to_sat());
     let snipe_psbt = snipe::SnipeInscriptionPsbtBuilder {
         cardinal_utxos,
         snipe_utxo_pairs: snipe_utxo_pairs
@@ -159,21 +441,28 @@ pub fn build_inscription_snipe_psbt(
     let tx = snipe_psbt.clone().extract_tx_unchecked_fee_rate();
     let outpoint = tx.output.first().unwrap();
 
-    let split_psbt = snipe::SplitInscriptionPsbtBuilder {
-        ordi_addr_outpoint_with_amount: (
-            ordi_addr.0.clone(),
-            bitcoin::OutPoint {
-                txid: tx.compute_txid(),
-                vout: 0,
-            },
-            outpoint.value,
-        ),
-        inscription_offsets: snipe_utxo_pairs
-            .into_iter()
-            .map(|x| x.prevout.value.0)
-            .collect(),
-        recv_addr: inscription_recv_addr.unwrap_or(ordi_addr).0.clone(),
+    // the dummy sats come first, then each listed inscription at offset 0 of its prevout
+    let mut offset = dummy_value;
+    let inscription_offsets = snipe_utxo_pairs
+        .iter()
+        .map(|x| {
+            let inscription = offset;
+            offset += x.prevout.value.0.to_sat();
+            inscription
+        })
+        .collect();
+    let split_psbt = unbundle::UnbundlePsbtBuilder {
+        outpoint: bitcoin::OutPoint {
+            txid: tx.compute_txid(),
+            vout: 0,
+        },
+        prevout: outpoint.clone(),
+        inscription_offsets,
+        runes: vec![],
+        cardinal_utxos: vec![],
+        destination: inscription_recv_addr.unwrap_or(ordi_addr).0.clone(),
         change_addr: pay_addr.0.clone(),
+        postage: None,
         fee_rate: split_rate.0,
     }
     .build()?;
@@ -183,6 +472,128 @@ pub fn build_inscription_snipe_psbt(
         split: Arc::new(Psbt::from(split_psbt)),
     })
 }
+
+/// Build the seller half of a listing selling `output` for `price` paid to `payout_addr`, signed by
+/// `wallet` with `SIGHASH_SINGLE|ANYONECANPAY`.
+///
+/// The returned PSBT is finalized; its input and output are what a buyer splices into their own
+/// transaction.
+#[uniffi::export]
+pub fn build_listing_psbt(
+    wallet: Arc<Wallet>,
+    output: LocalOutput,
+    price: Arc<Amount>,
+    payout_addr: Arc<Address>,
+) -> Result<Arc<Psbt>, ListingError> {
+    if wallet.get_wallet().get_utxo(output.outpoint.clone().into()).is_none() {
+        return Err(ListingError::NotOwned);
+    }
+
+    let psbt = Arc::new(Psbt::from(
+        listing::ListingPsbtBuilder {
+            output,
+            price: price.0,
+            payout_addr: payout_addr.0.clone(),
+        }
+        .build()?,
+    ));
+
+    let finalized = wallet
+        .sign(
+            psbt.clone(),
+            Some(SignOptions {
+                trust_witness_utxo: true,
+                assume_height: None,
+                allow_all_sighashes: true,
+                try_finalize: true,
+                sign_with_tap_internal_key: true,
+                allow_grinding: true,
+            }),
+        )
+        .map_err(|e| ListingError::Signer {
+            error_message: e.to_string(),
+        })?;
+    if !finalized {
+        return Err(ListingError::NotSigned);
+    }
+
+    Ok(psbt)
+}
+
+/// Signed listing input together with the output it spends and the payout it is signed over.
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct Listing {
+    pub txin: TxIn,
+    pub prevout: TxOut,
+    pub payout: TxOut,
+}
+
+/// Verify a third-party listing before buying it.
+///
+/// Every input must be finalized with a valid `SIGHASH_SINGLE|ANYONECANPAY` signature over the
+/// output at the same index, and the payouts together must not exceed `max_price` when given.
+#[uniffi::export(default(max_price = None))]
+pub fn verify_listing_psbt(
+    listing: Arc<Psbt>,
+    max_price: Option<Arc<Amount>>,
+) -> Result<Vec<Listing>, ListingError> {
+    let psbt = listing.0.lock().unwrap();
+    if psbt.inputs.is_empty() {
+        return Err(ListingError::NoInputs);
+    }
+
+    let mut listings = Vec::new();
+    let mut price = bitcoin::Amount::ZERO;
+    for (index, (input, txin)) in psbt.inputs.iter().zip(&psbt.unsigned_tx.input).enumerate() {
+        let index = index as u32;
+        let prevout = input
+            .witness_utxo
+            .clone()
+            .or_else(|| {
+                input.non_witness_utxo.as_ref().and_then(|tx| {
+                    tx.output
+                        .get(txin.previous_output.vout as usize)
+                        .cloned()
+                })
+            })
+            .ok_or(ListingError::MissingPrevout { index })?;
+        let payout = psbt
+            .unsigned_tx
+            .output
+            .get(index as usize)
+            .ok_or(ListingError::MissingPayout { index })?;
+        if input.final_script_witness.is_none() && input.final_script_sig.is_none() {
+            return Err(ListingError::Unsigned { index });
+        }
+
+        let signed = bitcoin::TxIn {
+            script_sig: input.final_script_sig.clone().unwrap_or_default(),
+            witness: input.final_script_witness.clone().unwrap_or_default(),
+            ..txin.clone()
+        };
+        listing::verify_listing_input(&signed, &prevout, payout)?;
+
+        price = price
+            .checked_add(payout.value)
+            .ok_or(ListingError::PriceOverflow)?;
+        listings.push(Listing {
+            txin: (&signed).into(),
+            prevout: prevout.into(),
+            payout: payout.into(),
+        });
+    }
+
+    if let Some(max_price) = max_price
+        && price > max_price.0
+    {
+        return Err(ListingError::PriceTooHigh {
+            price: price.to_sat(),
+            max_price: max_price.0.to_sat(),
+        });
+    }
+
+    Ok(listings)
+}
 //
 //#[cfg(test)]
 //mod tests {
@@ -285,7 +696,7 @@ pub fn build_inscription_snipe_psbt(
 //                },
 //                txout,
 //                rune_id: Arc::new(runeid),
-//                amount: amount.to_string(),
+//                amount: Arc::new(RuneAmount::from(amount)),
 //            };
 //            snipe_pair.push(p);
 //        }
@@ -4,6 +4,10 @@ mod rune_id;
 mod rune;
 #[allow(unused)]
 mod varint;
+mod rune_amount;
+mod runestone;
 #[allow(unused)]
 pub use rune_id::*;
 pub use rune::*;
+pub use rune_amount::*;
+pub use runestone::*;
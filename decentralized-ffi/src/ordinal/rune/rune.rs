@@ -1,4 +1,5 @@
 use crate::bitcoin::Script;
+use crate::ordinal::rune::rune_amount::RuneAmount;
 use crate::ordinal::rune::rune_id::RuneId;
 use crate::ordinal::rune::varint;
 use bdk_wallet::bitcoin::opcodes;
@@ -65,7 +66,7 @@ impl Rune {
 #[derive(uniffi::Record, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Edict {
     pub id: Arc<RuneId>,
-    pub amount: u64,
+    pub amount: Arc<RuneAmount>,
     pub output: u32,
 }
 
@@ -188,7 +189,7 @@ pub fn extract_rune_from_script(script: Arc<Script>) -> Result<Rune, RuneParseEr
 
                 let edict = Edict {
                     id: Arc::new(next.clone()),
-                    amount: chunk[2] as u64,
+                    amount: Arc::new(RuneAmount(chunk[2])),
                     output: chunk[3]
                         .try_into()
                         .map_err(|err| RuneParseError::U128Tou32)?,
@@ -0,0 +1,174 @@
+use crate::ordinal::inscription::decimal::Decimal;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Rune balance in the rune's smallest unit.
+///
+/// Runes carry `u128` amounts, which do not fit the FFI integer types, so the value crosses the
+/// boundary as an object and is rendered as a decimal string.
+#[derive(uniffi::Object, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[uniffi::export(Debug, Display, Eq, Hash)]
+pub struct RuneAmount(pub(crate) u128);
+
+impl Display for RuneAmount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for RuneAmount {
+    type Err = RuneAmountError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<u128>()
+            .map(RuneAmount)
+            .map_err(|e| RuneAmountError::InvalidAmount {
+                error_message: e.to_string(),
+            })
+    }
+}
+
+impl From<u128> for RuneAmount {
+    fn from(value: u128) -> Self {
+        RuneAmount(value)
+    }
+}
+
+impl From<RuneAmount> for u128 {
+    fn from(value: RuneAmount) -> Self {
+        value.0
+    }
+}
+
+#[derive(thiserror::Error, uniffi::Error, Debug)]
+pub enum RuneAmountError {
+    #[error("invalid amount:{error_message}")]
+    InvalidAmount { error_message: String },
+    #[error("invalid decimal amount:{error_message}")]
+    InvalidDecimal { error_message: String },
+    #[error("divisibility {divisibility} out of range")]
+    DivisibilityOutOfRange { divisibility: u8 },
+}
+
+#[uniffi::export]
+impl RuneAmount {
+    /// Parse an amount in the rune's smallest unit, e.g. `"340282366920938463463374607431768211455"`.
+    #[uniffi::constructor]
+    pub fn from_string(amount: &str) -> Result<Self, RuneAmountError> {
+        amount.parse()
+    }
+
+    #[uniffi::constructor]
+    pub fn from_u64(amount: u64) -> Self {
+        RuneAmount(amount.into())
+    }
+
+    /// Parse a human readable amount such as `"1.05"` for a rune with `divisibility` decimals.
+    #[uniffi::constructor]
+    pub fn from_decimal(amount: &str, divisibility: u8) -> Result<Self, RuneAmountError> {
+        amount
+            .parse::<Decimal>()
+            .and_then(|decimal| decimal.to_amount(divisibility))
+            .map(RuneAmount)
+            .map_err(|e| RuneAmountError::InvalidDecimal {
+                error_message: e.to_string(),
+            })
+    }
+
+    /// Format the amount for a rune with `divisibility` decimals, trimming trailing zeros.
+    pub fn to_decimal(&self, divisibility: u8) -> Result<String, RuneAmountError> {
+        if divisibility == 0 {
+            return Ok(self.0.to_string());
+        }
+
+        let cutoff = 10u128
+            .checked_pow(divisibility.into())
+            .ok_or(RuneAmountError::DivisibilityOutOfRange { divisibility })?;
+
+        let whole = self.0 / cutoff;
+        let fractional = self.0 % cutoff;
+
+        if fractional == 0 {
+            return Ok(whole.to_string());
+        }
+
+        let fractional = format!("{fractional:0width$}", width = usize::from(divisibility));
+
+        Ok(format!("{whole}.{}", fractional.trim_end_matches('0')))
+    }
+
+    /// The amount as `u64`, or `None` if it does not fit.
+    pub fn to_u64(&self) -> Option<u64> {
+        self.0.try_into().ok()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_above_u64() {
+        let value = u128::from(u64::MAX) + 1;
+        let amount = RuneAmount::from_string(&value.to_string()).unwrap();
+
+        assert_eq!(amount.0, value);
+        assert_eq!(amount.to_string(), "18446744073709551616");
+        assert_eq!(amount.to_u64(), None);
+
+        let max = RuneAmount::from_string("340282366920938463463374607431768211455").unwrap();
+        assert_eq!(max.0, u128::MAX);
+
+        assert!(RuneAmount::from_string("340282366920938463463374607431768211456").is_err());
+        assert!(RuneAmount::from_string("-1").is_err());
+        assert_eq!(RuneAmount::from_u64(u64::MAX).to_u64(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn to_decimal() {
+        #[track_caller]
+        fn case(value: u128, divisibility: u8, expected: &str) {
+            assert_eq!(
+                RuneAmount(value).to_decimal(divisibility).unwrap(),
+                expected
+            );
+        }
+
+        case(0, 0, "0");
+        case(0, 2, "0");
+        case(1, 0, "1");
+        case(1, 2, "0.01");
+        case(150, 2, "1.5");
+        case(100, 2, "1");
+        case(u128::MAX, 0, "340282366920938463463374607431768211455");
+        case(u128::MAX, 18, "340282366920938463463.374607431768211455");
+        case(u128::MAX, 38, "3.40282366920938463463374607431768211455");
+
+        assert!(matches!(
+            RuneAmount(1).to_decimal(39),
+            Err(RuneAmountError::DivisibilityOutOfRange { divisibility: 39 })
+        ));
+    }
+
+    #[test]
+    fn from_decimal() {
+        assert_eq!(RuneAmount::from_decimal("1.5", 2).unwrap().0, 150);
+        assert_eq!(
+            RuneAmount::from_decimal("340282366920938463463.374607431768211455", 18)
+                .unwrap()
+                .0,
+            u128::MAX
+        );
+        assert_eq!(
+            RuneAmount::from_decimal("18446744073709551616", 0)
+                .unwrap()
+                .to_string(),
+            "18446744073709551616"
+        );
+        assert!(RuneAmount::from_decimal("1.555", 2).is_err());
+        assert!(RuneAmount::from_decimal("340282366920938463464", 18).is_err());
+    }
+}
@@ -99,3 +99,21 @@ impl RuneId {
         })
     }
 }
+
+impl From<ordinals::RuneId> for RuneId {
+    fn from(id: ordinals::RuneId) -> Self {
+        RuneId {
+            block: id.block,
+            tx: id.tx,
+        }
+    }
+}
+
+impl From<&RuneId> for ordinals::RuneId {
+    fn from(id: &RuneId) -> Self {
+        ordinals::RuneId {
+            block: id.block,
+            tx: id.tx,
+        }
+    }
+}
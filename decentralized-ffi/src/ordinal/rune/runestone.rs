@@ -0,0 +1,541 @@
+use crate::bitcoin::{Script, Transaction};
+use crate::ordinal::rune::rune::Edict;
+use crate::ordinal::rune::rune_amount::RuneAmount;
+use crate::ordinal::rune::rune_id::RuneId;
+use ordinals::SpacedRune;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(uniffi::Enum, Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::large_enum_variant)]
+pub enum RuneArtifact {
+    Runestone { runestone: Runestone },
+    Cenotaph { cenotaph: Cenotaph },
+}
+
+#[derive(uniffi::Record, Debug, Clone, PartialEq, Eq)]
+pub struct Runestone {
+    pub edicts: Vec<Edict>,
+    pub etching: Option<Etching>,
+    pub mint: Option<Arc<RuneId>>,
+    pub pointer: Option<u32>,
+}
+
+#[derive(uniffi::Record, Debug, Clone, PartialEq, Eq)]
+pub struct Etching {
+    pub divisibility: Option<u8>,
+    pub premine: Option<Arc<RuneAmount>>,
+    /// rune name without spacers, e.g. `UNCOMMONGOODS`
+    pub rune: Option<String>,
+    /// rune name with spacers applied, e.g. `UNCOMMON•GOODS`; ignored when enciphering
+    pub spaced_rune: Option<String>,
+    pub spacers: Option<u32>,
+    pub symbol: Option<String>,
+    pub terms: Option<Terms>,
+    pub turbo: bool,
+}
+
+#[derive(uniffi::Record, Debug, Clone, PartialEq, Eq)]
+pub struct Terms {
+    /// amount per mint
+    pub amount: Option<Arc<RuneAmount>>,
+    /// maximum number of mints
+    pub cap: Option<Arc<RuneAmount>>,
+    pub height_start: Option<u64>,
+    pub height_end: Option<u64>,
+    pub offset_start: Option<u64>,
+    pub offset_end: Option<u64>,
+}
+
+#[derive(uniffi::Record, Debug, Clone, PartialEq, Eq)]
+pub struct Cenotaph {
+    /// rune name that is burned by this cenotaph, if it was etching one
+    pub etching: Option<String>,
+    pub flaw: Option<RuneFlaw>,
+    pub mint: Option<Arc<RuneId>>,
+}
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuneFlaw {
+    EdictOutput,
+    EdictRuneId,
+    InvalidScript,
+    Opcode,
+    SupplyOverflow,
+    TrailingIntegers,
+    TruncatedField,
+    UnrecognizedEvenTag,
+    UnrecognizedFlag,
+    Varint,
+}
+
+impl From<ordinals::Flaw> for RuneFlaw {
+    fn from(flaw: ordinals::Flaw) -> Self {
+        match flaw {
+            ordinals::Flaw::EdictOutput => RuneFlaw::EdictOutput,
+            ordinals::Flaw::EdictRuneId => RuneFlaw::EdictRuneId,
+            ordinals::Flaw::InvalidScript => RuneFlaw::InvalidScript,
+            ordinals::Flaw::Opcode => RuneFlaw::Opcode,
+            ordinals::Flaw::SupplyOverflow => RuneFlaw::SupplyOverflow,
+            ordinals::Flaw::TrailingIntegers => RuneFlaw::TrailingIntegers,
+            ordinals::Flaw::TruncatedField => RuneFlaw::TruncatedField,
+            ordinals::Flaw::UnrecognizedEvenTag => RuneFlaw::UnrecognizedEvenTag,
+            ordinals::Flaw::UnrecognizedFlag => RuneFlaw::UnrecognizedFlag,
+            ordinals::Flaw::Varint => RuneFlaw::Varint,
+        }
+    }
+}
+
+impl From<ordinals::Edict> for Edict {
+    fn from(edict: ordinals::Edict) -> Self {
+        Edict {
+            id: Arc::new(edict.id.into()),
+            amount: Arc::new(RuneAmount(edict.amount)),
+            output: edict.output,
+        }
+    }
+}
+
+impl From<ordinals::Terms> for Terms {
+    fn from(terms: ordinals::Terms) -> Self {
+        Terms {
+            amount: terms.amount.map(|amount| Arc::new(RuneAmount(amount))),
+            cap: terms.cap.map(|cap| Arc::new(RuneAmount(cap))),
+            height_start: terms.height.0,
+            height_end: terms.height.1,
+            offset_start: terms.offset.0,
+            offset_end: terms.offset.1,
+        }
+    }
+}
+
+impl From<ordinals::Etching> for Etching {
+    fn from(etching: ordinals::Etching) -> Self {
+        Etching {
+            divisibility: etching.divisibility,
+            premine: etching.premine.map(|premine| Arc::new(RuneAmount(premine))),
+            rune: etching.rune.map(|rune| rune.to_string()),
+            spaced_rune: etching
+                .rune
+                .map(|rune| SpacedRune::new(rune, etching.spacers.unwrap_or_default()).to_string()),
+            spacers: etching.spacers,
+            symbol: etching.symbol.map(String::from),
+            terms: etching.terms.map(Terms::from),
+            turbo: etching.turbo,
+        }
+    }
+}
+
+impl From<ordinals::Runestone> for Runestone {
+    fn from(runestone: ordinals::Runestone) -> Self {
+        Runestone {
+            edicts: runestone.edicts.into_iter().map(Edict::from).collect(),
+            etching: runestone.etching.map(Etching::from),
+            mint: runestone.mint.map(|id| Arc::new(id.into())),
+            pointer: runestone.pointer,
+        }
+    }
+}
+
+impl From<ordinals::Cenotaph> for Cenotaph {
+    fn from(cenotaph: ordinals::Cenotaph) -> Self {
+        Cenotaph {
+            etching: cenotaph.etching.map(|rune| rune.to_string()),
+            flaw: cenotaph.flaw.map(RuneFlaw::from),
+            mint: cenotaph.mint.map(|id| Arc::new(id.into())),
+        }
+    }
+}
+
+impl From<ordinals::Artifact> for RuneArtifact {
+    fn from(artifact: ordinals::Artifact) -> Self {
+        match artifact {
+            ordinals::Artifact::Runestone(runestone) => RuneArtifact::Runestone {
+                runestone: runestone.into(),
+            },
+            ordinals::Artifact::Cenotaph(cenotaph) => RuneArtifact::Cenotaph {
+                cenotaph: cenotaph.into(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum RunestoneError {
+    #[error("invalid rune name: {error_message}")]
+    InvalidRune { error_message: String },
+
+    #[error("symbol must be a single character: {symbol}")]
+    InvalidSymbol { symbol: String },
+
+    #[error("divisibility {divisibility} exceeds {max}")]
+    InvalidDivisibility { divisibility: u8, max: u8 },
+
+    #[error("spacers {spacers} exceed rune length")]
+    InvalidSpacers { spacers: u32 },
+
+    #[error("supply overflows u128")]
+    SupplyOverflow,
+
+    #[error("runestone payload exceeds {max} bytes")]
+    PayloadTooLarge { max: u64 },
+}
+
+impl From<&Edict> for ordinals::Edict {
+    fn from(edict: &Edict) -> Self {
+        ordinals::Edict {
+            id: edict.id.as_ref().into(),
+            amount: edict.amount.0,
+            output: edict.output,
+        }
+    }
+}
+
+impl From<&Terms> for ordinals::Terms {
+    fn from(terms: &Terms) -> Self {
+        ordinals::Terms {
+            amount: terms.amount.as_ref().map(|amount| amount.0),
+            cap: terms.cap.as_ref().map(|cap| cap.0),
+            height: (terms.height_start, terms.height_end),
+            offset: (terms.offset_start, terms.offset_end),
+        }
+    }
+}
+
+impl TryFrom<&Etching> for ordinals::Etching {
+    type Error = RunestoneError;
+
+    fn try_from(etching: &Etching) -> Result<Self, Self::Error> {
+        let spaced_rune = etching
+            .rune
+            .as_deref()
+            .map(SpacedRune::from_str)
+            .transpose()
+            .map_err(|e| RunestoneError::InvalidRune {
+                error_message: e.to_string(),
+            })?;
+
+        let spacers = etching
+            .spacers
+            .or(spaced_rune.and_then(|spaced| (spaced.spacers != 0).then_some(spaced.spacers)));
+
+        if let Some(spacers) = spacers {
+            let len = spaced_rune
+                .map(|spaced| spaced.rune.to_string().len())
+                .unwrap_or_default();
+            if spacers > ordinals::Etching::MAX_SPACERS || spacers >> len.saturating_sub(1) != 0 {
+                return Err(RunestoneError::InvalidSpacers { spacers });
+            }
+        }
+
+        if let Some(divisibility) = etching.divisibility
+            && divisibility > ordinals::Etching::MAX_DIVISIBILITY
+        {
+            return Err(RunestoneError::InvalidDivisibility {
+                divisibility,
+                max: ordinals::Etching::MAX_DIVISIBILITY,
+            });
+        }
+
+        let symbol = match etching.symbol.as_deref() {
+            None => None,
+            Some(symbol) => {
+                let mut chars = symbol.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => {
+                        return Err(RunestoneError::InvalidSymbol {
+                            symbol: symbol.to_string(),
+                        });
+                    }
+                }
+            }
+        };
+
+        let etching = ordinals::Etching {
+            divisibility: etching.divisibility,
+            premine: etching.premine.as_ref().map(|premine| premine.0),
+            rune: spaced_rune.map(|spaced| spaced.rune),
+            spacers,
+            symbol,
+            terms: etching.terms.as_ref().map(ordinals::Terms::from),
+            turbo: etching.turbo,
+        };
+
+        if etching.supply().is_none() {
+            return Err(RunestoneError::SupplyOverflow);
+        }
+
+        Ok(etching)
+    }
+}
+
+impl TryFrom<&Runestone> for ordinals::Runestone {
+    type Error = RunestoneError;
+
+    fn try_from(runestone: &Runestone) -> Result<Self, Self::Error> {
+        Ok(ordinals::Runestone {
+            edicts: runestone.edicts.iter().map(ordinals::Edict::from).collect(),
+            etching: runestone
+                .etching
+                .as_ref()
+                .map(ordinals::Etching::try_from)
+                .transpose()?,
+            mint: runestone.mint.as_deref().map(ordinals::RuneId::from),
+            pointer: runestone.pointer,
+        })
+    }
+}
+
+/// Largest runestone script `ord` is willing to relay, see `ord wallet send`.
+pub(crate) const MAX_RUNESTONE_SCRIPT_SIZE: usize = 82;
+
+impl Runestone {
+    pub(crate) fn encipher(&self) -> Result<bdk_wallet::bitcoin::ScriptBuf, RunestoneError> {
+        let script = ordinals::Runestone::try_from(self)?.encipher();
+
+        if script.len() > MAX_RUNESTONE_SCRIPT_SIZE {
+            return Err(RunestoneError::PayloadTooLarge {
+                max: MAX_RUNESTONE_SCRIPT_SIZE as u64,
+            });
+        }
+
+        Ok(script)
+    }
+}
+
+/// Encode `runestone` into an `OP_RETURN OP_13` script ready to be used as an output.
+#[uniffi::export]
+pub fn encipher_runestone(runestone: Runestone) -> Result<Arc<Script>, RunestoneError> {
+    Ok(Arc::new(Script(runestone.encipher()?)))
+}
+
+/// Decode the runestone of `tx`, if any.
+///
+/// Returns `None` when the transaction has no `OP_RETURN OP_13` output, a `Runestone` when it is
+/// valid and a `Cenotaph` carrying the flaw when it is malformed.
+#[uniffi::export]
+pub fn decipher_runestone(tx: &Transaction) -> Option<RuneArtifact> {
+    ordinals::Runestone::decipher(&tx.0).map(RuneArtifact::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk_wallet::bitcoin::absolute::LockTime;
+    use bdk_wallet::bitcoin::transaction::Version;
+    use bdk_wallet::bitcoin::{
+        Amount, ScriptBuf, Transaction as BdkTransaction, TxOut, opcodes, script,
+    };
+
+    fn tx_with_script(script_pubkey: ScriptBuf, outputs: usize) -> Transaction {
+        let mut output = vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey,
+        }];
+        output.extend((0..outputs).map(|_| TxOut {
+            value: Amount::from_sat(546),
+            script_pubkey: ScriptBuf::new(),
+        }));
+
+        Transaction(BdkTransaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output,
+        })
+    }
+
+    #[test]
+    fn decipher_etching() {
+        let runestone = ordinals::Runestone {
+            etching: Some(ordinals::Etching {
+                divisibility: Some(2),
+                premine: Some(u128::MAX / 2),
+                rune: Some("UNCOMMONGOODS".parse().unwrap()),
+                spacers: Some(0b10000000),
+                symbol: Some('⧉'),
+                terms: Some(ordinals::Terms {
+                    amount: Some(100),
+                    cap: Some(1_000),
+                    height: (Some(840_000), None),
+                    offset: (None, Some(4_320)),
+                }),
+                turbo: true,
+            }),
+            pointer: Some(1),
+            ..Default::default()
+        };
+
+        let artifact = decipher_runestone(&tx_with_script(runestone.encipher(), 1)).unwrap();
+
+        assert_eq!(
+            artifact,
+            RuneArtifact::Runestone {
+                runestone: Runestone {
+                    edicts: vec![],
+                    etching: Some(Etching {
+                        divisibility: Some(2),
+                        premine: Some(Arc::new(RuneAmount(u128::MAX / 2))),
+                        rune: Some("UNCOMMONGOODS".into()),
+                        spaced_rune: Some("UNCOMMON•GOODS".into()),
+                        spacers: Some(0b10000000),
+                        symbol: Some("⧉".into()),
+                        terms: Some(Terms {
+                            amount: Some(Arc::new(RuneAmount(100))),
+                            cap: Some(Arc::new(RuneAmount(1_000))),
+                            height_start: Some(840_000),
+                            height_end: None,
+                            offset_start: None,
+                            offset_end: Some(4_320),
+                        }),
+                        turbo: true,
+                    }),
+                    mint: None,
+                    pointer: Some(1),
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn decipher_mint_and_edicts() {
+        let runestone = ordinals::Runestone {
+            edicts: vec![ordinals::Edict {
+                id: ordinals::RuneId {
+                    block: 840_000,
+                    tx: 3,
+                },
+                amount: u128::from(u64::MAX) + 500,
+                output: 1,
+            }],
+            mint: Some(ordinals::RuneId { block: 1, tx: 0 }),
+            ..Default::default()
+        };
+
+        let Some(RuneArtifact::Runestone { runestone }) =
+            decipher_runestone(&tx_with_script(runestone.encipher(), 1))
+        else {
+            panic!("expected runestone");
+        };
+
+        assert_eq!(runestone.mint, Some(Arc::new(RuneId { block: 1, tx: 0 })));
+        assert_eq!(
+            runestone.edicts,
+            vec![Edict {
+                id: Arc::new(RuneId {
+                    block: 840_000,
+                    tx: 3
+                }),
+                amount: Arc::new(RuneAmount(u128::from(u64::MAX) + 500)),
+                output: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn encipher_roundtrip() {
+        let runestone = Runestone {
+            edicts: vec![Edict {
+                id: Arc::new(RuneId { block: 2, tx: 1 }),
+                amount: Arc::new(RuneAmount(u128::from(u64::MAX) * 3)),
+                output: 0,
+            }],
+            etching: Some(Etching {
+                divisibility: Some(8),
+                premine: Some(Arc::new(RuneAmount(21_000_000))),
+                rune: Some("UNCOMMON•GOODS".into()),
+                spaced_rune: None,
+                spacers: None,
+                symbol: Some("$".into()),
+                terms: Some(Terms {
+                    amount: Some(Arc::new(RuneAmount(1))),
+                    cap: None,
+                    height_start: None,
+                    height_end: Some(900_000),
+                    offset_start: None,
+                    offset_end: None,
+                }),
+                turbo: false,
+            }),
+            mint: None,
+            pointer: Some(0),
+        };
+
+        let script = encipher_runestone(runestone.clone()).unwrap();
+        let Some(RuneArtifact::Runestone {
+            runestone: deciphered,
+        }) = decipher_runestone(&tx_with_script(script.0.clone(), 1))
+        else {
+            panic!("expected runestone");
+        };
+
+        assert_eq!(deciphered.edicts, runestone.edicts);
+        assert_eq!(deciphered.pointer, Some(0));
+        let etching = deciphered.etching.unwrap();
+        assert_eq!(etching.rune.as_deref(), Some("UNCOMMONGOODS"));
+        assert_eq!(etching.spaced_rune.as_deref(), Some("UNCOMMON•GOODS"));
+        assert_eq!(etching.spacers, Some(0b10000000));
+        assert_eq!(etching.symbol.as_deref(), Some("$"));
+    }
+
+    #[test]
+    fn encipher_rejects_invalid_etching() {
+        let etching = |rune: &str, symbol: &str, divisibility: u8| Runestone {
+            edicts: vec![],
+            etching: Some(Etching {
+                divisibility: Some(divisibility),
+                premine: None,
+                rune: Some(rune.into()),
+                spaced_rune: None,
+                spacers: None,
+                symbol: Some(symbol.into()),
+                terms: None,
+                turbo: false,
+            }),
+            mint: None,
+            pointer: None,
+        };
+
+        assert!(matches!(
+            encipher_runestone(etching("abc", "$", 0)),
+            Err(RunestoneError::InvalidRune { .. })
+        ));
+        assert!(matches!(
+            encipher_runestone(etching("ABC", "$$", 0)),
+            Err(RunestoneError::InvalidSymbol { .. })
+        ));
+        assert!(matches!(
+            encipher_runestone(etching("ABC", "$", 39)),
+            Err(RunestoneError::InvalidDivisibility { .. })
+        ));
+    }
+
+    #[test]
+    fn decipher_cenotaph() {
+        let script = script::Builder::new()
+            .push_opcode(opcodes::all::OP_RETURN)
+            .push_opcode(opcodes::all::OP_PUSHNUM_13)
+            .push_opcode(opcodes::all::OP_VERIFY)
+            .into_script();
+
+        assert_eq!(
+            decipher_runestone(&tx_with_script(script, 0)),
+            Some(RuneArtifact::Cenotaph {
+                cenotaph: Cenotaph {
+                    etching: None,
+                    flaw: Some(RuneFlaw::Opcode),
+                    mint: None,
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn decipher_without_runestone() {
+        assert_eq!(
+            decipher_runestone(&tx_with_script(ScriptBuf::new(), 0)),
+            None
+        );
+    }
+}
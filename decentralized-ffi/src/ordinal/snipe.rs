@@ -1,20 +1,26 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::num::ParseIntError;
-use bdk_wallet::bitcoin::{Address, Amount, FeeRate, OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, TxOut};
+use std::sync::Arc;
+use bdk_wallet::bitcoin::{Address, Amount, FeeRate, OutPoint, Psbt, Sequence, Transaction, TxIn, TxOut};
 use bdk_wallet::bitcoin::absolute::LockTime;
 use bdk_wallet::bitcoin::policy::get_virtual_tx_size;
 use bdk_wallet::bitcoin::psbt::Input;
 use bdk_wallet::bitcoin::transaction::Version;
-use ordinals::{Edict, RuneId, Runestone};
+use bdk_wallet::descriptor::ExtendedDescriptor;
+use bdk_wallet::miniscript;
 use crate::error::EsploraError;
+use crate::esplora::EsploraClient;
+use crate::ordinal::declared_sighash;
 use crate::ordinal::dummy_transaction::DummyTransaction;
+use crate::ordinal::listing::{verify_listing_input, ListingError};
+use crate::ordinal::unbundle::UnbundleError;
 use crate::types::LocalOutput;
 //
 // pub(crate) const ADDITIONAL_INPUT_VBYTES: usize = 58;
 // pub(crate) const ADDITIONAL_OUTPUT_VBYTES: usize = 43;
 // pub(crate) const SCHNORR_SIGNATURE_SIZE: usize = 64;
 
-// pub(crate) const DUMMY_UTXO: Amount = Amount::from_sat(600);
+pub(crate) const DUMMY_UTXO: Amount = Amount::from_sat(600);
 
 pub(crate) const APPEND_NETWORK_FEE_SAT: Amount = Amount::from_sat(666);
 
@@ -32,6 +38,18 @@ pub(crate) enum SnipeError {
 
     #[error("missing dummy utxo")]
     MissingDummyUtxo,
+
+    #[error("no dummy outputs requested")]
+    NoDummyOutputs,
+
+    #[error("dummy value {value} sat is below the dust limit {dust} sat")]
+    DustDummy { value: u64, dust: u64 },
+
+    #[error("split error: {error_message}")]
+    Split { error_message: String },
+
+    #[error("descriptor error: {error_message}")]
+    Descriptor { error_message: String },
 }
 
 impl From<ParseIntError> for SnipeError {
@@ -40,12 +58,139 @@ impl From<ParseIntError> for SnipeError {
     }
 }
 
+impl From<UnbundleError> for SnipeError {
+    fn from(e: UnbundleError) -> Self {
+        Self::Split {
+            error_message: e.to_string(),
+        }
+    }
+}
+
+impl From<miniscript::Error> for SnipeError {
+    fn from(e: miniscript::Error) -> Self {
+        Self::Descriptor {
+            error_message: e.to_string(),
+        }
+    }
+}
+
 impl From<EsploraError> for SnipeError {
     fn from(_: EsploraError) -> Self {
         Self::ApiError
     }
 }
 
+/// Outcome of validating one listing pair before sniping it.
+#[derive(uniffi::Enum, Debug, Clone, PartialEq, Eq)]
+pub enum SnipePairStatus {
+    Valid,
+    /// The outpoint is listed by an earlier pair as well.
+    Duplicate,
+    /// The input carries neither a witness nor a scriptSig.
+    Unsigned,
+    UnsupportedScript {
+        script: String,
+    },
+    WrongSighash {
+        sighash: String,
+    },
+    InvalidSignature {
+        error_message: String,
+    },
+    InvalidPayout {
+        error_message: String,
+    },
+    /// The supplied prevout differs from the output the input spends on chain.
+    PrevoutMismatch,
+    Spent {
+        txid: Option<Arc<crate::bitcoin::Txid>>,
+    },
+    Lookup {
+        error_message: String,
+    },
+}
+
+impl From<ListingError> for SnipePairStatus {
+    fn from(e: ListingError) -> Self {
+        match e {
+            ListingError::UnsupportedScript { script } => Self::UnsupportedScript { script },
+            ListingError::Sighash { sighash } => Self::WrongSighash { sighash },
+            ListingError::DustPayout { .. } => Self::InvalidPayout {
+                error_message: e.to_string(),
+            },
+            e => Self::InvalidSignature {
+                error_message: e.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct SnipePairReport {
+    pub outpoint: crate::bitcoin::OutPoint,
+    /// Sighash type declared by the input's signature.
+    pub sighash: Option<String>,
+    pub status: SnipePairStatus,
+}
+
+/// Check every (listing input, prevout, payout) pair without building anything.
+///
+/// When `esplora` is given the prevouts are compared against the chain and spent listings are
+/// flagged.
+pub(crate) fn validate_snipe_pairs(
+    snipe_utxo_pairs: Vec<(TxIn, TxOut, TxOut)>,
+    esplora: Option<&EsploraClient>,
+) -> Vec<SnipePairReport> {
+    let mut seen = HashSet::new();
+    snipe_utxo_pairs
+        .into_iter()
+        .map(|(txin, prevout, txout)| {
+            let outpoint = txin.previous_output;
+            let status = if !seen.insert(outpoint) {
+                SnipePairStatus::Duplicate
+            } else if txin.witness.is_empty() && txin.script_sig.is_empty() {
+                SnipePairStatus::Unsigned
+            } else if let Err(e) = verify_listing_input(&txin, &prevout, &txout) {
+                e.into()
+            } else if let Some(esplora) = esplora {
+                chain_status(esplora, outpoint, &prevout)
+            } else {
+                SnipePairStatus::Valid
+            };
+            SnipePairReport {
+                outpoint: outpoint.into(),
+                sighash: txin
+                    .witness
+                    .nth(0)
+                    .and_then(declared_sighash)
+                    .map(|sighash| sighash.to_string()),
+                status,
+            }
+        })
+        .collect()
+}
+
+fn chain_status(esplora: &EsploraClient, outpoint: OutPoint, prevout: &TxOut) -> SnipePairStatus {
+    let lookup = |e: EsploraError| SnipePairStatus::Lookup {
+        error_message: e.to_string(),
+    };
+    let txid = Arc::new(crate::bitcoin::Txid(outpoint.txid));
+
+    let tx = match esplora.get_tx(txid.clone()) {
+        Ok(tx) => tx,
+        Err(e) => return lookup(e),
+    };
+    if tx.0.output.get(outpoint.vout as usize) != Some(prevout) {
+        return SnipePairStatus::PrevoutMismatch;
+    }
+
+    match esplora.get_output_status(txid, outpoint.vout.into()) {
+        Ok(status) if status.spent => SnipePairStatus::Spent { txid: status.txid },
+        Ok(_) => SnipePairStatus::Valid,
+        Err(e) => lookup(e),
+    }
+}
+
 pub(crate) struct SnipeRunePsbtBuilder {
     pub(crate) cardinal_utxos: Vec<LocalOutput>,
     pub(crate) snipe_utxo_pairs: Vec<(TxIn, TxOut, TxOut)>, // ordi input and prevout , ordi output
@@ -228,109 +373,6 @@ fn build_snipe_rune_psbt(
 }
 
 
-pub(crate) struct SplitRunePsbtBuilder {
-    pub(crate) ordi_addr_outpoint_with_amount: (Address, OutPoint, Amount),
-    pub(crate) runes: HashMap<RuneId, u128>, // all rune
-    pub(crate) recv_addr: Address, // rune recv address
-    pub(crate) change_addr: Address, // change address
-    pub(crate) fee_rate: FeeRate,
-}
-
-impl SplitRunePsbtBuilder {
-    pub(crate) fn build(self) -> Result<Psbt, SnipeError> {
-        build_split_rune_psbt(
-            self.ordi_addr_outpoint_with_amount, self.runes, self.recv_addr, self.change_addr, self.fee_rate)
-    }
-}
-
-
-fn build_split_rune_psbt(
-    (ordi_addr, outpoint, amount): (Address, OutPoint, Amount), // all rune
-    runes: HashMap<RuneId, u128>, // all rune
-    recv_addr: Address, // rune recv address
-    change_addr: Address, // change address
-    fee_rate: FeeRate,
-) -> Result<Psbt, SnipeError> {
-    let mut unsigned_tx = Transaction {
-        version: Version::TWO,
-        lock_time: LockTime::ZERO,
-        input: vec![],
-        output: vec![],
-    };
-    let mut dummy_tx = DummyTransaction::new();
-    let mut psbt_inputs = Vec::new();
-    // input rune
-    unsigned_tx.input.push(TxIn {
-        previous_output: outpoint,
-        script_sig: Default::default(),
-        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-        witness: Default::default(),
-    });
-
-    psbt_inputs.push(Input {
-        witness_utxo: Some(TxOut {
-            value: amount,
-            script_pubkey: ordi_addr.script_pubkey(),
-        }),
-        ..Default::default()
-    });
-    dummy_tx.append_input(ordi_addr.script_pubkey(), None, None);
-
-    // build edict
-    let mut rs = Runestone {
-        edicts: vec![],
-        etching: None,
-        mint: None,
-        pointer: None,
-    };
-
-    let runes_len = runes.len() as u64;
-    for (index, rune) in runes.into_iter().enumerate() {
-        if runes_len > 1 {
-            rs.edicts.push(Edict {
-                id: rune.0,
-                amount: rune.1,
-                output: index as u32,
-            });
-        }
-
-        // rune index
-        unsigned_tx.output.push(TxOut {
-            value: recv_addr.script_pubkey().minimal_non_dust(),
-            script_pubkey: recv_addr.script_pubkey(),
-        });
-        dummy_tx.append_output(recv_addr.script_pubkey());
-    }
-
-    if runes_len > 1 {
-        unsigned_tx.output.push(TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::from_bytes(rs.encipher().into_bytes()) });
-        dummy_tx.append_output(ScriptBuf::from_bytes(rs.encipher().into_bytes()));
-    }
-
-    // change
-    unsigned_tx.output.push(TxOut {
-        value: Amount::ZERO,
-        script_pubkey: change_addr.script_pubkey(),
-    });
-    dummy_tx.append_output(change_addr.script_pubkey());
-
-    let network_fee = fee_rate.fee_vb(dummy_tx.vsize() as u64).unwrap();
-    unsigned_tx.output.last_mut().unwrap().value = amount - network_fee - recv_addr.script_pubkey().minimal_non_dust() * runes_len;
-
-    let o_len = unsigned_tx.output.len();
-    let psbt = Psbt {
-        unsigned_tx,
-        version: 0,
-        xpub: Default::default(),
-        proprietary: Default::default(),
-        unknown: Default::default(),
-        inputs: psbt_inputs,
-        outputs: vec![Default::default(); o_len],
-    };
-    Ok(psbt)
-}
-
-
 pub struct SnipeInscriptionPsbtBuilder {
     pub cardinal_utxos: Vec<LocalOutput>,
     pub dummy_utxos: Vec<LocalOutput>,
@@ -524,96 +566,291 @@ fn build_snipe_inscription_psbt(
 }
 
 
-pub struct SplitInscriptionPsbtBuilder {
-    pub(crate) ordi_addr_outpoint_with_amount: (Address, OutPoint, Amount),
-    pub(crate) inscription_offsets: Vec<Amount>,
-    pub(crate) recv_addr: Address, // rune recv address
-    pub(crate) change_addr: Address, // change address
+pub(crate) struct DummyUtxoPsbtBuilder {
+    pub(crate) utxo: LocalOutput,
+    pub(crate) count: u32,
+    pub(crate) dummy_value: Amount,
+    pub(crate) dummy_addr: Address,
+    pub(crate) change_addr: Address,
     pub(crate) fee_rate: FeeRate,
+    /// descriptor of `utxo`, its largest satisfaction sizes the fee
+    pub(crate) descriptor: Option<ExtendedDescriptor>,
 }
 
-impl SplitInscriptionPsbtBuilder {
+impl DummyUtxoPsbtBuilder {
+    /// Split `utxo` into `count` outputs of `dummy_value`, the rest goes to `change_addr` unless it
+    /// is dust.
     pub(crate) fn build(self) -> Result<Psbt, SnipeError> {
-        build_split_inscription_psbt(
-            self.ordi_addr_outpoint_with_amount,
-            self.inscription_offsets,
-            self.recv_addr,
-            self.change_addr,
-            self.fee_rate,
-        )
+        if self.count == 0 {
+            return Err(SnipeError::NoDummyOutputs);
+        }
+        let dummy_script = self.dummy_addr.script_pubkey();
+        let dust = dummy_script.minimal_non_dust();
+        if self.dummy_value < dust {
+            return Err(SnipeError::DustDummy {
+                value: self.dummy_value.to_sat(),
+                dust: dust.to_sat(),
+            });
+        }
+
+        let prevout: TxOut = (&self.utxo.txout).into();
+        let available = self
+            .dummy_value
+            .checked_mul(u64::from(self.count))
+            .and_then(|dummies| prevout.value.checked_sub(dummies))
+            .ok_or(SnipeError::UtxoNotEnough)?;
+
+        let mut dummy_tx = DummyTransaction::new();
+        match &self.descriptor {
+            Some(descriptor) => dummy_tx.append_descriptor_input(descriptor)?,
+            None => dummy_tx.append_input(prevout.script_pubkey.clone(), None, None),
+        }
+        let mut outputs = Vec::new();
+        for _ in 0..self.count {
+            outputs.push(TxOut {
+                value: self.dummy_value,
+                script_pubkey: dummy_script.clone(),
+            });
+            dummy_tx.append_output(dummy_script.clone());
+        }
+        let fee = self
+            .fee_rate
+            .fee_vb(dummy_tx.vsize() as u64)
+            .ok_or(SnipeError::UtxoNotEnough)?;
+        available.checked_sub(fee).ok_or(SnipeError::UtxoNotEnough)?;
+
+        // 找零小于粉尘值时并入手续费
+        let change_script = self.change_addr.script_pubkey();
+        dummy_tx.append_output(change_script.clone());
+        let fee_with_change = self
+            .fee_rate
+            .fee_vb(dummy_tx.vsize() as u64)
+            .ok_or(SnipeError::UtxoNotEnough)?;
+        if let Some(change) = available.checked_sub(fee_with_change)
+            && change >= change_script.minimal_non_dust()
+        {
+            outputs.push(TxOut {
+                value: change,
+                script_pubkey: change_script,
+            });
+        }
+
+        let o_len = outputs.len();
+        Ok(Psbt {
+            unsigned_tx: Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: vec![TxIn {
+                    previous_output: self.utxo.outpoint.into(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    ..Default::default()
+                }],
+                output: outputs,
+            },
+            version: 0,
+            xpub: Default::default(),
+            proprietary: Default::default(),
+            unknown: Default::default(),
+            inputs: vec![Input {
+                witness_utxo: Some(prevout),
+                ..Default::default()
+            }],
+            outputs: vec![Default::default(); o_len],
+        })
     }
 }
 
-fn build_split_inscription_psbt(
-    (ordi_addr, outpoint, amount): (Address, OutPoint, Amount), // all inscription
-    inscription_offsets: Vec<Amount>,
-    recv_addr: Address, // rune recv address
-    change_addr: Address, // change address
-    fee_rate: FeeRate,
-) -> Result<Psbt, SnipeError> {
-    let mut unsigned_tx = Transaction {
-        version: Version::TWO,
-        lock_time: LockTime::ZERO,
-        input: vec![],
-        output: vec![],
-    };
-    let mut dummy_tx = DummyTransaction::new();
-    let mut psbt_inputs = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{address, funded_wallet, local_output, multisig_descriptor};
+    use crate::ordinal::tracker::{InscriptionOffset, OrdinalTracker};
+    use crate::ordinal::{build_listing_psbt, select_dummy_utxos};
+    use bdk_wallet::bitcoin;
+    use bdk_wallet::bitcoin::hashes::Hash;
+
+    fn listing_pair() -> (TxIn, TxOut, TxOut) {
+        let wallet = funded_wallet(&[10_000]);
+        let output = wallet.list_unspent().remove(0);
+        let listing = build_listing_psbt(
+            wallet,
+            output,
+            Arc::new(crate::bitcoin::Amount(Amount::from_sat(50_000))),
+            Arc::new(address().into()),
+        )
+        .unwrap();
+        let psbt = listing.0.lock().unwrap();
+        let tx = psbt.clone().extract_tx_unchecked_fee_rate();
+        (
+            tx.input[0].clone(),
+            psbt.inputs[0].witness_utxo.clone().unwrap(),
+            tx.output[0].clone(),
+        )
+    }
 
-    // all input inscription
-    unsigned_tx.input.push(TxIn {
-        previous_output: outpoint,
-        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-        ..Default::default()
-    });
+    #[test]
+    fn validate_listing_pairs() {
+        let (txin, prevout, txout) = listing_pair();
+        let mut cheaper = prevout.clone();
+        cheaper.value = Amount::from_sat(9_000);
+        let mut unsigned = txin.clone();
+        unsigned.previous_output.vout += 1;
+        unsigned.witness.clear();
+
+        let reports = validate_snipe_pairs(
+            vec![
+                (txin.clone(), prevout.clone(), txout.clone()),
+                (txin.clone(), prevout.clone(), txout.clone()),
+                (
+                    TxIn {
+                        previous_output: OutPoint::null(),
+                        ..txin
+                    },
+                    cheaper,
+                    txout.clone(),
+                ),
+                (unsigned, prevout, txout),
+            ],
+            None,
+        );
 
-    psbt_inputs.push(Input {
-        witness_utxo: Some(TxOut {
-            value: amount,
-            script_pubkey: ordi_addr.script_pubkey(),
-        }),
-        ..Default::default()
-    });
-    dummy_tx.append_input(ordi_addr.script_pubkey(), None, None);
+        assert_eq!(
+            reports[0].sighash.as_deref(),
+            Some("SIGHASH_SINGLE|SIGHASH_ANYONECANPAY")
+        );
+        assert_eq!(reports[0].status, SnipePairStatus::Valid);
+        assert_eq!(reports[1].status, SnipePairStatus::Duplicate);
+        assert!(matches!(
+            reports[2].status,
+            SnipePairStatus::InvalidSignature { .. }
+        ));
+        assert_eq!(reports[3].sighash, None);
+        assert_eq!(reports[3].status, SnipePairStatus::Unsigned);
+    }
 
-    let mut output_amount = Amount::ZERO;
+    #[test]
+    fn split_dummy_utxos() {
+        let builder = |value: u64, count: u32| DummyUtxoPsbtBuilder {
+            utxo: local_output(0, value),
+            count,
+            dummy_value: DUMMY_UTXO,
+            dummy_addr: address(),
+            change_addr: address(),
+            fee_rate: FeeRate::from_sat_per_vb_unchecked(10),
+            descriptor: None,
+        };
+        let build = |value: u64, count: u32| builder(value, count).build();
+
+        let tx = build(10_000, 3).unwrap().unsigned_tx;
+        assert_eq!(tx.output.len(), 4);
+        assert!(tx.output[..3].iter().all(|output| output.value == DUMMY_UTXO));
+        let mut dummy_tx = DummyTransaction::new();
+        dummy_tx.append_input(address().script_pubkey(), None, None);
+        for _ in 0..4 {
+            dummy_tx.append_output(address().script_pubkey());
+        }
+        assert_eq!(
+            tx.output[3].value,
+            Amount::from_sat(10_000 - 1_800 - 10 * dummy_tx.vsize() as u64)
+        );
 
-    unsigned_tx.output.push(TxOut {
-        value: Amount::from_sat(600),
-        script_pubkey: change_addr.script_pubkey(),
-    });
-    dummy_tx.append_output(change_addr.script_pubkey());
-    output_amount += Amount::from_sat(600);
-
-    for offset in inscription_offsets.into_iter() {
-        // index
-        unsigned_tx.output.push(TxOut {
-            value: offset,
-            script_pubkey: recv_addr.script_pubkey(),
-        });
-        dummy_tx.append_output(recv_addr.script_pubkey());
-        output_amount += offset;
+        // change below dust is left to the fee
+        let tx = build(4_000, 3).unwrap().unsigned_tx;
+        assert_eq!(tx.output.len(), 3);
+
+        assert!(matches!(build(1_800, 3), Err(SnipeError::UtxoNotEnough)));
+        assert!(matches!(
+            build(10_000, u32::MAX),
+            Err(SnipeError::UtxoNotEnough)
+        ));
+        assert!(matches!(
+            DummyUtxoPsbtBuilder {
+                dummy_value: Amount::MAX,
+                ..builder(10_000, 2)
+            }
+            .build(),
+            Err(SnipeError::UtxoNotEnough)
+        ));
+        assert!(matches!(
+            DummyUtxoPsbtBuilder {
+                fee_rate: FeeRate::from_sat_per_kwu(u64::MAX),
+                ..builder(10_000, 2)
+            }
+            .build(),
+            Err(SnipeError::UtxoNotEnough)
+        ));
+        assert!(matches!(build(10_000, 0), Err(SnipeError::NoDummyOutputs)));
     }
 
-    // change
-    unsigned_tx.output.push(TxOut {
-        value: Amount::ZERO,
-        script_pubkey: change_addr.script_pubkey(),
-    });
-    dummy_tx.append_output(change_addr.script_pubkey());
+    #[test]
+    fn split_dummy_utxos_sized_by_descriptor() {
+        let build = |descriptor: Option<ExtendedDescriptor>| {
+            DummyUtxoPsbtBuilder {
+                utxo: local_output(0, 10_000),
+                count: 3,
+                dummy_value: DUMMY_UTXO,
+                dummy_addr: address(),
+                change_addr: address(),
+                fee_rate: FeeRate::from_sat_per_vb_unchecked(10),
+                descriptor,
+            }
+            .build()
+            .unwrap()
+            .unsigned_tx
+        };
+
+        let descriptor = multisig_descriptor().extended_descriptor.clone();
+        let mut dummy_tx = DummyTransaction::new();
+        dummy_tx.append_descriptor_input(&descriptor).unwrap();
+        for _ in 0..4 {
+            dummy_tx.append_output(address().script_pubkey());
+        }
 
-    let network_fee = fee_rate.fee_vb(dummy_tx.vsize() as u64).unwrap();
-    unsigned_tx.output.last_mut().unwrap().value = amount - network_fee - output_amount;
+        let tx = build(Some(descriptor));
+        assert_eq!(
+            tx.output[3].value,
+            Amount::from_sat(10_000 - 1_800 - 10 * dummy_tx.vsize() as u64)
+        );
+        assert!(tx.output[3].value < build(None).output[3].value);
+    }
 
-    let o_len = unsigned_tx.output.len();
-    let psbt = Psbt {
-        unsigned_tx,
-        version: 0,
-        xpub: Default::default(),
-        proprietary: Default::default(),
-        unknown: Default::default(),
-        inputs: psbt_inputs,
-        outputs: vec![Default::default(); o_len],
-    };
-    Ok(psbt)
+    #[test]
+    fn select_dummies_skips_ordinals() {
+        let wallet = funded_wallet(&[600, 546, 10_000, 330]);
+        let values = |utxos: Vec<LocalOutput>| {
+            utxos
+                .iter()
+                .map(|utxo| utxo.txout.value.0.to_sat())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            values(select_dummy_utxos(wallet.clone(), None, None)),
+            vec![330, 546, 600]
+        );
+
+        let tracker = Arc::new(OrdinalTracker::new());
+        let inscribed = wallet
+            .list_unspent()
+            .into_iter()
+            .find(|utxo| utxo.txout.value.0.to_sat() == 546)
+            .unwrap();
+        tracker
+            .annotate(
+                inscribed.outpoint,
+                vec![InscriptionOffset {
+                    inscription_id: format!("{}i0", bitcoin::Txid::all_zeros()),
+                    offset: 0,
+                }],
+                Vec::new(),
+            )
+            .unwrap();
+        assert_eq!(
+            values(select_dummy_utxos(
+                wallet,
+                Some(Arc::new(crate::bitcoin::Amount(Amount::from_sat(1_000)))),
+                Some(tracker)
+            )),
+            vec![330, 600]
+        );
+    }
 }
@@ -0,0 +1,572 @@
+use crate::bitcoin::{Amount, OutPoint, Transaction};
+use crate::ordinal::inscription::inscription_id::InscriptionId;
+use crate::ordinal::inscription::parse_inscriptions;
+use crate::ordinal::rune::{RuneAmount, RuneId};
+use crate::ordinal::RuneBalance;
+use crate::types::LocalOutput;
+use bdk_wallet::bitcoin;
+use bdk_wallet::serde_json;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum OrdinalTrackerError {
+    #[error("expected {expected} input values, got {got}")]
+    InputValues { expected: u64, got: u64 },
+
+    #[error("invalid inscription id `{inscription_id}`")]
+    InscriptionId { inscription_id: String },
+
+    #[error("json error: {error_message}")]
+    Json { error_message: String },
+}
+
+/// Inscription and the offset of its sat in an output.
+#[derive(uniffi::Record, Debug, Clone, PartialEq, Eq)]
+pub struct InscriptionOffset {
+    /// inscription id formatted as `<txid>i<index>`
+    pub inscription_id: String,
+    pub offset: u64,
+}
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UtxoKind {
+    /// safe to spend as fees
+    Cardinal,
+    /// carries inscriptions, maybe runes too
+    Inscribed,
+    /// carries runes only
+    Runic,
+}
+
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct ClassifiedUtxo {
+    pub output: LocalOutput,
+    pub kind: UtxoKind,
+    pub inscriptions: Vec<InscriptionOffset>,
+    pub runes: Vec<RuneBalance>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+struct OutputOrdinals {
+    /// inscription ids with sat offsets, in offset order
+    inscriptions: Vec<(String, u64)>,
+    runes: BTreeMap<ordinals::RuneId, u128>,
+}
+
+impl OutputOrdinals {
+    fn is_empty(&self) -> bool {
+        self.inscriptions.is_empty() && self.runes.is_empty()
+    }
+}
+
+/// Inscriptions and runes on outputs, learned from transactions applied in order and from
+/// annotations.
+///
+/// It follows sats first-in first-out through the transactions it is given and allocates runes by
+/// their runestones. Mints and etchings are not credited, annotate the outputs receiving them.
+/// Store it with [`OrdinalTracker::to_json`].
+#[derive(uniffi::Object, Debug, Default)]
+pub struct OrdinalTracker {
+    outputs: Mutex<BTreeMap<bitcoin::OutPoint, OutputOrdinals>>,
+}
+
+#[uniffi::export]
+impl OrdinalTracker {
+    #[uniffi::constructor]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[uniffi::constructor]
+    pub fn from_json(json: String) -> Result<Self, OrdinalTrackerError> {
+        let outputs: Vec<(bitcoin::OutPoint, OutputOrdinals)> = serde_json::from_str(&json)
+            .map_err(|e| OrdinalTrackerError::Json {
+                error_message: e.to_string(),
+            })?;
+        Ok(Self {
+            outputs: Mutex::new(outputs.into_iter().collect()),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let outputs = self.outputs.lock().unwrap();
+        serde_json::to_string(&outputs.iter().collect::<Vec<_>>())
+            .expect("tracker serializes to json")
+    }
+
+    /// Record what `outpoint` holds, replacing anything known about it.
+    pub fn annotate(
+        &self,
+        outpoint: OutPoint,
+        inscriptions: Vec<InscriptionOffset>,
+        runes: Vec<RuneBalance>,
+    ) -> Result<(), OrdinalTrackerError> {
+        let mut ordinals = OutputOrdinals::default();
+        for inscription in inscriptions {
+            InscriptionId::from_str(&inscription.inscription_id).map_err(|_| {
+                OrdinalTrackerError::InscriptionId {
+                    inscription_id: inscription.inscription_id.clone(),
+                }
+            })?;
+            ordinals
+                .inscriptions
+                .push((inscription.inscription_id, inscription.offset));
+        }
+        ordinals.inscriptions.sort_by_key(|(_, offset)| *offset);
+        for balance in runes {
+            let amount = ordinals
+                .runes
+                .entry(balance.rune_id.as_ref().into())
+                .or_default();
+            *amount = amount.saturating_add(balance.amount.0);
+        }
+
+        let mut outputs = self.outputs.lock().unwrap();
+        if ordinals.is_empty() {
+            outputs.remove(&outpoint.into());
+        } else {
+            outputs.insert(outpoint.into(), ordinals);
+        }
+        Ok(())
+    }
+
+    /// Move what the inputs of `tx` hold to its outputs and record new inscriptions.
+    ///
+    /// `input_values` are the values of the outputs spent by `tx`, in input order.
+    pub fn apply_transaction(
+        &self,
+        tx: &Transaction,
+        input_values: Vec<Arc<Amount>>,
+    ) -> Result<(), OrdinalTrackerError> {
+        let bitcoin_tx = &tx.0;
+        if input_values.len() != bitcoin_tx.input.len() {
+            return Err(OrdinalTrackerError::InputValues {
+                expected: bitcoin_tx.input.len() as u64,
+                got: input_values.len() as u64,
+            });
+        }
+
+        let mut outputs = self.outputs.lock().unwrap();
+        let txid = bitcoin_tx.compute_txid();
+
+        let mut input_offsets = Vec::new();
+        let mut inscriptions = Vec::new();
+        let mut runes = BTreeMap::<ordinals::RuneId, u128>::new();
+        let mut offset = 0;
+        for (txin, value) in bitcoin_tx.input.iter().zip(&input_values) {
+            input_offsets.push(offset);
+            if let Some(spent) = outputs.remove(&txin.previous_output) {
+                inscriptions.extend(
+                    spent
+                        .inscriptions
+                        .into_iter()
+                        .map(|(id, sat)| (id, offset + sat)),
+                );
+                for (id, amount) in spent.runes {
+                    let balance = runes.entry(id).or_default();
+                    *balance = balance.saturating_add(amount);
+                }
+            }
+            offset += value.0.to_sat();
+        }
+
+        let total_output = bitcoin_tx
+            .output
+            .iter()
+            .map(|txout| txout.value.to_sat())
+            .sum::<u64>();
+
+        // 新铭文在所在输入的第一个 sat, 有 pointer 时在 pointer 指向的 sat
+        for (index, inscription) in parse_inscriptions(tx).into_iter().enumerate() {
+            let sat = inscription
+                .pointer
+                .filter(|pointer| *pointer < total_output)
+                .unwrap_or(input_offsets[inscription.input as usize]);
+            inscriptions.push((
+                InscriptionId {
+                    txid,
+                    index: index as u32,
+                }
+                .to_string(),
+                sat,
+            ));
+        }
+
+        let mut allocated = vec![OutputOrdinals::default(); bitcoin_tx.output.len()];
+
+        for (id, sat) in inscriptions {
+            let mut start = 0;
+            for (vout, txout) in bitcoin_tx.output.iter().enumerate() {
+                let end = start + txout.value.to_sat();
+                if sat < end {
+                    allocated[vout].inscriptions.push((id, sat - start));
+                    break;
+                }
+                start = end;
+            }
+            // 超出输出的 sat 作为手续费, 铭文不再跟踪
+        }
+
+        Self::allocate_runes(bitcoin_tx, runes, &mut allocated);
+
+        for (vout, mut ordinals) in allocated.into_iter().enumerate() {
+            if ordinals.is_empty() {
+                continue;
+            }
+            ordinals.inscriptions.sort_by_key(|(_, offset)| *offset);
+            outputs.insert(
+                bitcoin::OutPoint {
+                    txid,
+                    vout: vout as u32,
+                },
+                ordinals,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Tag `utxos` by what they hold.
+    pub fn classify(&self, utxos: Vec<LocalOutput>) -> Vec<ClassifiedUtxo> {
+        let outputs = self.outputs.lock().unwrap();
+        utxos
+            .into_iter()
+            .map(|output| {
+                let ordinals = outputs
+                    .get(&output.outpoint.clone().into())
+                    .cloned()
+                    .unwrap_or_default();
+                let kind = if !ordinals.inscriptions.is_empty() {
+                    UtxoKind::Inscribed
+                } else if !ordinals.runes.is_empty() {
+                    UtxoKind::Runic
+                } else {
+                    UtxoKind::Cardinal
+                };
+                ClassifiedUtxo {
+                    output,
+                    kind,
+                    inscriptions: ordinals
+                        .inscriptions
+                        .into_iter()
+                        .map(|(inscription_id, offset)| InscriptionOffset {
+                            inscription_id,
+                            offset,
+                        })
+                        .collect(),
+                    runes: ordinals
+                        .runes
+                        .into_iter()
+                        .map(|(id, amount)| RuneBalance {
+                            rune_id: Arc::new(RuneId::from(id)),
+                            amount: Arc::new(RuneAmount::from(amount)),
+                        })
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Outputs known to hold inscriptions or runes.
+    pub fn non_cardinal_outpoints(&self) -> Vec<OutPoint> {
+        self.outputs
+            .lock()
+            .unwrap()
+            .keys()
+            .map(OutPoint::from)
+            .collect()
+    }
+}
+
+impl OrdinalTracker {
+    pub(crate) fn non_cardinal(&self) -> Vec<bitcoin::OutPoint> {
+        self.outputs.lock().unwrap().keys().copied().collect()
+    }
+
+    /// Allocate `runes` to the outputs of `tx` by its runestone, a cenotaph burns them.
+    fn allocate_runes(
+        tx: &bitcoin::Transaction,
+        mut runes: BTreeMap<ordinals::RuneId, u128>,
+        allocated: &mut [OutputOrdinals],
+    ) {
+        // 分配给 OP_RETURN 的符文被销毁
+        let mut credit = |vout: usize, id: ordinals::RuneId, amount: u128| {
+            if amount > 0 && !tx.output[vout].script_pubkey.is_op_return() {
+                let balance = allocated[vout].runes.entry(id).or_default();
+                *balance = balance.saturating_add(amount);
+            }
+        };
+
+        let spendable = tx
+            .output
+            .iter()
+            .enumerate()
+            .filter(|(_, txout)| !txout.script_pubkey.is_op_return())
+            .map(|(vout, _)| vout)
+            .collect::<Vec<_>>();
+
+        let pointer = match ordinals::Runestone::decipher(tx) {
+            Some(ordinals::Artifact::Cenotaph(_)) => return,
+            Some(ordinals::Artifact::Runestone(runestone)) => {
+                for edict in runestone.edicts {
+                    let Some(balance) = runes.get_mut(&edict.id) else {
+                        continue;
+                    };
+                    let output = edict.output as usize;
+                    if output == tx.output.len() {
+                        if spendable.is_empty() {
+                            continue;
+                        }
+                        if edict.amount == 0 {
+                            let count = spendable.len() as u128;
+                            let each = *balance / count;
+                            let remainder = *balance % count;
+                            for (i, vout) in spendable.iter().enumerate() {
+                                credit(*vout, edict.id, each + u128::from((i as u128) < remainder));
+                            }
+                            *balance = 0;
+                        } else {
+                            for vout in &spendable {
+                                let amount = edict.amount.min(*balance);
+                                credit(*vout, edict.id, amount);
+                                *balance -= amount;
+                            }
+                        }
+                    } else {
+                        let amount = if edict.amount == 0 {
+                            *balance
+                        } else {
+                            edict.amount.min(*balance)
+                        };
+                        credit(output, edict.id, amount);
+                        *balance -= amount;
+                    }
+                }
+                runestone.pointer.map(|pointer| pointer as usize)
+            }
+            None => None,
+        };
+
+        // 未分配的符文给 pointer 或第一个非 OP_RETURN 输出, 否则销毁
+        if let Some(vout) = pointer.or(spendable.first().copied()) {
+            for (id, amount) in runes {
+                credit(vout, id, amount);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ordinal::inscription::{mint, NamedFile};
+    use crate::testing::{
+        address, block_on, funded_wallet, local_output, TAPROOT_ADDRESS,
+    };
+    use bdk_wallet::bitcoin::{
+        absolute::LockTime, transaction::Version, Network, ScriptBuf, TxIn, TxOut,
+    };
+
+    fn amounts(values: &[u64]) -> Vec<Arc<Amount>> {
+        values
+            .iter()
+            .map(|value| Arc::new(Amount(bitcoin::Amount::from_sat(*value))))
+            .collect()
+    }
+
+    fn tx(inputs: &[bitcoin::OutPoint], outputs: Vec<(ScriptBuf, u64)>) -> Transaction {
+        bitcoin::Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: inputs
+                .iter()
+                .map(|outpoint| TxIn {
+                    previous_output: *outpoint,
+                    ..Default::default()
+                })
+                .collect(),
+            output: outputs
+                .into_iter()
+                .map(|(script_pubkey, value)| TxOut {
+                    value: bitcoin::Amount::from_sat(value),
+                    script_pubkey,
+                })
+                .collect(),
+        }
+        .into()
+    }
+
+    fn outpoint(tx: &Transaction, vout: u32) -> bitcoin::OutPoint {
+        bitcoin::OutPoint {
+            txid: tx.0.compute_txid(),
+            vout,
+        }
+    }
+
+    #[test]
+    fn inscriptions_follow_sats() {
+        let output = block_on(mint(
+            Network::Bitcoin,
+            vec![local_output(0, 100_000)],
+            NamedFile {
+                name: "a.txt".into(),
+                data: b"a".to_vec(),
+                content_type: None,
+            },
+            TAPROOT_ADDRESS,
+            TAPROOT_ADDRESS,
+            2,
+            Some(1_000),
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+        let reveal = output.reveal_tx.as_ref();
+        let commit_value = output.session.commit_value;
+
+        let tracker = OrdinalTracker::new();
+        tracker
+            .apply_transaction(reveal, amounts(&[commit_value]))
+            .unwrap();
+
+        // cardinal input first, the inscribed sat moves to offset 600 of the first output
+        let cardinal = bitcoin::OutPoint {
+            txid: bitcoin::Txid::from_str(&"1".repeat(64)).unwrap(),
+            vout: 0,
+        };
+        let transfer = tx(
+            &[cardinal, outpoint(reveal, 0)],
+            vec![
+                (address().script_pubkey(), 1_000),
+                (address().script_pubkey(), 600),
+            ],
+        );
+        tracker
+            .apply_transaction(&transfer, amounts(&[400, 1_000]))
+            .unwrap();
+
+        let mut utxos = vec![local_output(0, 1_000), local_output(1, 600)];
+        for (vout, utxo) in utxos.iter_mut().enumerate() {
+            utxo.outpoint = outpoint(&transfer, vout as u32).into();
+        }
+        let classified = tracker.classify(utxos);
+        assert_eq!(classified[0].kind, UtxoKind::Inscribed);
+        assert_eq!(
+            classified[0].inscriptions,
+            vec![InscriptionOffset {
+                inscription_id: output.inscriptions[0].id.clone(),
+                offset: 400,
+            }]
+        );
+        assert_eq!(classified[1].kind, UtxoKind::Cardinal);
+
+        let restored = OrdinalTracker::from_json(tracker.to_json()).unwrap();
+        assert_eq!(restored.non_cardinal(), vec![outpoint(&transfer, 0)]);
+    }
+
+    #[test]
+    fn runes_follow_runestones() {
+        let id = ordinals::RuneId {
+            block: 840_000,
+            tx: 1,
+        };
+        let funding = tx(&[], vec![(address().script_pubkey(), 1_000)]);
+        let tracker = OrdinalTracker::new();
+        tracker
+            .annotate(
+                outpoint(&funding, 0).into(),
+                Vec::new(),
+                vec![RuneBalance {
+                    rune_id: Arc::new(id.into()),
+                    amount: Arc::new(RuneAmount::from(1_000)),
+                }],
+            )
+            .unwrap();
+
+        let runestone = ordinals::Runestone {
+            edicts: vec![ordinals::Edict {
+                id,
+                amount: 300,
+                output: 2,
+            }],
+            ..Default::default()
+        }
+        .encipher();
+        let transfer = tx(
+            &[outpoint(&funding, 0)],
+            vec![
+                (runestone, 0),
+                (address().script_pubkey(), 330),
+                (address().script_pubkey(), 330),
+            ],
+        );
+        tracker
+            .apply_transaction(&transfer, amounts(&[1_000]))
+            .unwrap();
+
+        let balance = |vout| {
+            let mut utxo = local_output(vout, 330);
+            utxo.outpoint = outpoint(&transfer, vout).into();
+            let classified = tracker.classify(vec![utxo]).remove(0);
+            (
+                classified.kind,
+                classified
+                    .runes
+                    .iter()
+                    .map(|balance| balance.amount.0)
+                    .sum::<u128>(),
+            )
+        };
+        // the rest goes to the first output that is not OP_RETURN
+        assert_eq!(balance(1), (UtxoKind::Runic, 700));
+        assert_eq!(balance(2), (UtxoKind::Runic, 300));
+
+        // a cenotaph burns everything
+        let cenotaph = tx(
+            &[outpoint(&transfer, 1)],
+            vec![
+                (ScriptBuf::from_bytes(vec![0x6a, 0x5d, 0xff]), 0),
+                (address().script_pubkey(), 330),
+            ],
+        );
+        tracker
+            .apply_transaction(&cenotaph, amounts(&[330]))
+            .unwrap();
+        assert_eq!(tracker.non_cardinal(), vec![outpoint(&transfer, 2)]);
+
+        assert!(tracker.apply_transaction(&cenotaph, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn tx_builder_protects_ordinals() {
+        let wallet = funded_wallet(&[100_000, 50_000]);
+        let inscribed = wallet
+            .list_unspent()
+            .into_iter()
+            .find(|utxo| utxo.txout.value.to_sat() == 100_000)
+            .unwrap();
+
+        let tracker = Arc::new(OrdinalTracker::new());
+        tracker
+            .annotate(
+                inscribed.outpoint,
+                vec![InscriptionOffset {
+                    inscription_id: format!("{}i0", "0".repeat(64)),
+                    offset: 0,
+                }],
+                Vec::new(),
+            )
+            .unwrap();
+
+        let script = crate::bitcoin::Script(address().script_pubkey());
+        let amount = Arc::new(Amount(bitcoin::Amount::from_sat(60_000)));
+        let builder = crate::tx_builder::TxBuilder::new().add_recipient(&script, amount);
+
+        assert!(builder.finish(&wallet).is_ok());
+        assert!(builder.protect_ordinals(tracker).finish(&wallet).is_err());
+    }
+}
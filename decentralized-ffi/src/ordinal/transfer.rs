@@ -0,0 +1,489 @@
+use std::collections::{HashMap, HashSet};
+
+use bdk_wallet::bitcoin::absolute::LockTime;
+use bdk_wallet::bitcoin::psbt::Input;
+use bdk_wallet::bitcoin::transaction::Version;
+use bdk_wallet::bitcoin::{
+    Address, Amount, FeeRate, OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+};
+
+use crate::ordinal::dummy_transaction::DummyTransaction;
+use crate::ordinal::rune::{Edict, RuneAmount, RuneId, Runestone, RunestoneError};
+use crate::types::LocalOutput;
+use std::sync::Arc;
+
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum RuneTransferError {
+    #[error("no transfers")]
+    NoTransfers,
+
+    #[error("transfer amount of {rune_id} is zero")]
+    ZeroAmount { rune_id: String },
+
+    #[error("transfer amount of {rune_id} overflows")]
+    AmountOverflow { rune_id: String },
+
+    #[error("insufficient {rune_id} balance: required {required}, available {available}")]
+    InsufficientRunes {
+        rune_id: String,
+        required: String,
+        available: String,
+    },
+
+    #[error("insufficient funds: required {required} sat, available {available} sat")]
+    InsufficientFunds { required: u64, available: u64 },
+
+    #[error("postage {postage} sat is below the dust limit {dust} sat")]
+    DustPostage { postage: u64, dust: u64 },
+
+    #[error("runestone error: {error_message}")]
+    Runestone { error_message: String },
+}
+
+impl From<RunestoneError> for RuneTransferError {
+    fn from(e: RunestoneError) -> Self {
+        Self::Runestone {
+            error_message: e.to_string(),
+        }
+    }
+}
+
+pub(crate) struct RuneTransferPsbtBuilder {
+    pub(crate) rune_utxos: Vec<(LocalOutput, HashMap<ordinals::RuneId, u128>)>,
+    pub(crate) cardinal_utxos: Vec<LocalOutput>,
+    pub(crate) transfers: Vec<(ordinals::RuneId, u128, Address)>,
+    pub(crate) rune_change_addr: Address, // remaining runes
+    pub(crate) change_addr: Address,      // btc change
+    pub(crate) postage: Option<Amount>,
+    pub(crate) fee_rate: FeeRate,
+}
+
+impl RuneTransferPsbtBuilder {
+    pub(crate) fn build(self) -> Result<Psbt, RuneTransferError> {
+        if self.transfers.is_empty() {
+            return Err(RuneTransferError::NoTransfers);
+        }
+
+        let mut required: HashMap<ordinals::RuneId, u128> = HashMap::new();
+        for (id, amount, _) in self.transfers.iter() {
+            if *amount == 0 {
+                return Err(RuneTransferError::ZeroAmount {
+                    rune_id: id.to_string(),
+                });
+            }
+            let total = required.entry(*id).or_default();
+            *total = total
+                .checked_add(*amount)
+                .ok_or(RuneTransferError::AmountOverflow {
+                    rune_id: id.to_string(),
+                })?;
+        }
+
+        // no rune utxo may be used to pay fees, its runes would go to the recipient
+        let rune_outpoints: HashSet<OutPoint> = self
+            .rune_utxos
+            .iter()
+            .map(|(utxo, _)| utxo.outpoint.clone().into())
+            .collect();
+
+        // pick the rune utxos holding the requested runes
+        let mut available: HashMap<ordinals::RuneId, u128> = HashMap::new();
+        let mut rune_inputs = Vec::new();
+        for (utxo, balances) in self.rune_utxos {
+            let needed = balances.keys().any(|id| {
+                required.get(id).is_some_and(|required| {
+                    available.get(id).copied().unwrap_or_default() < *required
+                })
+            });
+            if !needed {
+                continue;
+            }
+            for (id, amount) in balances {
+                let total = available.entry(id).or_default();
+                *total = total.saturating_add(amount);
+            }
+            rune_inputs.push(utxo);
+        }
+
+        for (id, required) in required.iter() {
+            let available = available.get(id).copied().unwrap_or_default();
+            if available < *required {
+                return Err(RuneTransferError::InsufficientRunes {
+                    rune_id: id.to_string(),
+                    required: required.to_string(),
+                    available: available.to_string(),
+                });
+            }
+        }
+
+        let has_remainder = available
+            .iter()
+            .any(|(id, amount)| required.get(id).copied().unwrap_or_default() < *amount);
+
+        let mut unsigned_tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let mut dummy_tx = DummyTransaction::new();
+        let mut psbt_inputs = Vec::new();
+        let mut input_amount = Amount::ZERO;
+
+        for utxo in rune_inputs.iter() {
+            push_input(&mut unsigned_tx, &mut psbt_inputs, &mut dummy_tx, utxo);
+            input_amount += utxo.txout.value.0;
+        }
+
+        // rune outputs
+        let mut edicts = Vec::new();
+        for (id, amount, destination) in self.transfers.iter() {
+            edicts.push(Edict {
+                id: Arc::new(RuneId::from(*id)),
+                amount: Arc::new(RuneAmount(*amount)),
+                output: unsigned_tx.output.len() as u32,
+            });
+            let script_pubkey = destination.script_pubkey();
+            unsigned_tx.output.push(TxOut {
+                value: postage_for(self.postage, &script_pubkey)?,
+                script_pubkey: script_pubkey.clone(),
+            });
+            dummy_tx.append_output(script_pubkey);
+        }
+
+        // unallocated runes go to the pointer
+        let pointer = if has_remainder {
+            let script_pubkey = self.rune_change_addr.script_pubkey();
+            unsigned_tx.output.push(TxOut {
+                value: postage_for(self.postage, &script_pubkey)?,
+                script_pubkey: script_pubkey.clone(),
+            });
+            dummy_tx.append_output(script_pubkey);
+            Some(unsigned_tx.output.len() as u32 - 1)
+        } else {
+            None
+        };
+
+        let runestone = Runestone {
+            edicts,
+            etching: None,
+            mint: None,
+            pointer,
+        }
+        .encipher()?;
+        unsigned_tx.output.push(TxOut {
+            value: Amount::ZERO,
+            script_pubkey: runestone.clone(),
+        });
+        dummy_tx.append_output(runestone);
+
+        let cardinal_utxos = self
+            .cardinal_utxos
+            .into_iter()
+            .filter(|utxo| !rune_outpoints.contains(&utxo.outpoint.clone().into()))
+            .collect();
+
+        fund_psbt(
+            &mut unsigned_tx,
+            &mut psbt_inputs,
+            &mut dummy_tx,
+            input_amount,
+            cardinal_utxos,
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: self.change_addr.script_pubkey(),
+            },
+            self.fee_rate,
+        )?;
+
+        let o_len = unsigned_tx.output.len();
+        let psbt = Psbt {
+            unsigned_tx,
+            version: 0,
+            xpub: Default::default(),
+            proprietary: Default::default(),
+            unknown: Default::default(),
+            inputs: psbt_inputs,
+            outputs: vec![Default::default(); o_len],
+        };
+        Ok(psbt)
+    }
+}
+
+fn postage_for(
+    postage: Option<Amount>,
+    script_pubkey: &ScriptBuf,
+) -> Result<Amount, RuneTransferError> {
+    let dust = script_pubkey.minimal_non_dust();
+    match postage {
+        None => Ok(dust),
+        Some(postage) if postage < dust => Err(RuneTransferError::DustPostage {
+            postage: postage.to_sat(),
+            dust: dust.to_sat(),
+        }),
+        Some(postage) => Ok(postage),
+    }
+}
+
+pub(crate) fn push_input(
+    unsigned_tx: &mut Transaction,
+    psbt_inputs: &mut Vec<Input>,
+    dummy_tx: &mut DummyTransaction,
+    utxo: &LocalOutput,
+) {
+    push_prevout(
+        unsigned_tx,
+        psbt_inputs,
+        dummy_tx,
+        utxo.outpoint.clone().into(),
+        (&utxo.txout).into(),
+    );
+}
+
+pub(crate) fn push_prevout(
+    unsigned_tx: &mut Transaction,
+    psbt_inputs: &mut Vec<Input>,
+    dummy_tx: &mut DummyTransaction,
+    previous_output: OutPoint,
+    prevout: TxOut,
+) {
+    unsigned_tx.input.push(TxIn {
+        previous_output,
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        ..Default::default()
+    });
+    dummy_tx.append_input(prevout.script_pubkey.clone(), None, None);
+    psbt_inputs.push(Input {
+        witness_utxo: Some(prevout),
+        ..Default::default()
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct InsufficientFunds {
+    pub(crate) required: Amount,
+    pub(crate) available: Amount,
+}
+
+impl From<InsufficientFunds> for RuneTransferError {
+    fn from(e: InsufficientFunds) -> Self {
+        Self::InsufficientFunds {
+            required: e.required.to_sat(),
+            available: e.available.to_sat(),
+        }
+    }
+}
+
+/// Add cardinal inputs until the outputs of `unsigned_tx` and the fee are covered, then append
+/// `change` carrying the leftover.
+///
+/// The value of `change` is the least it must receive: with zero the change output is dropped
+/// when it would be dust, otherwise it is always added.
+pub(crate) fn fund_psbt(
+    unsigned_tx: &mut Transaction,
+    psbt_inputs: &mut Vec<Input>,
+    dummy_tx: &mut DummyTransaction,
+    mut input_amount: Amount,
+    cardinal_utxos: Vec<LocalOutput>,
+    change: TxOut,
+    fee_rate: FeeRate,
+) -> Result<Amount, InsufficientFunds> {
+    // an overflowing total can never be funded
+    let output_amount = unsigned_tx
+        .output
+        .iter()
+        .try_fold(Amount::ZERO, |total, txout| total.checked_add(txout.value))
+        .unwrap_or(Amount::MAX);
+    let required = |fee: Amount| output_amount.checked_add(fee).unwrap_or(Amount::MAX);
+    let reserved = change.value;
+    let min_change = reserved.max(change.script_pubkey.minimal_non_dust());
+
+    let mut cardinal_utxos = cardinal_utxos.into_iter();
+    loop {
+        let fee = fee_rate
+            .fee_wu(dummy_tx.weight())
+            .unwrap_or(Amount::MAX_MONEY);
+        let change_fee = fee_rate
+            .fee_wu(dummy_tx.weight() + change.weight())
+            .unwrap_or(Amount::MAX_MONEY);
+
+        if let Some(value) = input_amount.checked_sub(required(change_fee))
+            && value >= min_change
+        {
+            unsigned_tx.output.push(TxOut {
+                value,
+                ..change.clone()
+            });
+            dummy_tx.append_output(change.script_pubkey.clone());
+            return Ok(change_fee);
+        }
+
+        if reserved == Amount::ZERO && input_amount >= required(fee) {
+            // the leftover is below dust and goes to the miners
+            return Ok(input_amount - output_amount);
+        }
+
+        let Some(utxo) = cardinal_utxos.next() else {
+            let required = if reserved == Amount::ZERO {
+                required(fee)
+            } else {
+                required(change_fee)
+                    .checked_add(reserved)
+                    .unwrap_or(Amount::MAX)
+            };
+            return Err(InsufficientFunds {
+                required,
+                available: input_amount,
+            });
+        };
+        push_input(unsigned_tx, psbt_inputs, dummy_tx, &utxo);
+        input_amount += utxo.txout.value.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{address, local_output as utxo};
+
+    fn decipher(psbt: &Psbt) -> ordinals::Runestone {
+        match ordinals::Runestone::decipher(&psbt.unsigned_tx).unwrap() {
+            ordinals::Artifact::Runestone(runestone) => runestone,
+            ordinals::Artifact::Cenotaph(cenotaph) => panic!("cenotaph: {cenotaph:?}"),
+        }
+    }
+
+    #[test]
+    fn transfer_with_remainder() {
+        let id = ordinals::RuneId {
+            block: 840_000,
+            tx: 1,
+        };
+        let amount = u128::from(u64::MAX) + 10;
+        let psbt = RuneTransferPsbtBuilder {
+            rune_utxos: vec![(utxo(0, 546), HashMap::from([(id, amount * 2)]))],
+            cardinal_utxos: vec![utxo(1, 100_000)],
+            transfers: vec![(id, amount, address())],
+            rune_change_addr: address(),
+            change_addr: address(),
+            postage: None,
+            fee_rate: FeeRate::from_sat_per_vb_unchecked(10),
+        }
+        .build()
+        .unwrap();
+
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 2);
+        // destination, rune change, runestone, btc change
+        assert_eq!(tx.output.len(), 4);
+        assert_eq!(tx.output[0].value, Amount::from_sat(330));
+        assert_eq!(tx.output[1].value, Amount::from_sat(330));
+        assert!(tx.output[3].value > Amount::ZERO);
+
+        let runestone = decipher(&psbt);
+        assert_eq!(runestone.pointer, Some(1));
+        assert_eq!(
+            runestone.edicts,
+            vec![ordinals::Edict {
+                id,
+                amount,
+                output: 0
+            }]
+        );
+
+        let fee =
+            Amount::from_sat(100_546) - tx.output.iter().map(|txout| txout.value).sum::<Amount>();
+        assert!(
+            fee >= FeeRate::from_sat_per_vb_unchecked(10)
+                .fee_vb(tx.vsize() as u64)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn transfer_exact_balance_has_no_pointer() {
+        let id = ordinals::RuneId { block: 1, tx: 0 };
+        let psbt = RuneTransferPsbtBuilder {
+            rune_utxos: vec![
+                (utxo(0, 10_000), HashMap::from([(id, 7)])),
+                (
+                    utxo(1, 546),
+                    HashMap::from([(ordinals::RuneId { block: 2, tx: 0 }, 1)]),
+                ),
+            ],
+            cardinal_utxos: vec![],
+            transfers: vec![(id, 3, address()), (id, 4, address())],
+            rune_change_addr: address(),
+            change_addr: address(),
+            postage: Some(Amount::from_sat(600)),
+            fee_rate: FeeRate::from_sat_per_vb_unchecked(2),
+        }
+        .build()
+        .unwrap();
+
+        // the unrelated rune utxo is left alone
+        assert_eq!(psbt.unsigned_tx.input.len(), 1);
+        let runestone = decipher(&psbt);
+        assert_eq!(runestone.pointer, None);
+        assert_eq!(runestone.edicts.len(), 2);
+        assert_eq!(psbt.unsigned_tx.output[0].value, Amount::from_sat(600));
+    }
+
+    #[test]
+    fn spare_rune_utxo_is_not_used_for_fees() {
+        let id = ordinals::RuneId { block: 1, tx: 0 };
+        let spare = utxo(1, 50_000);
+        let psbt = RuneTransferPsbtBuilder {
+            rune_utxos: vec![
+                (utxo(0, 546), HashMap::from([(id, 5)])),
+                (spare.clone(), HashMap::from([(id, 5)])),
+            ],
+            cardinal_utxos: vec![spare.clone(), utxo(2, 20_000)],
+            transfers: vec![(id, 5, address())],
+            rune_change_addr: address(),
+            change_addr: address(),
+            postage: None,
+            fee_rate: FeeRate::from_sat_per_vb_unchecked(5),
+        }
+        .build()
+        .unwrap();
+
+        let spare: OutPoint = spare.outpoint.into();
+        let inputs: Vec<_> = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect();
+        assert_eq!(inputs.len(), 2);
+        assert!(!inputs.contains(&spare));
+    }
+
+    #[test]
+    fn transfer_errors() {
+        let id = ordinals::RuneId { block: 1, tx: 0 };
+        let builder =
+            |balance: u128, cardinal: u64, postage: Option<u64>| RuneTransferPsbtBuilder {
+                rune_utxos: vec![(utxo(0, 330), HashMap::from([(id, balance)]))],
+                cardinal_utxos: vec![utxo(1, cardinal)],
+                transfers: vec![(id, 5, address())],
+                rune_change_addr: address(),
+                change_addr: address(),
+                postage: postage.map(Amount::from_sat),
+                fee_rate: FeeRate::from_sat_per_vb_unchecked(5),
+            };
+
+        assert!(matches!(
+            builder(4, 100_000, None).build(),
+            Err(RuneTransferError::InsufficientRunes { .. })
+        ));
+        assert!(matches!(
+            builder(5, 100, None).build(),
+            Err(RuneTransferError::InsufficientFunds { .. })
+        ));
+        assert!(matches!(
+            builder(5, 100_000, Some(100)).build(),
+            Err(RuneTransferError::DustPostage { .. })
+        ));
+    }
+}
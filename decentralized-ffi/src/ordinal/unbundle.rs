@@ -0,0 +1,397 @@
+use std::sync::Arc;
+
+use bdk_wallet::bitcoin::absolute::LockTime;
+use bdk_wallet::bitcoin::transaction::Version;
+use bdk_wallet::bitcoin::{
+    Address, Amount, FeeRate, OutPoint, Psbt, ScriptBuf, Transaction, TxOut,
+};
+
+use crate::ordinal::dummy_transaction::DummyTransaction;
+use crate::ordinal::rune::{Edict, RuneAmount, RuneId, Runestone, RunestoneError};
+use crate::ordinal::transfer::{InsufficientFunds, fund_psbt, push_prevout};
+use crate::types::LocalOutput;
+
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum UnbundleError {
+    #[error("no inscriptions or runes to unbundle")]
+    NothingToUnbundle,
+
+    #[error("inscription offset {offset} is outside the {value} sat output")]
+    OffsetOutOfRange { offset: u64, value: u64 },
+
+    #[error("postage {postage} sat is below the dust limit {dust} sat")]
+    DustPostage { postage: u64, dust: u64 },
+
+    #[error("{offset} sat ahead of the first inscription are below the dust limit {dust} sat")]
+    LeadingSatsBelowDust { offset: u64, dust: u64 },
+
+    #[error(
+        "inscriptions at offsets {offset} and {next} are closer than the dust limit {dust} sat"
+    )]
+    InscriptionsTooClose { offset: u64, next: u64, dust: u64 },
+
+    #[error("rune amount of {rune_id} is zero")]
+    ZeroAmount { rune_id: String },
+
+    #[error("rune amount of {rune_id} overflows")]
+    AmountOverflow { rune_id: String },
+
+    #[error("insufficient funds: required {required} sat, available {available} sat")]
+    InsufficientFunds { required: u64, available: u64 },
+
+    #[error("runestone error: {error_message}")]
+    Runestone { error_message: String },
+}
+
+impl From<RunestoneError> for UnbundleError {
+    fn from(e: RunestoneError) -> Self {
+        Self::Runestone {
+            error_message: e.to_string(),
+        }
+    }
+}
+
+impl From<InsufficientFunds> for UnbundleError {
+    fn from(e: InsufficientFunds) -> Self {
+        Self::InsufficientFunds {
+            required: e.required.to_sat(),
+            available: e.available.to_sat(),
+        }
+    }
+}
+
+/// Split one output holding several inscriptions and runes into one output per asset.
+///
+/// Every inscription lands on offset 0 of its own output of `postage` sats, or of the distance to
+/// the next inscription when that is smaller. Sats ahead of the first inscription and between
+/// inscriptions further apart than `postage` go to `change_addr`. Each rune balance gets its own
+/// `postage` output with an edict, so `runes` must list everything the output holds.
+pub(crate) struct UnbundlePsbtBuilder {
+    pub(crate) outpoint: OutPoint,
+    pub(crate) prevout: TxOut,
+    pub(crate) inscription_offsets: Vec<u64>,
+    pub(crate) runes: Vec<(ordinals::RuneId, u128)>,
+    pub(crate) cardinal_utxos: Vec<LocalOutput>,
+    pub(crate) destination: Address,
+    pub(crate) change_addr: Address,
+    pub(crate) postage: Option<Amount>,
+    pub(crate) fee_rate: FeeRate,
+}
+
+impl UnbundlePsbtBuilder {
+    pub(crate) fn build(self) -> Result<Psbt, UnbundleError> {
+        if self.inscription_offsets.is_empty() && self.runes.is_empty() {
+            return Err(UnbundleError::NothingToUnbundle);
+        }
+
+        let value = self.prevout.value.to_sat();
+        let mut offsets = self.inscription_offsets;
+        offsets.sort_unstable();
+        offsets.dedup();
+        if let Some(&offset) = offsets.last()
+            && offset >= value
+        {
+            return Err(UnbundleError::OffsetOutOfRange { offset, value });
+        }
+
+        let destination = self.destination.script_pubkey();
+        let destination_dust = destination.minimal_non_dust();
+        let postage = match self.postage {
+            None => destination_dust,
+            Some(postage) if postage < destination_dust => {
+                return Err(UnbundleError::DustPostage {
+                    postage: postage.to_sat(),
+                    dust: destination_dust.to_sat(),
+                });
+            }
+            Some(postage) => postage,
+        };
+        let change_script = self.change_addr.script_pubkey();
+        let change_dust = change_script.minimal_non_dust();
+
+        let mut unsigned_tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let mut dummy_tx = DummyTransaction::new();
+        let mut psbt_inputs = Vec::new();
+        push_prevout(
+            &mut unsigned_tx,
+            &mut psbt_inputs,
+            &mut dummy_tx,
+            self.outpoint,
+            self.prevout,
+        );
+
+        // sats ahead of the first inscription
+        if let Some(&first) = offsets.first()
+            && first > 0
+        {
+            if first < change_dust.to_sat() {
+                return Err(UnbundleError::LeadingSatsBelowDust {
+                    offset: first,
+                    dust: change_dust.to_sat(),
+                });
+            }
+            push_output(
+                &mut unsigned_tx,
+                &mut dummy_tx,
+                &change_script,
+                Amount::from_sat(first),
+            );
+        }
+
+        for (i, &offset) in offsets.iter().enumerate() {
+            let Some(&next) = offsets.get(i + 1) else {
+                // the last inscription may take sats of the inputs that follow
+                push_output(&mut unsigned_tx, &mut dummy_tx, &destination, postage);
+                continue;
+            };
+            let gap = Amount::from_sat(next - offset);
+            match gap.checked_sub(postage) {
+                Some(rest) if rest >= change_dust => {
+                    push_output(&mut unsigned_tx, &mut dummy_tx, &destination, postage);
+                    push_output(&mut unsigned_tx, &mut dummy_tx, &change_script, rest);
+                }
+                _ if gap < destination_dust => {
+                    return Err(UnbundleError::InscriptionsTooClose {
+                        offset,
+                        next,
+                        dust: destination_dust.to_sat(),
+                    });
+                }
+                _ => push_output(&mut unsigned_tx, &mut dummy_tx, &destination, gap),
+            }
+        }
+
+        let mut runes = self.runes;
+        runes.sort_unstable();
+        let mut edicts = Vec::new();
+        for (id, amount) in runes {
+            if amount == 0 {
+                return Err(UnbundleError::ZeroAmount {
+                    rune_id: id.to_string(),
+                });
+            }
+            edicts.push(Edict {
+                id: Arc::new(RuneId::from(id)),
+                amount: Arc::new(RuneAmount(amount)),
+                output: unsigned_tx.output.len() as u32,
+            });
+            push_output(&mut unsigned_tx, &mut dummy_tx, &destination, postage);
+        }
+        if !edicts.is_empty() {
+            let runestone = Runestone {
+                edicts,
+                etching: None,
+                mint: None,
+                pointer: None,
+            }
+            .encipher()?;
+            push_output(&mut unsigned_tx, &mut dummy_tx, &runestone, Amount::ZERO);
+        }
+
+        let outpoint = self.outpoint;
+        fund_psbt(
+            &mut unsigned_tx,
+            &mut psbt_inputs,
+            &mut dummy_tx,
+            Amount::from_sat(value),
+            self.cardinal_utxos
+                .into_iter()
+                .filter(|utxo| OutPoint::from(utxo.outpoint.clone()) != outpoint)
+                .collect(),
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: change_script,
+            },
+            self.fee_rate,
+        )?;
+
+        let o_len = unsigned_tx.output.len();
+        Ok(Psbt {
+            unsigned_tx,
+            version: 0,
+            xpub: Default::default(),
+            proprietary: Default::default(),
+            unknown: Default::default(),
+            inputs: psbt_inputs,
+            outputs: vec![Default::default(); o_len],
+        })
+    }
+}
+
+fn push_output(
+    unsigned_tx: &mut Transaction,
+    dummy_tx: &mut DummyTransaction,
+    script_pubkey: &ScriptBuf,
+    value: Amount,
+) {
+    unsigned_tx.output.push(TxOut {
+        value,
+        script_pubkey: script_pubkey.clone(),
+    });
+    dummy_tx.append_output(script_pubkey.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{address, local_output as utxo};
+
+    fn builder(value: u64, inscription_offsets: Vec<u64>) -> UnbundlePsbtBuilder {
+        let bundle = utxo(0, value);
+        UnbundlePsbtBuilder {
+            outpoint: bundle.outpoint.clone().into(),
+            prevout: (&bundle.txout).into(),
+            inscription_offsets,
+            runes: vec![],
+            cardinal_utxos: vec![],
+            destination: address(),
+            change_addr: address(),
+            postage: None,
+            fee_rate: FeeRate::from_sat_per_vb_unchecked(2),
+        }
+    }
+
+    #[test]
+    fn inscriptions_stay_at_offset_zero() {
+        let id = ordinals::RuneId { block: 2, tx: 1 };
+        let psbt = UnbundlePsbtBuilder {
+            runes: vec![(id, 42)],
+            ..builder(10_000, vec![5_000, 600, 1_200])
+        }
+        .build()
+        .unwrap();
+
+        let tx = &psbt.unsigned_tx;
+        let values = tx
+            .output
+            .iter()
+            .map(|txout| txout.value.to_sat())
+            .collect::<Vec<_>>();
+        // leading sats, 600 merges the sub-dust gap, 1200 splits off its gap, 5000, rune,
+        // runestone, change
+        assert_eq!(&values[..6], &[600, 600, 330, 3_470, 330, 330]);
+        assert_eq!(values.len(), 8);
+
+        let starts = values
+            .iter()
+            .scan(0, |start, value| {
+                let output_start = *start;
+                *start += value;
+                Some(output_start)
+            })
+            .collect::<Vec<_>>();
+        for offset in [600, 1_200, 5_000] {
+            assert!(starts.contains(&offset), "{offset} is not at offset 0");
+        }
+
+        let ordinals::Artifact::Runestone(runestone) = ordinals::Runestone::decipher(tx).unwrap()
+        else {
+            panic!("cenotaph");
+        };
+        assert_eq!(
+            runestone.edicts,
+            vec![ordinals::Edict {
+                id,
+                amount: 42,
+                output: 5
+            }]
+        );
+
+        let fee = Amount::from_sat(10_000 - values.iter().sum::<u64>());
+        assert!(
+            fee >= FeeRate::from_sat_per_vb_unchecked(2)
+                .fee_vb(tx.vsize() as u64)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn last_inscription_is_topped_up() {
+        let psbt = UnbundlePsbtBuilder {
+            cardinal_utxos: vec![utxo(0, 50_000), utxo(1, 50_000)],
+            postage: Some(Amount::from_sat(1_000)),
+            ..builder(400, vec![0])
+        }
+        .build()
+        .unwrap();
+
+        // the bundle itself is never spent twice
+        assert_eq!(psbt.unsigned_tx.input.len(), 2);
+        assert_eq!(psbt.unsigned_tx.input[1].previous_output.vout, 1);
+        assert_eq!(psbt.unsigned_tx.output[0].value, Amount::from_sat(1_000));
+        assert_eq!(psbt.unsigned_tx.output.len(), 2);
+    }
+
+    #[test]
+    fn unbundle_errors() {
+        assert!(matches!(
+            builder(10_000, vec![]).build(),
+            Err(UnbundleError::NothingToUnbundle)
+        ));
+        assert!(matches!(
+            builder(10_000, vec![10_000]).build(),
+            Err(UnbundleError::OffsetOutOfRange {
+                offset: 10_000,
+                value: 10_000
+            })
+        ));
+        assert!(matches!(
+            builder(10_000, vec![100]).build(),
+            Err(UnbundleError::LeadingSatsBelowDust { offset: 100, .. })
+        ));
+        assert!(matches!(
+            builder(10_000, vec![0, 100]).build(),
+            Err(UnbundleError::InscriptionsTooClose {
+                offset: 0,
+                next: 100,
+                ..
+            })
+        ));
+        assert!(matches!(
+            UnbundlePsbtBuilder {
+                postage: Some(Amount::from_sat(100)),
+                ..builder(10_000, vec![0])
+            }
+            .build(),
+            Err(UnbundleError::DustPostage { postage: 100, .. })
+        ));
+        assert!(matches!(
+            UnbundlePsbtBuilder {
+                runes: vec![(ordinals::RuneId { block: 1, tx: 0 }, 0)],
+                ..builder(10_000, vec![])
+            }
+            .build(),
+            Err(UnbundleError::ZeroAmount { .. })
+        ));
+        // two inscriptions need 660 sat plus fees out of 700
+        assert!(matches!(
+            builder(700, vec![0, 350]).build(),
+            Err(UnbundleError::InsufficientFunds { available: 700, .. })
+        ));
+
+        let balance = || crate::ordinal::RuneBalance {
+            rune_id: Arc::new(ordinals::RuneId { block: 1, tx: 0 }.into()),
+            amount: Arc::new(RuneAmount::from(u128::MAX)),
+        };
+        assert!(matches!(
+            crate::ordinal::build_unbundle_psbt(
+                utxo(0, 10_000),
+                vec![],
+                vec![balance(), balance()],
+                vec![],
+                Arc::new(address().into()),
+                Arc::new(address().into()),
+                Arc::new(crate::bitcoin::FeeRate(
+                    bdk_wallet::bitcoin::FeeRate::from_sat_per_vb_unchecked(1)
+                )),
+                None,
+            ),
+            Err(UnbundleError::AmountOverflow { .. })
+        ));
+    }
+}
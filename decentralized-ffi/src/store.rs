@@ -1,15 +1,31 @@
 use crate::error::PersistenceError;
+use crate::labels::{Label, LabelType, merge_labels};
 use crate::types::ChangeSet;
 
-use bdk_wallet::{WalletPersister, rusqlite::Connection as BdkConnection};
+use bdk_wallet::WalletPersister;
+use bdk_wallet::chain::Merge;
+#[cfg(feature = "sqlite")]
+use bdk_wallet::rusqlite::Connection as BdkConnection;
+#[cfg(feature = "sqlite")]
+use encrypted::EncryptedStore;
+use file::FileStore;
+use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "sqlite")]
 use std::ops::DerefMut;
 use std::sync::{Arc, Mutex};
 
+#[cfg(feature = "sqlite")]
+mod encrypted;
+mod file;
+
 /// Definition of a wallet persistence implementation.
 #[uniffi::export(with_foreign)]
 pub trait Persistence: Send + Sync {
     /// Initialize the total aggregate `ChangeSet` for the underlying wallet.
+    ///
+    /// Wallet labels are persisted as `ChangeSet`s of their own, merge them with
+    /// `ChangeSet::from_merge` so they are part of the aggregate.
     fn initialize(&self) -> Result<Arc<ChangeSet>, PersistenceError>;
 
     /// Persist a `ChangeSet` to the total aggregate changeset of the wallet.
@@ -17,8 +33,158 @@ pub trait Persistence: Send + Sync {
 }
 
 pub(crate) enum PersistenceType {
-    Custom(Arc<dyn Persistence>),
+    /// Foreign persistence and the labels of the aggregate it last initialized.
+    Custom {
+        persistence: Arc<dyn Persistence>,
+        labels: Vec<Label>,
+    },
+    #[cfg(feature = "sqlite")]
     Sql(Mutex<BdkConnection>),
+    #[cfg(feature = "sqlite")]
+    Encrypted(Box<EncryptedStore>),
+    File(FileStore),
+}
+
+/// What the native stores save per persisted changeset: a wallet changeset and label changes.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Record {
+    #[serde(default)]
+    pub(crate) changeset: bdk_wallet::ChangeSet,
+    #[serde(default)]
+    pub(crate) labels: Vec<Label>,
+}
+
+impl Merge for Record {
+    fn merge(&mut self, other: Self) {
+        self.changeset.merge(other.changeset);
+        self.labels = merge_labels(std::mem::take(&mut self.labels), other.labels);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.changeset.is_empty() && self.labels.is_empty()
+    }
+}
+
+/// Encoding of a file persister.
+#[derive(uniffi::Enum)]
+pub enum FileFormat {
+    /// Compact binary encoding.
+    Cbor,
+    /// One JSON object per line, for inspecting a wallet while debugging.
+    Json,
+}
+
+#[cfg(feature = "sqlite")]
+/// Key of an encrypted persister.
+#[derive(uniffi::Enum)]
+pub enum EncryptionKey {
+    /// Passphrase stretched with PBKDF2-HMAC-SHA256 and a random salt stored in the database.
+    Passphrase { passphrase: String },
+    /// 32 key bytes used as is.
+    Bytes { key: Vec<u8> },
+}
+
+#[cfg(feature = "sqlite")]
+const LABELS_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS deffi_labels ( \
+    type TEXT NOT NULL, \
+    ref TEXT NOT NULL, \
+    label TEXT, \
+    origin TEXT, \
+    spendable INTEGER, \
+    PRIMARY KEY (type, ref) \
+    ) STRICT";
+
+impl PersistenceType {
+    /// Load the wallet labels stored alongside the wallet `ChangeSet`.
+    ///
+    /// Custom persistence returns them as part of the aggregate `ChangeSet`, they are taken from
+    /// the aggregate loaded with the wallet instead of initializing it again.
+    pub(crate) fn initialize_labels(&mut self) -> Result<Vec<Label>, PersistenceError> {
+        match self {
+            #[cfg(feature = "sqlite")]
+            PersistenceType::Sql(conn) => {
+                let conn = conn.get_mut().unwrap();
+                conn.execute(LABELS_SCHEMA, ())?;
+                let mut statement =
+                    conn.prepare("SELECT type, ref, label, origin, spendable FROM deffi_labels")?;
+                let rows = statement.query_map((), |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        Label {
+                            label_type: LabelType::Tx,
+                            reference: row.get(1)?,
+                            label: row.get(2)?,
+                            origin: row.get(3)?,
+                            spendable: row.get(4)?,
+                        },
+                    ))
+                })?;
+                let mut labels = Vec::new();
+                for row in rows {
+                    let (label_type, label) = row?;
+                    let label_type = LabelType::from_name(&label_type).ok_or_else(|| {
+                        PersistenceError::Reason {
+                            error_message: format!("unknown label type {label_type}"),
+                        }
+                    })?;
+                    labels.push(Label {
+                        label_type,
+                        ..label
+                    });
+                }
+                Ok(labels)
+            }
+            PersistenceType::Custom { labels, .. } => Ok(std::mem::take(labels)),
+            #[cfg(feature = "sqlite")]
+            PersistenceType::Encrypted(store) => Ok(store.aggregate()?.labels),
+            PersistenceType::File(store) => Ok(store.aggregate()?.labels),
+        }
+    }
+
+    /// Persist staged label changes, labels without content are deleted.
+    pub(crate) fn persist_labels(&mut self, labels: Vec<Label>) -> Result<(), PersistenceError> {
+        match self {
+            #[cfg(feature = "sqlite")]
+            PersistenceType::Sql(conn) => {
+                let conn = conn.get_mut().unwrap();
+                let db_tx = conn.transaction()?;
+                db_tx.execute(LABELS_SCHEMA, ())?;
+                for label in labels {
+                    if label.is_empty() {
+                        db_tx.execute(
+                            "DELETE FROM deffi_labels WHERE type = ?1 AND ref = ?2",
+                            (label.label_type.as_str(), &label.reference),
+                        )?;
+                    } else {
+                        db_tx.execute(
+                            "INSERT OR REPLACE INTO deffi_labels \
+                             (type, ref, label, origin, spendable) VALUES (?1, ?2, ?3, ?4, ?5)",
+                            (
+                                label.label_type.as_str(),
+                                &label.reference,
+                                &label.label,
+                                &label.origin,
+                                label.spendable,
+                            ),
+                        )?;
+                    }
+                }
+                Ok(db_tx.commit()?)
+            }
+            PersistenceType::Custom { persistence, .. } => {
+                persistence.persist(Arc::new(ChangeSet::from_labels(labels)))
+            }
+            #[cfg(feature = "sqlite")]
+            PersistenceType::Encrypted(store) => store.persist(&Record {
+                labels,
+                ..Default::default()
+            }),
+            PersistenceType::File(store) => store.persist(&Record {
+                labels,
+                ..Default::default()
+            }),
+        }
+    }
 }
 
 /// Wallet backend implementations.
@@ -27,6 +193,50 @@ pub struct Persister {
     pub(crate) inner: Mutex<PersistenceType>,
 }
 
+#[uniffi::export]
+impl Persister {
+    /// Open or create an append-only changeset log at the specified file path.
+    ///
+    /// Every persisted changeset is appended to the file. After `compact_after` appends, 100 by
+    /// default, the log is rewritten as a single aggregate changeset.
+    #[uniffi::constructor(default(compact_after = None))]
+    pub fn new_file(
+        path: String,
+        format: FileFormat,
+        compact_after: Option<u32>,
+    ) -> Result<Self, PersistenceError> {
+        let store = FileStore::open(
+            path.into(),
+            format,
+            compact_after.unwrap_or(file::COMPACT_AFTER),
+        )?;
+        Ok(Self {
+            inner: PersistenceType::File(store).into(),
+        })
+    }
+
+    /// Rewrite a file log as a single aggregate changeset. Does nothing for other backends.
+    pub fn compact(&self) -> Result<(), PersistenceError> {
+        match &mut *self.inner.lock().unwrap() {
+            PersistenceType::File(store) => store.compact(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Use a native persistence layer.
+    #[uniffi::constructor]
+    pub fn custom(persistence: Arc<dyn Persistence>) -> Self {
+        Self {
+            inner: PersistenceType::Custom {
+                persistence,
+                labels: Vec::new(),
+            }
+            .into(),
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
 #[uniffi::export]
 impl Persister {
     /// Create a new Sqlite connection at the specified file path.
@@ -47,11 +257,29 @@ impl Persister {
         })
     }
 
-    /// Use a native persistence layer.
+    /// Open or create a Sqlite database at the specified file path storing every changeset
+    /// encrypted with `key`.
+    ///
+    /// Fails with `PersistenceError::WrongKey` when the database was created with another key.
     #[uniffi::constructor]
-    pub fn custom(persistence: Arc<dyn Persistence>) -> Self {
-        Self {
-            inner: PersistenceType::Custom(persistence).into(),
+    pub fn new_encrypted_sqlite(
+        path: String,
+        key: EncryptionKey,
+    ) -> Result<Self, PersistenceError> {
+        let store = EncryptedStore::open(BdkConnection::open(path)?, key)?;
+        Ok(Self {
+            inner: PersistenceType::Encrypted(Box::new(store)).into(),
+        })
+    }
+
+    /// Re-encrypt an encrypted database with a new key.
+    ///
+    /// All changesets are re-encrypted in one database transaction, on failure the old key stays
+    /// valid.
+    pub fn rotate_key(&self, key: EncryptionKey) -> Result<(), PersistenceError> {
+        match &mut *self.inner.lock().unwrap() {
+            PersistenceType::Encrypted(store) => store.rotate_key(key),
+            _ => Err(PersistenceError::NotEncrypted),
         }
     }
 }
@@ -61,28 +289,47 @@ impl WalletPersister for PersistenceType {
 
     fn initialize(persister: &mut Self) -> Result<bdk_wallet::ChangeSet, Self::Error> {
         match persister {
+            #[cfg(feature = "sqlite")]
             PersistenceType::Sql(conn) => {
                 let mut lock = conn.lock().unwrap();
                 let deref = lock.deref_mut();
                 Ok(BdkConnection::initialize(deref)?)
             }
-            PersistenceType::Custom(any) => any
-                .initialize()
-                .map(|changeset| changeset.as_ref().clone().into()),
+            PersistenceType::Custom {
+                persistence,
+                labels,
+            } => {
+                let changeset = persistence.initialize()?;
+                *labels = changeset.labels();
+                Ok(changeset.as_ref().clone().into())
+            }
+            #[cfg(feature = "sqlite")]
+            PersistenceType::Encrypted(store) => Ok(store.aggregate()?.changeset),
+            PersistenceType::File(store) => Ok(store.aggregate()?.changeset),
         }
     }
 
     fn persist(persister: &mut Self, changeset: &bdk_wallet::ChangeSet) -> Result<(), Self::Error> {
         match persister {
+            #[cfg(feature = "sqlite")]
             PersistenceType::Sql(conn) => {
                 let mut lock = conn.lock().unwrap();
                 let deref = lock.deref_mut();
                 Ok(BdkConnection::persist(deref, changeset)?)
             }
-            PersistenceType::Custom(any) => {
+            PersistenceType::Custom { persistence, .. } => {
                 let ffi_changeset: ChangeSet = changeset.clone().into();
-                any.persist(Arc::new(ffi_changeset))
+                persistence.persist(Arc::new(ffi_changeset))
             }
+            #[cfg(feature = "sqlite")]
+            PersistenceType::Encrypted(store) => store.persist(&Record {
+                changeset: changeset.clone(),
+                ..Default::default()
+            }),
+            PersistenceType::File(store) => store.persist(&Record {
+                changeset: changeset.clone(),
+                ..Default::default()
+            }),
         }
     }
 }
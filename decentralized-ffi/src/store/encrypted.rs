@@ -0,0 +1,329 @@
+use crate::error::PersistenceError;
+use crate::store::{EncryptionKey, Record};
+
+use bdk_wallet::chain::Merge;
+use bdk_wallet::rusqlite::{Connection, OptionalExtension};
+use ring::aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+
+use std::num::NonZeroU32;
+
+const SCHEMA: [&str; 2] = [
+    "CREATE TABLE IF NOT EXISTS deffi_encryption ( \
+    id INTEGER PRIMARY KEY CHECK (id = 0), \
+    salt BLOB NOT NULL, \
+    iterations INTEGER NOT NULL, \
+    key_check BLOB NOT NULL \
+    ) STRICT",
+    "CREATE TABLE IF NOT EXISTS deffi_encrypted_changesets ( \
+    id INTEGER PRIMARY KEY AUTOINCREMENT, \
+    data BLOB NOT NULL \
+    ) STRICT",
+];
+
+/// PBKDF2-HMAC-SHA256 rounds for passphrases, as recommended by OWASP.
+const PBKDF2_ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+const KEY_CHECK: &[u8] = b"deffi key check";
+const CHANGESET_AAD: &[u8] = b"deffi changeset";
+
+/// Sqlite database holding every changeset encrypted with ChaCha20-Poly1305.
+///
+/// Each persisted changeset is one row of `nonce || ciphertext`. The key is checked against an
+/// encrypted constant when opening, so a wrong key fails right away instead of on the first row.
+pub(crate) struct EncryptedStore {
+    conn: Connection,
+    key: LessSafeKey,
+}
+
+impl EncryptedStore {
+    pub(crate) fn open(mut conn: Connection, key: EncryptionKey) -> Result<Self, PersistenceError> {
+        let db_tx = conn.transaction()?;
+        for statement in SCHEMA {
+            db_tx.execute(statement, ())?;
+        }
+        let stored = db_tx
+            .query_row(
+                "SELECT salt, iterations, key_check FROM deffi_encryption WHERE id = 0",
+                (),
+                |row| {
+                    Ok((
+                        row.get::<_, Vec<u8>>(0)?,
+                        row.get::<_, u32>(1)?,
+                        row.get::<_, Vec<u8>>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        let key = match stored {
+            Some((salt, iterations, key_check)) => {
+                let key = derive_key(&key, &salt, iterations)?;
+                match open_blob(&key, KEY_CHECK, &key_check) {
+                    Some(check) if check == KEY_CHECK => key,
+                    _ => return Err(PersistenceError::WrongKey),
+                }
+            }
+            None => {
+                let (key, salt) = new_key(&key)?;
+                write_key_check(&db_tx, &key, &salt)?;
+                key
+            }
+        };
+        db_tx.commit()?;
+
+        Ok(Self { conn, key })
+    }
+
+    /// Decrypt and merge every stored changeset.
+    pub(crate) fn aggregate(&self) -> Result<Record, PersistenceError> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT data FROM deffi_encrypted_changesets ORDER BY id")?;
+        let rows = statement.query_map((), |row| row.get::<_, Vec<u8>>(0))?;
+
+        let mut aggregate = Record::default();
+        for row in rows {
+            let plaintext = open_blob(&self.key, CHANGESET_AAD, &row?).ok_or_else(|| {
+                PersistenceError::Reason {
+                    error_message: "encrypted changeset is corrupted".to_string(),
+                }
+            })?;
+            let record: Record = ciborium::from_reader(plaintext.as_slice()).map_err(|e| {
+                PersistenceError::Reason {
+                    error_message: e.to_string(),
+                }
+            })?;
+            aggregate.merge(record);
+        }
+        Ok(aggregate)
+    }
+
+    pub(crate) fn persist(&mut self, record: &Record) -> Result<(), PersistenceError> {
+        if record.is_empty() {
+            return Ok(());
+        }
+        let mut plaintext = Vec::new();
+        ciborium::into_writer(record, &mut plaintext).map_err(|e| PersistenceError::Reason {
+            error_message: e.to_string(),
+        })?;
+        self.conn.execute(
+            "INSERT INTO deffi_encrypted_changesets (data) VALUES (?1)",
+            [seal_blob(&self.key, CHANGESET_AAD, plaintext)?],
+        )?;
+        Ok(())
+    }
+
+    /// Re-encrypt every changeset with `key`, atomically.
+    pub(crate) fn rotate_key(&mut self, key: EncryptionKey) -> Result<(), PersistenceError> {
+        let (new_key, salt) = new_key(&key)?;
+        let db_tx = self.conn.transaction()?;
+        let rows = {
+            let mut statement = db_tx.prepare("SELECT id, data FROM deffi_encrypted_changesets")?;
+            statement
+                .query_map((), |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        for (id, data) in rows {
+            let plaintext = open_blob(&self.key, CHANGESET_AAD, &data).ok_or_else(|| {
+                PersistenceError::Reason {
+                    error_message: "encrypted changeset is corrupted".to_string(),
+                }
+            })?;
+            db_tx.execute(
+                "UPDATE deffi_encrypted_changesets SET data = ?1 WHERE id = ?2",
+                (seal_blob(&new_key, CHANGESET_AAD, plaintext)?, id),
+            )?;
+        }
+        write_key_check(&db_tx, &new_key, &salt)?;
+        db_tx.commit()?;
+
+        self.key = new_key;
+        Ok(())
+    }
+}
+
+fn new_key(key: &EncryptionKey) -> Result<(LessSafeKey, [u8; SALT_LEN]), PersistenceError> {
+    let mut salt = [0; SALT_LEN];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| PersistenceError::Reason {
+            error_message: "no randomness available".to_string(),
+        })?;
+    Ok((derive_key(key, &salt, PBKDF2_ITERATIONS)?, salt))
+}
+
+fn derive_key(
+    key: &EncryptionKey,
+    salt: &[u8],
+    iterations: u32,
+) -> Result<LessSafeKey, PersistenceError> {
+    let mut key_bytes = [0; KEY_LEN];
+    match key {
+        EncryptionKey::Passphrase { passphrase } => {
+            let iterations = NonZeroU32::new(iterations).ok_or(PersistenceError::InvalidKey {
+                error_message: "zero PBKDF2 iterations".to_string(),
+            })?;
+            pbkdf2::derive(
+                pbkdf2::PBKDF2_HMAC_SHA256,
+                iterations,
+                salt,
+                passphrase.as_bytes(),
+                &mut key_bytes,
+            );
+        }
+        EncryptionKey::Bytes { key } => {
+            key_bytes = key
+                .as_slice()
+                .try_into()
+                .map_err(|_| PersistenceError::InvalidKey {
+                    error_message: format!("expected {KEY_LEN} key bytes, got {}", key.len()),
+                })?;
+        }
+    }
+    let key = UnboundKey::new(&CHACHA20_POLY1305, &key_bytes).expect("key length");
+    Ok(LessSafeKey::new(key))
+}
+
+fn write_key_check(
+    conn: &Connection,
+    key: &LessSafeKey,
+    salt: &[u8],
+) -> Result<(), PersistenceError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO deffi_encryption (id, salt, iterations, key_check) \
+         VALUES (0, ?1, ?2, ?3)",
+        (
+            salt,
+            PBKDF2_ITERATIONS,
+            seal_blob(key, KEY_CHECK, KEY_CHECK.to_vec())?,
+        ),
+    )?;
+    Ok(())
+}
+
+fn seal_blob(
+    key: &LessSafeKey,
+    aad: &[u8],
+    mut data: Vec<u8>,
+) -> Result<Vec<u8>, PersistenceError> {
+    let mut nonce = [0; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| PersistenceError::Reason {
+            error_message: "no randomness available".to_string(),
+        })?;
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(aad),
+        &mut data,
+    )
+    .map_err(|_| PersistenceError::Reason {
+        error_message: "encryption failed".to_string(),
+    })?;
+    Ok([nonce.as_slice(), &data].concat())
+}
+
+fn open_blob(key: &LessSafeKey, aad: &[u8], blob: &[u8]) -> Option<Vec<u8>> {
+    let (nonce, ciphertext) = blob.split_at_checked(NONCE_LEN)?;
+    let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+    let mut data = ciphertext.to_vec();
+    let plaintext = key.open_in_place(nonce, Aad::from(aad), &mut data).ok()?;
+    Some(plaintext.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Persister;
+    use crate::testing::{TPRV, create_labeled_wallet, load_labeled_wallet};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "deffi-encrypted-{name}-{}.sqlite",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+
+        fn open(&self, key: EncryptionKey) -> Result<Arc<Persister>, PersistenceError> {
+            Persister::new_encrypted_sqlite(self.0.to_string_lossy().into_owned(), key)
+                .map(Arc::new)
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn key(byte: u8) -> EncryptionKey {
+        EncryptionKey::Bytes {
+            key: vec![byte; KEY_LEN],
+        }
+    }
+
+    #[test]
+    fn wallet_round_trip() {
+        let db = TempDb::new("round-trip");
+        let address = create_labeled_wallet(db.open(key(1)).unwrap());
+        load_labeled_wallet(db.open(key(1)).unwrap(), &address);
+
+        let raw = std::fs::read(&db.0).unwrap();
+        let contains = |needle: &str| {
+            raw.windows(needle.len())
+                .any(|window| window == needle.as_bytes())
+        };
+        assert!(!contains(&TPRV[..20]));
+        assert!(!contains(&address));
+        assert!(!contains("Deposit"));
+    }
+
+    #[test]
+    fn wrong_key() {
+        let db = TempDb::new("wrong-key");
+        create_labeled_wallet(db.open(key(1)).unwrap());
+
+        assert!(matches!(db.open(key(2)), Err(PersistenceError::WrongKey)));
+        assert!(matches!(
+            db.open(EncryptionKey::Bytes { key: vec![1; 16] }),
+            Err(PersistenceError::InvalidKey { .. })
+        ));
+        assert!(matches!(
+            Persister::new_in_memory().unwrap().rotate_key(key(2)),
+            Err(PersistenceError::NotEncrypted)
+        ));
+    }
+
+    #[test]
+    fn rotate_key() {
+        let db = TempDb::new("rotate");
+        let passphrase = |passphrase: &str| EncryptionKey::Passphrase {
+            passphrase: passphrase.into(),
+        };
+        let address = create_labeled_wallet(db.open(passphrase("old")).unwrap());
+
+        db.open(passphrase("old"))
+            .unwrap()
+            .rotate_key(key(3))
+            .unwrap();
+
+        assert!(matches!(
+            db.open(passphrase("old")),
+            Err(PersistenceError::WrongKey)
+        ));
+        load_labeled_wallet(db.open(key(3)).unwrap(), &address);
+    }
+}
@@ -0,0 +1,355 @@
+use crate::error::PersistenceError;
+use crate::store::{FileFormat, Record};
+
+use bdk_wallet::chain::Merge;
+use bdk_wallet::serde_json;
+use ring::digest::{SHA256, digest};
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Compact the log after this many appended changesets unless told otherwise.
+pub(crate) const COMPACT_AFTER: u32 = 100;
+
+/// Length and checksum preceding every CBOR entry.
+const FRAME_LEN: usize = 8;
+
+impl FileFormat {
+    fn header(&self) -> &'static [u8] {
+        match self {
+            FileFormat::Cbor => b"deffi changesets v1 cbor\n",
+            FileFormat::Json => b"deffi changesets v1 json\n",
+        }
+    }
+
+    fn encode(&self, record: &Record) -> Result<Vec<u8>, PersistenceError> {
+        match self {
+            FileFormat::Cbor => {
+                let mut payload = Vec::new();
+                ciborium::into_writer(record, &mut payload).map_err(|e| {
+                    PersistenceError::Reason {
+                        error_message: e.to_string(),
+                    }
+                })?;
+                let mut entry = Vec::with_capacity(FRAME_LEN + payload.len());
+                entry.extend((payload.len() as u32).to_be_bytes());
+                entry.extend(checksum(&payload));
+                entry.extend(payload);
+                Ok(entry)
+            }
+            FileFormat::Json => {
+                let mut entry =
+                    serde_json::to_vec(record).map_err(|e| PersistenceError::Reason {
+                        error_message: e.to_string(),
+                    })?;
+                entry.push(b'\n');
+                Ok(entry)
+            }
+        }
+    }
+
+    /// Decode the entries of a log without its header.
+    ///
+    /// Returns the records and the length of the entries that were complete. Anything after that
+    /// is a write torn by a crash.
+    fn decode(&self, mut data: &[u8]) -> Result<(Vec<Record>, usize), PersistenceError> {
+        let mut records = Vec::new();
+        let mut complete = 0;
+        loop {
+            let (payload, len) = match self {
+                FileFormat::Cbor => {
+                    let Some((frame, rest)) = data.split_at_checked(FRAME_LEN) else {
+                        break;
+                    };
+                    let len = u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize;
+                    match rest.get(..len) {
+                        Some(payload) if checksum(payload) == frame[4..] => {
+                            (payload, FRAME_LEN + len)
+                        }
+                        _ => break,
+                    }
+                }
+                FileFormat::Json => {
+                    let Some(end) = data.iter().position(|byte| *byte == b'\n') else {
+                        break;
+                    };
+                    (&data[..end], end + 1)
+                }
+            };
+            let record = match self {
+                FileFormat::Cbor => ciborium::from_reader(payload).map_err(|e| e.to_string()),
+                FileFormat::Json => serde_json::from_slice(payload).map_err(|e| e.to_string()),
+            }
+            .map_err(|error_message| PersistenceError::Reason { error_message })?;
+            records.push(record);
+            complete += len;
+            data = &data[len..];
+        }
+        Ok((records, complete))
+    }
+}
+
+/// Append-only log of changesets.
+///
+/// Every persisted changeset is appended and synced as one entry. A crash in the middle of an
+/// append leaves an incomplete last entry which is dropped when the log is opened again. Once
+/// `compact_after` entries accumulate they are replaced by their aggregate, written to a temporary
+/// file and renamed over the log.
+pub(crate) struct FileStore {
+    path: PathBuf,
+    format: FileFormat,
+    file: File,
+    len: u64,
+    entries: u32,
+    compact_after: u32,
+}
+
+impl FileStore {
+    pub(crate) fn open(
+        path: PathBuf,
+        format: FileFormat,
+        compact_after: u32,
+    ) -> Result<Self, PersistenceError> {
+        if !path.exists() {
+            write_atomic(&path, format.header())?;
+        }
+        let (records, len) = read_log(&path, &format)?;
+        let file = OpenOptions::new().append(true).open(&path)?;
+        if file.metadata()?.len() > len {
+            file.set_len(len)?;
+            file.sync_all()?;
+        }
+        Ok(Self {
+            path,
+            format,
+            file,
+            len,
+            entries: records.len() as u32,
+            compact_after: compact_after.max(1),
+        })
+    }
+
+    /// Read and merge every entry of the log.
+    pub(crate) fn aggregate(&self) -> Result<Record, PersistenceError> {
+        let (records, _) = read_log(&self.path, &self.format)?;
+        let mut aggregate = Record::default();
+        for record in records {
+            aggregate.merge(record);
+        }
+        Ok(aggregate)
+    }
+
+    pub(crate) fn persist(&mut self, record: &Record) -> Result<(), PersistenceError> {
+        if record.is_empty() {
+            return Ok(());
+        }
+        let entry = self.format.encode(record)?;
+        if let Err(e) = self
+            .file
+            .write_all(&entry)
+            .and_then(|_| self.file.sync_data())
+        {
+            // Do not leave a partial entry for the next append to follow.
+            let _ = self.file.set_len(self.len);
+            return Err(e.into());
+        }
+        self.len += entry.len() as u64;
+        self.entries += 1;
+
+        if self.entries > self.compact_after {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Replace the log with a single entry holding the aggregate of all entries.
+    pub(crate) fn compact(&mut self) -> Result<(), PersistenceError> {
+        let mut aggregate = self.aggregate()?;
+        // Nothing is left for removed labels to override.
+        aggregate.labels.retain(|label| !label.is_empty());
+        let mut data = self.format.header().to_vec();
+        if !aggregate.is_empty() {
+            data.extend(self.format.encode(&aggregate)?);
+        }
+        write_atomic(&self.path, &data)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.len = data.len() as u64;
+        self.entries = u32::from(!aggregate.is_empty());
+        Ok(())
+    }
+}
+
+fn read_log(path: &Path, format: &FileFormat) -> Result<(Vec<Record>, u64), PersistenceError> {
+    let data = fs::read(path)?;
+    let header = format.header();
+    let Some(entries) = data.strip_prefix(header) else {
+        return Err(PersistenceError::Reason {
+            error_message: format!(
+                "{} is not a {} changeset log",
+                path.display(),
+                String::from_utf8_lossy(&header[..header.len() - 1])
+            ),
+        });
+    };
+    let (records, len) = format.decode(entries)?;
+    Ok((records, (header.len() + len) as u64))
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    digest(&SHA256, payload).as_ref()[..4].try_into().unwrap()
+}
+
+/// Write `data` to a temporary file next to `path` and rename it over `path`.
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), PersistenceError> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    // Make the rename itself durable.
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::labels::{Label, LabelType};
+    use crate::store::Persister;
+    use crate::testing::{create_labeled_wallet, load_labeled_wallet};
+    use std::sync::Arc;
+
+    struct TempLog(PathBuf);
+
+    impl TempLog {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("deffi-file-{name}-{}.log", std::process::id()));
+            let _ = fs::remove_file(&path);
+            Self(path)
+        }
+
+        fn open(
+            &self,
+            format: FileFormat,
+            compact_after: u32,
+        ) -> Result<FileStore, PersistenceError> {
+            FileStore::open(self.0.clone(), format, compact_after)
+        }
+    }
+
+    impl Drop for TempLog {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn label(reference: &str, text: Option<&str>) -> Record {
+        Record {
+            labels: vec![Label {
+                label_type: LabelType::Tx,
+                reference: reference.into(),
+                label: text.map(Into::into),
+                origin: None,
+                spendable: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn texts(store: &FileStore) -> Vec<(String, Option<String>)> {
+        let mut labels = store.aggregate().unwrap().labels;
+        labels.sort_by(|a, b| a.reference.cmp(&b.reference));
+        labels
+            .into_iter()
+            .map(|label| (label.reference, label.label))
+            .collect()
+    }
+
+    #[test]
+    fn wallet_round_trip() {
+        for (name, format) in [("cbor", FileFormat::Cbor), ("json", FileFormat::Json)] {
+            let log = TempLog::new(&format!("wallet-{name}"));
+            let path = log.0.to_string_lossy().into_owned();
+            let persister = Arc::new(Persister::new_file(path.clone(), format, None).unwrap());
+            let address = create_labeled_wallet(persister.clone());
+            persister.compact().unwrap();
+
+            let format = if name == "json" {
+                let text = fs::read_to_string(&log.0).unwrap();
+                let mut lines = text.lines();
+                assert_eq!(lines.next(), Some("deffi changesets v1 json"));
+                let entry: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+                assert_eq!(entry["labels"][0]["label"], "Deposit");
+                assert_eq!(lines.next(), None);
+                FileFormat::Json
+            } else {
+                FileFormat::Cbor
+            };
+
+            let persister = Arc::new(Persister::new_file(path, format, None).unwrap());
+            load_labeled_wallet(persister, &address);
+        }
+    }
+
+    #[test]
+    fn compact_periodically() {
+        let log = TempLog::new("compact");
+        let mut store = log.open(FileFormat::Cbor, 2).unwrap();
+        store.persist(&label("a", Some("first"))).unwrap();
+        store.persist(&label("b", Some("second"))).unwrap();
+        assert_eq!(store.entries, 2);
+        store.persist(&label("a", None)).unwrap();
+        assert_eq!(store.entries, 1);
+        assert_eq!(store.len, fs::metadata(&log.0).unwrap().len());
+
+        let store = log.open(FileFormat::Cbor, 2).unwrap();
+        assert_eq!(store.entries, 1);
+        assert_eq!(texts(&store), vec![("b".into(), Some("second".into()))]);
+    }
+
+    #[test]
+    fn drop_torn_entry() {
+        for (name, format) in [("cbor", FileFormat::Cbor), ("json", FileFormat::Json)] {
+            let log = TempLog::new(&format!("torn-{name}"));
+            let mut store = log.open(format, COMPACT_AFTER).unwrap();
+            store.persist(&label("a", Some("kept"))).unwrap();
+            let len = store.len;
+
+            let entry = store.format.encode(&label("b", Some("torn"))).unwrap();
+            let mut file = OpenOptions::new().append(true).open(&log.0).unwrap();
+            file.write_all(&entry[..entry.len() - 1]).unwrap();
+            drop(file);
+
+            let mut store = log.open(store.format, COMPACT_AFTER).unwrap();
+            assert_eq!(fs::metadata(&log.0).unwrap().len(), len);
+            assert_eq!(texts(&store), vec![("a".into(), Some("kept".into()))]);
+
+            store.persist(&label("c", Some("appended"))).unwrap();
+            let store = log.open(store.format, COMPACT_AFTER).unwrap();
+            assert_eq!(store.entries, 2);
+        }
+    }
+
+    #[test]
+    fn reject_other_format() {
+        let log = TempLog::new("format");
+        let mut store = log.open(FileFormat::Json, COMPACT_AFTER).unwrap();
+        store.persist(&label("a", Some("kept"))).unwrap();
+        let data = fs::read(&log.0).unwrap();
+
+        assert!(matches!(
+            log.open(FileFormat::Cbor, COMPACT_AFTER),
+            Err(PersistenceError::Reason { .. })
+        ));
+        assert_eq!(fs::read(&log.0).unwrap(), data);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::bitcoin::{Amount, OutPoint, Script, TxOut, Txid};
+use crate::descriptor::Descriptor;
+use crate::error::PersistenceError;
+use crate::labels::{Label, LabelType};
+use crate::store::{Persistence, Persister};
+use crate::types::{ChainPosition, ChangeSet, LocalOutput};
+use crate::wallet::Wallet;
+use bdk_wallet::KeychainKind;
+use bdk_wallet::bitcoin::hashes::Hash;
+use bdk_wallet::bitcoin::{self, Address, Network};
+use std::future::Future;
+use std::pin::pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+pub(crate) const TAPROOT_ADDRESS: &str =
+    "bc1pfuqd6gadnlycmyas8nc8zgads69uhzhejjvx8epenqa7pcfxqtkqyq6666";
+
+pub(crate) fn address() -> Address {
+    Address::from_str(TAPROOT_ADDRESS)
+        .unwrap()
+        .require_network(Network::Bitcoin)
+        .unwrap()
+}
+
+pub(crate) fn local_output(vout: u32, value: u64) -> LocalOutput {
+    LocalOutput {
+        outpoint: OutPoint {
+            txid: Arc::new(Txid(bitcoin::Txid::all_zeros())),
+            vout,
+        },
+        txout: TxOut {
+            value: Arc::new(Amount(bitcoin::Amount::from_sat(value))),
+            script_pubkey: Arc::new(Script(address().script_pubkey())),
+            serialize_hex: String::new(),
+        },
+        keychain: KeychainKind::External,
+        is_spent: false,
+        derivation_index: 0,
+        chain_position: ChainPosition::Unconfirmed { timestamp: None },
+        label: None,
+    }
+}
+
+/// Custom persistence keeping the aggregate `ChangeSet` in memory, available without sqlite.
+#[derive(Default)]
+pub(crate) struct MemoryPersistence {
+    aggregate: Mutex<Option<Arc<ChangeSet>>>,
+    initializations: AtomicU32,
+}
+
+impl MemoryPersistence {
+    /// How many times the aggregate was initialized.
+    pub(crate) fn initializations(&self) -> u32 {
+        self.initializations.load(Ordering::Relaxed)
+    }
+}
+
+impl Persistence for MemoryPersistence {
+    fn initialize(&self) -> Result<Arc<ChangeSet>, PersistenceError> {
+        self.initializations.fetch_add(1, Ordering::Relaxed);
+        Ok(self
+            .aggregate
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| Arc::new(ChangeSet::new())))
+    }
+
+    fn persist(&self, changeset: Arc<ChangeSet>) -> Result<(), PersistenceError> {
+        let mut aggregate = self.aggregate.lock().unwrap();
+        *aggregate = Some(match aggregate.take() {
+            Some(aggregate) => Arc::new(ChangeSet::from_merge(aggregate, changeset)),
+            None => changeset,
+        });
+        Ok(())
+    }
+}
+
+/// Persister backed by a fresh [`MemoryPersistence`].
+pub(crate) fn memory_persister() -> Arc<Persister> {
+    Arc::new(Persister::custom(Arc::new(MemoryPersistence::default())))
+}
+
+pub(crate) const TPRV: &str = "tprv8ZgxMBicQKsPeitVUz3s6cfyCECovNP7t82FaKPa4UKqV1kssWcXgLkMDjzDbgG9GWoza4pL7z727QitfzkiwX99E1Has3T3a1MKHvYWmQZ";
+
+/// Signet taproot descriptor of `keychain` of the test wallet.
+pub(crate) fn wallet_descriptor(keychain: u32) -> Arc<Descriptor> {
+    Arc::new(
+        Descriptor::new(
+            format!("tr({TPRV}/86'/1'/0'/{keychain}/*)"),
+            Network::Signet,
+        )
+        .unwrap(),
+    )
+}
+
+/// Create the test wallet in `persister` with its first address labeled `Deposit`, returns the
+/// address.
+pub(crate) fn create_labeled_wallet(persister: Arc<Persister>) -> String {
+    let wallet = Wallet::new(
+        wallet_descriptor(0),
+        wallet_descriptor(1),
+        Network::Signet,
+        persister.clone(),
+        25,
+    )
+    .unwrap();
+    let address = wallet.reveal_next_address(KeychainKind::External).address;
+    wallet
+        .set_label(Label {
+            label_type: LabelType::Addr,
+            reference: address.to_string(),
+            label: Some("Deposit".into()),
+            origin: None,
+            spendable: None,
+        })
+        .unwrap();
+    assert!(wallet.persist(persister).unwrap());
+    address.to_string()
+}
+
+/// Load the wallet of [`create_labeled_wallet`] from `persister` and check it is intact.
+pub(crate) fn load_labeled_wallet(persister: Arc<Persister>, address: &str) -> Wallet {
+    let wallet = Wallet::load(wallet_descriptor(0), Some(wallet_descriptor(1)), persister).unwrap();
+    assert_eq!(wallet.reveal_next_address(KeychainKind::External).index, 1);
+    assert_eq!(
+        wallet
+            .get_label(LabelType::Addr, address.into())
+            .and_then(|label| label.label),
+        Some("Deposit".into())
+    );
+    wallet
+}
+
+/// Signet taproot wallet holding one unconfirmed output of each of `values`.
+pub(crate) fn funded_wallet(values: &[u64]) -> Arc<Wallet> {
+    let wallet = Wallet::new(
+        wallet_descriptor(0),
+        wallet_descriptor(1),
+        Network::Signet,
+        memory_persister(),
+        25,
+    )
+    .unwrap();
+
+    let mut inner = wallet.get_wallet();
+    let funding = bitcoin::Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![bitcoin::TxIn {
+            previous_output: bitcoin::OutPoint {
+                txid: bitcoin::Txid::all_zeros(),
+                vout: 0,
+            },
+            ..Default::default()
+        }],
+        output: values
+            .iter()
+            .map(|value| bitcoin::TxOut {
+                value: bitcoin::Amount::from_sat(*value),
+                script_pubkey: inner
+                    .reveal_next_address(KeychainKind::External)
+                    .script_pubkey(),
+            })
+            .collect(),
+    };
+    inner.apply_unconfirmed_txs([(funding, 0)]);
+    drop(inner);
+
+    Arc::new(wallet)
+}
+
+/// 2-of-3 P2WSH multisig descriptor, far heavier to satisfy than a single key spend.
+pub(crate) fn multisig_descriptor() -> Arc<Descriptor> {
+    Arc::new(
+        Descriptor::new(
+            format!("wsh(multi(2,{TPRV}/0/*,{TPRV}/1/*,{TPRV}/2/*))"),
+            Network::Signet,
+        )
+        .unwrap(),
+    )
+}
+
+/// Drive a future that never yields, like the builders in this module.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::bitcoin::{Amount, FeeRate, OutPoint, Psbt, Script, Txid};
 use crate::error::CreateTxError;
+use crate::ordinal::tracker::OrdinalTracker;
 use crate::types::{LockTime, ScriptAmount};
 use crate::wallet::Wallet;
 
@@ -51,6 +52,7 @@ pub struct TxBuilder {
     allow_dust: bool,
     version: Option<i32>,
     ordering: Option<TxOrdering>,
+    ordinal_tracker: Option<Arc<OrdinalTracker>>,
 }
 
 #[uniffi::export]
@@ -77,6 +79,7 @@ impl TxBuilder {
             allow_dust: false,
             version: None,
             ordering: None,
+            ordinal_tracker: None,
         }
     }
 
@@ -131,6 +134,7 @@ impl TxBuilder {
     /// Replace the internal list of unspendable utxos with a new list.
     ///
     /// It’s important to note that the "must-be-spent" utxos added with `TxBuilder::add_utxo` have priority over these.
+    /// Utxos frozen with `Wallet::freeze_utxo` are unspendable in addition to these.
     pub fn unspendable(&self, unspendable: Vec<OutPoint>) -> Arc<Self> {
         let new_unspendable_vec: Vec<BdkOutPoint> =
             unspendable.into_iter().map(BdkOutPoint::from).collect();
@@ -141,6 +145,16 @@ impl TxBuilder {
         })
     }
 
+    /// Treat the outputs `tracker` knows to hold inscriptions or runes as unspendable when finishing.
+    ///
+    /// Utxos added with `TxBuilder::add_utxo` are still spent.
+    pub fn protect_ordinals(&self, tracker: Arc<OrdinalTracker>) -> Arc<Self> {
+        Arc::new(TxBuilder {
+            ordinal_tracker: Some(tracker),
+            ..self.clone()
+        })
+    }
+
     /// Add a utxo to the internal list of utxos that must be spent.
     ///
     /// These have priority over the "unspendable" utxos, meaning that if a utxo is present both in the "utxos" and the
@@ -361,6 +375,7 @@ impl TxBuilder {
     /// WARNING: To avoid change address reuse you must persist the changes resulting from one or more calls to this
     /// method before closing the wallet. See `Wallet::reveal_next_address`.
     pub fn finish(&self, wallet: &Arc<Wallet>) -> Result<Arc<Psbt>, CreateTxError> {
+        let frozen = wallet.get_labels().frozen();
         // TODO: I had to change the wallet here to be mutable. Why is that now required with the 1.0 API?
         let mut wallet = wallet.get_wallet();
         let mut tx_builder = wallet.build_tx();
@@ -382,8 +397,13 @@ impl TxBuilder {
                 .add_utxos(&self.utxos)
                 .map_err(CreateTxError::from)?;
         }
-        if !self.unspendable.is_empty() {
-            tx_builder.unspendable(self.unspendable.clone());
+        let mut unspendable = self.unspendable.clone();
+        unspendable.extend(frozen);
+        if let Some(tracker) = &self.ordinal_tracker {
+            unspendable.extend(tracker.non_cardinal());
+        }
+        if !unspendable.is_empty() {
+            tx_builder.unspendable(unspendable);
         }
         if self.manually_selected_only {
             tx_builder.manually_selected_only();
@@ -535,11 +555,15 @@ impl BumpFeeTxBuilder {
     /// WARNING: To avoid change address reuse you must persist the changes resulting from one or more calls to this
     /// method before closing the wallet. See `Wallet::reveal_next_address`.
     pub fn finish(&self, wallet: &Arc<Wallet>) -> Result<Arc<Psbt>, CreateTxError> {
+        let frozen = wallet.get_labels().frozen();
         let mut wallet = wallet.get_wallet();
         let mut tx_builder = wallet
             .build_fee_bump(self.txid.0)
             .map_err(CreateTxError::from)?;
         tx_builder.fee_rate(self.fee_rate.0);
+        if !frozen.is_empty() {
+            tx_builder.unspendable(frozen.into_iter().collect());
+        }
         if let Some(sequence) = self.sequence {
             tx_builder.set_exact_sequence(Sequence(sequence));
         }
@@ -580,7 +604,7 @@ pub enum ChangeSpendPolicy {
 mod tests {
     use crate::bitcoin::{Amount, Script};
     use crate::{
-        descriptor::Descriptor, esplora::EsploraClient, store::Persister,
+        descriptor::Descriptor, esplora::EsploraClient, testing::memory_persister,
         types::FullScanScriptInspector, wallet::Wallet,
     };
     use bdk_wallet::bitcoin::Network;
@@ -630,6 +654,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn frozen_utxos_are_not_selected() {
+        use crate::testing::{address, funded_wallet};
+        use crate::tx_builder::TxBuilder;
+
+        let wallet = funded_wallet(&[10_000, 20_000]);
+        let frozen = wallet
+            .list_unspent()
+            .into_iter()
+            .find(|utxo| utxo.txout.value.to_sat() == 20_000)
+            .unwrap();
+        wallet.freeze_utxo(frozen.outpoint.clone());
+        assert_eq!(wallet.list_frozen(), vec![frozen.outpoint.clone()]);
+
+        let balance = wallet.balance();
+        assert_eq!(balance.frozen.to_sat(), 20_000);
+        assert_eq!(balance.untrusted_pending.to_sat(), 10_000);
+        assert_eq!(balance.total.to_sat(), 30_000);
+
+        let drain = || {
+            let psbt = TxBuilder::new()
+                .drain_wallet()
+                .drain_to(&Script(address().script_pubkey()))
+                .finish(&wallet)
+                .unwrap();
+            psbt.0.lock().unwrap().unsigned_tx.input.len()
+        };
+        assert_eq!(drain(), 1);
+
+        wallet.unfreeze_utxo(frozen.outpoint);
+        assert!(wallet.list_frozen().is_empty());
+        assert_eq!(wallet.balance().frozen.to_sat(), 0);
+        assert_eq!(drain(), 2);
+    }
+
     fn create_and_sync_wallet() -> Wallet {
         let external_descriptor = format!(
             "wsh(thresh(2,pk({}/0/*),sj:and_v(v:pk({}/0/*),n:older(6)),snj:and_v(v:pk({}/0/*),after(630000))))",
@@ -647,7 +706,7 @@ mod tests {
             Arc::new(Descriptor::new(external_descriptor, Network::Signet).unwrap()),
             Arc::new(Descriptor::new(internal_descriptor, Network::Signet).unwrap()),
             Network::Signet,
-            Arc::new(Persister::new_in_memory().unwrap()),
+            memory_persister(),
             25,
         )
         .unwrap();
@@ -4,6 +4,7 @@ use crate::bitcoin::{
 };
 use crate::descriptor::Descriptor;
 use crate::error::{CreateTxError, RequestBuilderError};
+use crate::labels::{merge_labels, Label};
 
 use bdk_wallet::bitcoin::absolute::LockTime as BdkLockTime;
 use bdk_wallet::bitcoin::consensus::encode::serialize_hex;
@@ -77,6 +78,24 @@ pub enum Network {
     Regtest,
 }
 
+type AddressType = bdk_wallet::bitcoin::AddressType;
+
+/// The different types of addresses.
+#[uniffi::remote(Enum)]
+#[non_exhaustive]
+pub enum AddressType {
+    /// Pay to pubkey hash.
+    P2pkh,
+    /// Pay to script hash.
+    P2sh,
+    /// Pay to witness pubkey hash.
+    P2wpkh,
+    /// Pay to witness script hash.
+    P2wsh,
+    /// Pay to taproot.
+    P2tr,
+}
+
 pub type KeychainKind = bdk_wallet::KeychainKind;
 
 /// Types of keychains.
@@ -232,6 +251,8 @@ impl From<BdkAddressInfo> for AddressInfo {
 }
 
 /// Balance, differentiated into various categories.
+///
+/// Frozen UTXOs only count towards `frozen` and `total`.
 #[derive(uniffi::Record)]
 pub struct Balance {
     /// All coinbase outputs not yet matured
@@ -249,6 +270,8 @@ pub struct Balance {
     pub trusted_spendable: Arc<Amount>,
     /// Get the whole balance visible to the wallet.
     pub total: Arc<Amount>,
+    /// UTXOs frozen with `Wallet::freeze_utxo`.
+    pub frozen: Arc<Amount>,
 }
 
 impl From<BdkBalance> for Balance {
@@ -260,6 +283,7 @@ impl From<BdkBalance> for Balance {
             confirmed: Arc::new(bdk_balance.confirmed.into()),
             trusted_spendable: Arc::new(bdk_balance.trusted_spendable().into()),
             total: Arc::new(bdk_balance.total().into()),
+            frozen: Arc::new(Amount(bdk_wallet::bitcoin::Amount::ZERO)),
         }
     }
 }
@@ -279,6 +303,8 @@ pub struct LocalOutput {
     pub derivation_index: u32,
     /// The position of the output in the blockchain.
     pub chain_position: ChainPosition,
+    /// BIP-329 label of the output.
+    pub label: Option<String>,
 }
 
 impl From<BdkLocalOutput> for LocalOutput {
@@ -298,6 +324,7 @@ impl From<BdkLocalOutput> for LocalOutput {
             is_spent: local_utxo.is_spent,
             derivation_index: local_utxo.derivation_index,
             chain_position: local_utxo.chain_position.into(),
+            label: None,
         }
     }
 }
@@ -1069,6 +1096,7 @@ pub struct ChangeSet {
     local_chain: LocalChainChangeSet,
     tx_graph: TxGraphChangeSet,
     indexer: IndexerChangeSet,
+    labels: Vec<Label>,
 }
 
 #[uniffi::export]
@@ -1096,6 +1124,7 @@ impl ChangeSet {
             local_chain,
             tx_graph,
             indexer,
+            labels: Vec::new(),
         }
     }
 
@@ -1112,6 +1141,16 @@ impl ChangeSet {
             local_chain: LocalChainChangeSet::default(),
             tx_graph: TxGraphChangeSet::default(),
             indexer: IndexerChangeSet::default(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// Start a wallet `ChangeSet` from label changes.
+    #[uniffi::constructor]
+    pub fn from_labels(labels: Vec<Label>) -> Self {
+        Self {
+            labels,
+            ..bdk_wallet::ChangeSet::default().into()
         }
     }
 
@@ -1143,10 +1182,14 @@ impl ChangeSet {
     /// Build a `ChangeSet` by merging together two `ChangeSet`.
     #[uniffi::constructor]
     pub fn from_merge(left: Arc<ChangeSet>, right: Arc<ChangeSet>) -> Self {
+        let labels = merge_labels(left.labels.clone(), right.labels.clone());
         let mut left: bdk_wallet::ChangeSet = left.as_ref().clone().into();
         let right: bdk_wallet::ChangeSet = right.as_ref().clone().into();
         left.merge(right);
-        left.into()
+        Self {
+            labels,
+            ..left.into()
+        }
     }
 
     /// Get the receiving `Descriptor`.
@@ -1178,6 +1221,11 @@ impl ChangeSet {
     pub fn indexer_changeset(&self) -> IndexerChangeSet {
         self.indexer.clone()
     }
+
+    /// Get the changes to the wallet labels, labels without content were removed.
+    pub fn labels(&self) -> Vec<Label> {
+        self.labels.clone()
+    }
 }
 
 impl From<ChangeSet> for bdk_wallet::ChangeSet {
@@ -1226,6 +1274,7 @@ impl From<bdk_wallet::ChangeSet> for ChangeSet {
             local_chain,
             tx_graph,
             indexer,
+            labels: Vec::new(),
         }
     }
 }
@@ -1242,6 +1291,8 @@ pub struct TxDetails {
     pub tx: Arc<Transaction>,
     pub can_rbf: bool,
     pub can_cpfp: bool,
+    /// BIP-329 label of the transaction.
+    pub label: Option<String>,
 }
 
 impl From<bdk_wallet::TxDetails> for TxDetails {
@@ -1257,6 +1308,7 @@ impl From<bdk_wallet::TxDetails> for TxDetails {
             tx: Arc::new(Transaction::from(details.tx.as_ref().clone())),
             can_rbf: false,
             can_cpfp: false,
+            label: None,
         }
     }
 }
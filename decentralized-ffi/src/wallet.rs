@@ -1,9 +1,10 @@
 use crate::bitcoin::{Amount, FeeRate, OutPoint, Psbt, Script, Transaction, TxOut, Txid};
 use crate::descriptor::Descriptor;
 use crate::error::{
-    CalculateFeeError, CannotConnectError, CreateWithPersistError, DescriptorError,
+    CalculateFeeError, CannotConnectError, CreateWithPersistError, DescriptorError, LabelError,
     LoadWithPersistError, PersistenceError, SignerError, TxidParseError,
 };
+use crate::labels::{normalize_reference, parse_labels, Label, LabelType, Labels};
 use crate::store::{PersistenceType, Persister};
 use crate::types::{
     AddressInfo, Balance, BlockId, CanonicalTx, FullScanRequestBuilder, KeychainAndIndex,
@@ -12,6 +13,7 @@ use crate::types::{
 };
 
 use bdk_wallet::bitcoin::Network;
+use bdk_wallet::chain::CanonicalizationParams;
 use bdk_wallet::signer::SignOptions as BdkSignOptions;
 use bdk_wallet::{KeychainKind, PersistedWallet, Wallet as BdkWallet};
 
@@ -36,6 +38,7 @@ use std::sync::{Arc, Mutex, MutexGuard};
 #[derive(uniffi::Object)]
 pub struct Wallet {
     inner_mutex: Mutex<PersistedWallet<PersistenceType>>,
+    labels: Mutex<Labels>,
 }
 
 #[uniffi::export]
@@ -67,6 +70,7 @@ impl Wallet {
 
         Ok(Wallet {
             inner_mutex: Mutex::new(wallet),
+            labels: Mutex::new(Labels::default()),
         })
     }
 
@@ -91,6 +95,7 @@ impl Wallet {
 
         Ok(Wallet {
             inner_mutex: Mutex::new(wallet),
+            labels: Mutex::new(Labels::default()),
         })
     }
 
@@ -117,9 +122,15 @@ impl Wallet {
                 error_message: e.to_string(),
             })?
             .ok_or(LoadWithPersistError::CouldNotLoad)?;
+        let labels = deref
+            .initialize_labels()
+            .map_err(|e| LoadWithPersistError::Persist {
+                error_message: e.to_string(),
+            })?;
 
         Ok(Wallet {
             inner_mutex: Mutex::new(wallet),
+            labels: Mutex::new(Labels::from_changeset(labels)),
         })
     }
 
@@ -145,9 +156,8 @@ impl Wallet {
     /// Returns the utxo owned by this wallet corresponding to `outpoint` if it exists in the
     /// wallet's database.
     pub fn get_utxo(&self, op: OutPoint) -> Option<LocalOutput> {
-        self.get_wallet()
-            .get_utxo(op.into())
-            .map(|local_output| local_output.into())
+        let utxo = self.get_wallet().get_utxo(op.into())?;
+        Some(self.labeled_output(utxo.into()))
     }
 
     /// Attempt to reveal the next address of the given `keychain`.
@@ -276,9 +286,33 @@ impl Wallet {
 
     /// Return the balance, separated into available, trusted-pending, untrusted-pending and
     /// immature values.
+    ///
+    /// Frozen UTXOs are left out of every category and reported as `frozen` instead, `total` still
+    /// includes them.
     pub fn balance(&self) -> Balance {
-        let bdk_balance = self.get_wallet().balance();
-        Balance::from(bdk_balance)
+        let frozen = self.get_labels().frozen();
+        let wallet = self.get_wallet();
+        let balance = |frozen_only: bool| {
+            wallet.tx_graph().balance(
+                wallet.local_chain(),
+                wallet.latest_checkpoint().block_id(),
+                CanonicalizationParams::default(),
+                wallet
+                    .spk_index()
+                    .outpoints()
+                    .iter()
+                    .filter(|(_, outpoint)| frozen.contains(outpoint) == frozen_only)
+                    .cloned(),
+                |&(keychain, _), _| keychain == KeychainKind::Internal,
+            )
+        };
+        let spendable = balance(false);
+        let frozen = balance(true).total();
+        Balance {
+            total: Arc::new((spendable.total() + frozen).into()),
+            frozen: Arc::new(frozen.into()),
+            ..Balance::from(spendable)
+        }
     }
 
     /// Return whether or not a `script` is part of this wallet (either internal or external).
@@ -361,6 +395,7 @@ impl Wallet {
 
     pub fn transaction_details(&self) -> Vec<TxDetails> {
         let wallet = self.get_wallet();
+        let labels = self.get_labels();
 
         wallet
             .transactions_sort_by(|tx1, tx2| tx2.chain_position.cmp(&tx1.chain_position))
@@ -390,8 +425,10 @@ impl Wallet {
                     .iter()
                     .any(|output| wallet.is_mine(output.script_pubkey.0.clone()));
 
+                let txid = tx.transaction.compute_txid();
                 TxDetails {
-                    txid: tx.transaction.compute_txid(),
+                    label: labels.text(LabelType::Tx, &txid.0.to_string()),
+                    txid,
                     sent: Arc::new(sent.0.into()),
                     received: Arc::new(sent.1.into()),
                     fee: fee.map(|f| Arc::new(f.into())),
@@ -465,14 +502,22 @@ impl Wallet {
 
     /// Return the list of unspent outputs of this wallet.
     pub fn list_unspent(&self) -> Vec<LocalOutput> {
-        self.get_wallet().list_unspent().map(|o| o.into()).collect()
+        let utxos = self.get_wallet().list_unspent().collect::<Vec<_>>();
+        utxos
+            .into_iter()
+            .map(|o| self.labeled_output(o.into()))
+            .collect()
     }
 
     /// List all relevant outputs (includes both spent and unspent, confirmed and unconfirmed).
     ///
     /// To list only unspent outputs (UTXOs), use [`Wallet::list_unspent`] instead.
     pub fn list_output(&self) -> Vec<LocalOutput> {
-        self.get_wallet().list_output().map(|o| o.into()).collect()
+        let outputs = self.get_wallet().list_output().collect::<Vec<_>>();
+        outputs
+            .into_iter()
+            .map(|o| self.labeled_output(o.into()))
+            .collect()
     }
 
     /// Create a [`FullScanRequest] for this wallet.
@@ -506,11 +551,21 @@ impl Wallet {
     pub fn persist(&self, persister: Arc<Persister>) -> Result<bool, PersistenceError> {
         let mut persist_lock = persister.inner.lock().unwrap();
         let deref = persist_lock.deref_mut();
-        self.get_wallet()
-            .persist(deref)
-            .map_err(|e| PersistenceError::Reason {
-                error_message: e.to_string(),
-            })
+        let mut persisted =
+            self.get_wallet()
+                .persist(deref)
+                .map_err(|e| PersistenceError::Reason {
+                    error_message: e.to_string(),
+                })?;
+
+        let mut labels = self.get_labels();
+        let staged = labels.staged();
+        if !staged.is_empty() {
+            deref.persist_labels(staged)?;
+            labels.clear_stage();
+            persisted = true;
+        }
+        Ok(persisted)
     }
 
     /// Returns the latest checkpoint.
@@ -520,9 +575,11 @@ impl Wallet {
 
     /// Get the [`TxDetails`] of a wallet transaction.
     pub fn tx_details(&self, txid: Arc<Txid>) -> Option<crate::types::TxDetails> {
-        self.get_wallet()
-            .tx_details(txid.0)
-            .map(|details| details.into())
+        let details = self.get_wallet().tx_details(txid.0)?;
+        Some(TxDetails {
+            label: self.get_labels().text(LabelType::Tx, &txid.0.to_string()),
+            ..details.into()
+        })
     }
 
     /// Returns the descriptor used to create addresses for a particular `keychain`.
@@ -552,7 +609,89 @@ impl Wallet {
             tx: Arc::new(tx.clone()),
             can_rbf: false,
             can_cpfp: false,
+            label: self
+                .get_labels()
+                .text(LabelType::Tx, &tx.0.compute_txid().to_string()),
+        }
+    }
+
+    /// Freeze `outpoint` so `TxBuilder` and `BumpFeeTxBuilder` never select it on their own.
+    ///
+    /// The freeze is kept as an output label with `spendable` set to false, staged until
+    /// [`Wallet::persist`] and exported with the other labels. Outputs added explicitly with
+    /// `TxBuilder::add_utxo` are still spent.
+    pub fn freeze_utxo(&self, outpoint: OutPoint) {
+        self.set_spendable(outpoint, Some(false));
+    }
+
+    /// Make a frozen `outpoint` spendable again.
+    pub fn unfreeze_utxo(&self, outpoint: OutPoint) {
+        self.set_spendable(outpoint, None);
+    }
+
+    /// List the frozen outpoints, spent ones included.
+    pub fn list_frozen(&self) -> Vec<OutPoint> {
+        self.get_labels()
+            .frozen()
+            .into_iter()
+            .map(OutPoint::from)
+            .collect()
+    }
+
+    /// Set a [BIP-329](https://github.com/bitcoin/bips/blob/master/bip-0329.mediawiki) label,
+    /// replacing the label with the same type and reference.
+    ///
+    /// A label without `label`, `origin` and `spendable` removes it. Label changes are staged like
+    /// any other wallet change and written by [`Wallet::persist`].
+    pub fn set_label(&self, label: Label) -> Result<(), LabelError> {
+        let label = label.normalize()?;
+        self.get_labels().insert(label);
+        Ok(())
+    }
+
+    /// Get the label of `label_type` for `reference`, if any.
+    pub fn get_label(&self, label_type: LabelType, reference: String) -> Option<Label> {
+        let reference = normalize_reference(label_type, &reference).ok()?;
+        self.get_labels().get(label_type, &reference).cloned()
+    }
+
+    /// Remove the label of `label_type` for `reference`.
+    pub fn remove_label(&self, label_type: LabelType, reference: String) -> Result<(), LabelError> {
+        self.set_label(Label {
+            label_type,
+            reference,
+            label: None,
+            origin: None,
+            spendable: None,
+        })
+    }
+
+    /// List all labels of the wallet.
+    pub fn labels(&self) -> Vec<Label> {
+        self.get_labels().iter().cloned().collect()
+    }
+
+    /// Export all labels as BIP-329 JSON lines.
+    pub fn export_labels(&self) -> String {
+        self.get_labels().export()
+    }
+
+    /// Import BIP-329 JSON lines, replacing existing labels with the same type and reference.
+    ///
+    /// Nothing is imported when any line is invalid. Records left without `label`, `origin` and
+    /// `spendable`, such as `spendable` on a transaction, are skipped, importing never removes a
+    /// label. Returns the number of imported labels.
+    pub fn import_labels(&self, jsonl: String) -> Result<u32, LabelError> {
+        let imported: Vec<Label> = parse_labels(&jsonl)?
+            .into_iter()
+            .filter(|label| !label.is_empty())
+            .collect();
+        let count = imported.len() as u32;
+        let mut labels = self.get_labels();
+        for label in imported {
+            labels.insert(label);
         }
+        Ok(count)
     }
 }
 
@@ -560,4 +699,34 @@ impl Wallet {
     pub(crate) fn get_wallet(&self) -> MutexGuard<'_, PersistedWallet<PersistenceType>> {
         self.inner_mutex.lock().expect("wallet")
     }
+
+    pub(crate) fn get_labels(&self) -> MutexGuard<'_, Labels> {
+        self.labels.lock().expect("labels")
+    }
+
+    fn set_spendable(&self, outpoint: OutPoint, spendable: Option<bool>) {
+        let reference = bdk_wallet::bitcoin::OutPoint::from(outpoint).to_string();
+        let mut labels = self.get_labels();
+        let label = labels
+            .get(LabelType::Output, &reference)
+            .cloned()
+            .unwrap_or(Label {
+                label_type: LabelType::Output,
+                reference,
+                label: None,
+                origin: None,
+                spendable: None,
+            });
+        labels.insert(Label { spendable, ..label });
+    }
+
+    fn labeled_output(&self, output: LocalOutput) -> LocalOutput {
+        let outpoint: bdk_wallet::bitcoin::OutPoint = output.outpoint.clone().into();
+        LocalOutput {
+            label: self
+                .get_labels()
+                .text(LabelType::Output, &outpoint.to_string()),
+            ..output
+        }
+    }
 }